@@ -0,0 +1,484 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::FuzzConfig;
+use crate::TransportProtocol;
+use crate::UdpSettings;
+use crate::TlsSettings;
+use crate::Timeouts;
+use crate::Swarm;
+use crate::BayesianOptimizer;
+use crate::ProcessSettings;
+use crate::FuzzRng;
+use crate::Framing;
+use crate::Engine;
+
+
+// A Campaign describes everything needed to run the fuzzer against a target: where the
+// target is, how to talk to it, which PCAP files seed the corpus, the FuzzConfig
+// hyperparameters and the settings of both hyperparameter optimization stages. It can be
+// loaded from and saved to a TOML or JSON file, chosen by the file extension.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Campaign {
+    pub target: TargetSettings,
+    #[serde(default)]
+    pub corpus: CorpusSettings,
+    #[serde(default = "Campaign::default_fuzz_config")]
+    pub fuzz: FuzzConfig,
+    #[serde(default)]
+    pub optimizer: OptimizerSettings,
+    #[serde(default)]
+    pub execution: ExecutionSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TargetSettings {
+    pub address: String,
+    #[serde(default = "TargetSettings::default_transport")]
+    pub transport: TransportProtocol,
+    pub protocol: String,
+    // Further instances of the same target which parallel workers are spread over
+    #[serde(default)]
+    pub additional_addresses: Vec<String>,
+    // Launch and supervise the target instead of connecting to an already running server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<ProcessSettings>,
+    // How responses are split from the stream, if not the way the protocol frames them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub framing: Option<Framing>,
+    // How datagrams are received if transport is udp
+    #[serde(default)]
+    pub udp: UdpSettings,
+    // How the target's certificate is verified if transport is tls or the protocol starts TLS
+    #[serde(default)]
+    pub tls: TlsSettings,
+    // The process launched for every connection if transport is pipe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipe: Option<ProcessSettings>,
+    // How long connecting, each response and each whole sequence may take
+    #[serde(default)]
+    pub timeouts: Timeouts,
+    // Whether to read a greeting the target sends on connecting, if not what the protocol says
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub greeting: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct CorpusSettings {
+    #[serde(default)]
+    pub pcap_files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct OptimizerSettings {
+    #[serde(default)]
+    pub pso: PsoSettings,
+    #[serde(default)]
+    pub bayesian: BayesianSettings,
+}
+
+// How MessageSequences are run against the target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutionSettings {
+    // Number of MessageSequences run at the same time
+    pub workers: usize,
+    // Whether the workers are threads or tasks on an async runtime
+    pub engine: Engine,
+    // Number of MessageSequences sent over one connection, for bugs which need a long session
+    pub sequences_per_connection: usize,
+    // Whether the protocol's reset message, e.g. SMTP's RSET, is sent between them
+    pub reset_between_sequences: bool,
+    // Whether mutation also places close, reconnect and wait steps between the messages
+    pub mutate_controls: bool,
+    // Whether mutation also fragments, slows down and pipelines the writes of messages
+    pub mutate_delivery: bool,
+    // The longest delay between two writes of a fragmented or slow message, in seconds
+    pub max_delivery_delay: f32,
+    // Seed of the random number generator, a random one is chosen if it is not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+// Arguments of Swarm::new
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PsoSettings {
+    pub swarm_size: usize,
+    pub iterations: usize,
+    pub fuzzer_generations: usize,
+    pub message_pool_size: usize,
+    pub inertial_weight: f32,
+    pub cognitive_weight: f32,
+    pub social_weight: f32,
+    pub regularization_strength: f32,
+    pub vmax: f32,
+}
+
+// Arguments of BayesianOptimizer::new which are not taken from the PSO stage
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BayesianSettings {
+    pub iterations: usize,
+    pub fuzzer_generations: usize,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    UnsupportedFormat(PathBuf),
+    Parse(PathBuf, String),
+    Serialize(String),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::UnsupportedFormat(path) => write!(f, "{}: campaign files must end in .toml or .json", path.display()),
+            ConfigError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Serialize(e) => write!(f, "could not serialize campaign: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid campaign: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// The supported campaign file formats, chosen by file extension
+enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Self, ConfigError> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Ok(Format::Toml),
+            Some("json") => Ok(Format::Json),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+}
+
+impl Campaign {
+    pub fn new(address: String, transport: TransportProtocol, protocol: String) -> Self {
+        Self {
+            target: TargetSettings { address, transport, protocol, additional_addresses: Vec::new(), process: None, framing: None, udp: UdpSettings::default(), tls: TlsSettings::default(), pipe: None, timeouts: Timeouts::default(), greeting: None },
+            corpus: CorpusSettings::default(),
+            fuzz: Campaign::default_fuzz_config(),
+            optimizer: OptimizerSettings::default(),
+            execution: ExecutionSettings::default(),
+        }
+    }
+
+    // These hyperparameters were found by a two-stage optimization run against SMTP and
+    // are used for any FuzzConfig field the campaign file leaves out
+    pub fn default_fuzz_config() -> FuzzConfig {
+        FuzzConfig {
+            generations: 20,
+            selection_pressure: 0.4208,
+            sequence_mutation_rate: 0.4129,
+            sequence_crossover_rate: 0.2749,
+            message_mutation_rate: 0.6024,
+            message_crossover_rate: 0.3268,
+            message_pool_size: 50,
+            pool_update_rate: 0.4425,
+            state_rarity_threshold: 0.4724,
+            state_coverage_weight: 0.3726,
+            response_time_weight: 0.7373,
+            state_roc_weight: 0.2529,
+            state_rarity_weight: 0.5072,
+        }
+    }
+
+    // Read, parse and validate a campaign file
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let format = Format::from_path(path)?;
+        let contents = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+
+        let campaign: Campaign = match format {
+            Format::Toml => toml::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))?,
+            Format::Json => serde_json::from_str(&contents).map_err(|e| ConfigError::Parse(path.to_path_buf(), e.to_string()))?,
+        };
+
+        campaign.validate()?;
+        Ok(campaign)
+    }
+
+    // Write the campaign to a file, in the format given by the file extension
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let contents = match Format::from_path(path)? {
+            Format::Toml => toml::to_string_pretty(self).map_err(|e| ConfigError::Serialize(e.to_string()))?,
+            Format::Json => serde_json::to_string_pretty(self).map_err(|e| ConfigError::Serialize(e.to_string()))?,
+        };
+
+        fs::write(path, contents).map_err(|e| ConfigError::Io(path.to_path_buf(), e))
+    }
+
+    // Check every field for values the fuzzer or the optimizers cannot work with and
+    // report the first offending field by its name in the campaign file
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let target = &self.target;
+        let expected_address = match target.transport {
+            TransportProtocol::UnixStream | TransportProtocol::UnixDatagram => "the path of a socket",
+            TransportProtocol::Pipe | TransportProtocol::InProcess | TransportProtocol::Custom(_) => "a name for the target",
+            _ => "host:port",
+        };
+
+        if !is_target_address(&target.transport, &target.address) {
+            return Err(invalid(format!("target.address must be {}, got {:?}", expected_address, target.address)));
+        }
+
+        for address in &target.additional_addresses {
+            if !is_target_address(&target.transport, address) {
+                return Err(invalid(format!("target.additional_addresses must be {}, got {:?}", expected_address, address)));
+            }
+        }
+
+        if target.protocol.trim().is_empty() {
+            return Err(invalid(String::from("target.protocol must not be empty")));
+        }
+
+        if let Some(process) = &target.process {
+            if process.program.trim().is_empty() {
+                return Err(invalid(String::from("target.process.program must not be empty")));
+            }
+            if !(process.startup_timeout > 0.0) {
+                return Err(invalid(format!("target.process.startup_timeout must be positive, got {}", process.startup_timeout)));
+            }
+            if process.stack_depth == 0 {
+                return Err(invalid(String::from("target.process.stack_depth must be at least 1")));
+            }
+            if self.execution.workers > 1 {
+                return Err(invalid(String::from("execution.workers must be 1 when the target is launched by target.process")));
+            }
+        }
+
+        match (&target.transport, &target.pipe) {
+            (TransportProtocol::Pipe, None) => {
+                return Err(invalid(String::from("target.pipe must name the program to launch when target.transport is pipe")));
+            }
+            (TransportProtocol::Pipe, Some(pipe)) => {
+                if pipe.program.trim().is_empty() {
+                    return Err(invalid(String::from("target.pipe.program must not be empty")));
+                }
+                if pipe.stack_depth == 0 {
+                    return Err(invalid(String::from("target.pipe.stack_depth must be at least 1")));
+                }
+                if target.process.is_some() {
+                    return Err(invalid(String::from("target.process cannot be used with the pipe transport, which launches target.pipe itself")));
+                }
+            }
+            (_, Some(_)) => return Err(invalid(String::from("target.pipe is only used when target.transport is pipe"))),
+            (_, None) => {}
+        }
+
+        if let Some(framing) = &target.framing {
+            framing.validate().map_err(|e| invalid(format!("target.framing: {}", e)))?;
+        }
+
+        if target.udp.receive_buffer == 0 {
+            return Err(invalid(String::from("target.udp.receive_buffer must be at least 1")));
+        }
+        if !(target.udp.quiet_period >= 0.0) {
+            return Err(invalid(format!("target.udp.quiet_period must not be negative, got {}", target.udp.quiet_period)));
+        }
+
+        let timeouts = &target.timeouts;
+        let mut seconds = vec![("target.timeouts.connect", timeouts.connect), ("target.timeouts.read", timeouts.read)];
+        seconds.extend(timeouts.sequence.map(|sequence| ("target.timeouts.sequence", sequence)));
+        for (field, value) in seconds {
+            if !(value > 0.0) {
+                return Err(invalid(format!("{} must be positive, got {}", field, value)));
+            }
+        }
+        for (message_type, value) in &timeouts.messages {
+            if !(*value > 0.0) {
+                return Err(invalid(format!("target.timeouts.messages.{} must be positive, got {}", message_type, value)));
+            }
+        }
+
+        let engine = self.execution.engine;
+        if !engine.is_available() {
+            return Err(invalid(String::from("execution.engine async needs fuzz_client built with the async feature")));
+        }
+        if !engine.supports(&target.transport) {
+            return Err(invalid(format!("execution.engine async cannot connect over target.transport {}", target.transport)));
+        }
+
+        for pcap_file in &self.corpus.pcap_files {
+            if !Path::new(pcap_file).is_file() {
+                return Err(invalid(format!("corpus.pcap_files: {} does not exist", pcap_file)));
+            }
+        }
+
+        let fuzz = &self.fuzz;
+        if fuzz.generations == 0 {
+            return Err(invalid(String::from("fuzz.generations must be at least 1")));
+        }
+        if fuzz.message_pool_size == 0 {
+            return Err(invalid(String::from("fuzz.message_pool_size must be at least 1")));
+        }
+
+        let unit_interval_fields = [
+            ("fuzz.selection_pressure", fuzz.selection_pressure),
+            ("fuzz.sequence_mutation_rate", fuzz.sequence_mutation_rate),
+            ("fuzz.sequence_crossover_rate", fuzz.sequence_crossover_rate),
+            ("fuzz.message_mutation_rate", fuzz.message_mutation_rate),
+            ("fuzz.message_crossover_rate", fuzz.message_crossover_rate),
+            ("fuzz.pool_update_rate", fuzz.pool_update_rate),
+            ("fuzz.state_rarity_threshold", fuzz.state_rarity_threshold),
+            ("fuzz.state_coverage_weight", fuzz.state_coverage_weight),
+            ("fuzz.response_time_weight", fuzz.response_time_weight),
+            ("fuzz.state_roc_weight", fuzz.state_roc_weight),
+            ("fuzz.state_rarity_weight", fuzz.state_rarity_weight),
+        ];
+
+        for (name, value) in unit_interval_fields {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(invalid(format!("{} must be between 0 and 1, got {}", name, value)));
+            }
+        }
+
+        let pso = &self.optimizer.pso;
+        let positive_counts = [
+            ("optimizer.pso.swarm_size", pso.swarm_size),
+            ("optimizer.pso.iterations", pso.iterations),
+            ("optimizer.pso.fuzzer_generations", pso.fuzzer_generations),
+            ("optimizer.pso.message_pool_size", pso.message_pool_size),
+            ("optimizer.bayesian.iterations", self.optimizer.bayesian.iterations),
+            ("optimizer.bayesian.fuzzer_generations", self.optimizer.bayesian.fuzzer_generations),
+            ("execution.workers", self.execution.workers),
+            ("execution.sequences_per_connection", self.execution.sequences_per_connection),
+        ];
+
+        for (name, value) in positive_counts {
+            if value == 0 {
+                return Err(invalid(format!("{} must be at least 1", name)));
+            }
+        }
+
+        if !(pso.vmax > 0.0 && pso.vmax <= 1.0) {
+            return Err(invalid(format!("optimizer.pso.vmax must be in (0, 1], got {}", pso.vmax)));
+        }
+
+        let non_negative_fields = [
+            ("optimizer.pso.inertial_weight", pso.inertial_weight),
+            ("optimizer.pso.cognitive_weight", pso.cognitive_weight),
+            ("optimizer.pso.social_weight", pso.social_weight),
+            ("optimizer.pso.regularization_strength", pso.regularization_strength),
+            ("execution.max_delivery_delay", self.execution.max_delivery_delay),
+        ];
+
+        for (name, value) in non_negative_fields {
+            if !(value >= 0.0) {
+                return Err(invalid(format!("{} must not be negative, got {}", name, value)));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Build the first optimization stage from the PSO settings. The particles start at random 
+    // positions drawn from rng
+    pub fn swarm(&self, rng: &mut FuzzRng) -> Swarm {
+        let pso = &self.optimizer.pso;
+        Swarm::new(
+            pso.swarm_size,
+            pso.iterations,
+            pso.fuzzer_generations,
+            pso.message_pool_size,
+            pso.inertial_weight,
+            pso.cognitive_weight,
+            pso.social_weight,
+            pso.regularization_strength,
+            pso.vmax,
+            rng,
+        )
+    }
+
+    // Build the second optimization stage, starting from the configs the swarm converged on
+    pub fn bayesian_optimizer(&self, pso_optimized_configs: &FuzzConfig) -> BayesianOptimizer {
+        let mut pso_optimized_configs = pso_optimized_configs.clone();
+        pso_optimized_configs.generations = self.optimizer.bayesian.fuzzer_generations;
+
+        BayesianOptimizer::new(
+            &pso_optimized_configs,
+            self.optimizer.bayesian.iterations,
+            self.optimizer.pso.swarm_size,
+            self.optimizer.pso.iterations,
+            self.optimizer.pso.fuzzer_generations,
+        )
+    }
+}
+
+impl TargetSettings {
+    fn default_transport() -> TransportProtocol {
+        TransportProtocol::TCP
+    }
+}
+
+impl Default for PsoSettings {
+    fn default() -> Self {
+        Self {
+            swarm_size: 10,
+            iterations: 10,
+            fuzzer_generations: 10,
+            message_pool_size: 50,
+            inertial_weight: 1.0,
+            cognitive_weight: 1.0,
+            social_weight: 1.0,
+            regularization_strength: 0.1,
+            vmax: 0.1,
+        }
+    }
+}
+
+impl Default for ExecutionSettings {
+    fn default() -> Self {
+        Self {
+            workers: 1,
+            engine: Engine::default(),
+            sequences_per_connection: 1,
+            reset_between_sequences: true,
+            mutate_controls: false,
+            mutate_delivery: false,
+            max_delivery_delay: 0.05,
+            seed: None,
+        }
+    }
+}
+
+impl Default for BayesianSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 20,
+            fuzzer_generations: 20,
+        }
+    }
+}
+
+fn is_target_address(transport: &TransportProtocol, address: &str) -> bool {
+    match transport {
+        TransportProtocol::UnixStream | TransportProtocol::UnixDatagram | TransportProtocol::Pipe | TransportProtocol::InProcess | TransportProtocol::Custom(_) => !address.trim().is_empty(),
+        _ => is_socket_address(address),
+    }
+}
+
+fn is_socket_address(address: &str) -> bool {
+    match address.rsplit_once(':') {
+        Some((host, port)) => !host.is_empty() && port.parse::<u16>().is_ok(),
+        None => false,
+    }
+}
+
+fn invalid(message: String) -> ConfigError {
+    ConfigError::Invalid(message)
+}
@@ -0,0 +1,389 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use pcap::{Capture, Linktype};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::ipv6::Ipv6Packet;
+use pnet::packet::tcp::{TcpFlags, TcpPacket};
+use pnet::packet::Packet;
+
+use crate::Error;
+use crate::Protocol;
+use crate::MessageSequence;
+use crate::Response;
+use crate::StateTransition;
+use crate::Framing;
+
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: [u16; 3] = [0x8100, 0x88a8, 0x9100];
+
+// IPv6 extension headers which are skipped on the way to the TCP header
+const IPV6_EXTENSION_HEADERS: [IpNextHeaderProtocol; 3] = [
+    IpNextHeaderProtocols::Hopopt,
+    IpNextHeaderProtocols::Ipv6Route,
+    IpNextHeaderProtocols::Ipv6Opts,
+];
+
+// Which side of a connection sent a Turn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ToServer,
+    ToClient,
+}
+
+// The bytes one side of a connection sent before the other side sent anything, i.e. a request
+// or a reply as the application on the other end read it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turn {
+    pub direction: Direction,
+    pub data: Vec<u8>,
+}
+
+// A TCP connection to the server found in a capture, with what both sides sent in the order
+// they sent it
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedConnection {
+    pub client: SocketAddr,
+    pub server: SocketAddr,
+    pub turns: Vec<Turn>,
+}
+
+impl CapturedConnection {
+    // What the client sent, one request per turn
+    pub fn requests(&self) -> impl Iterator<Item = &[u8]> {
+        self.turns.iter()
+            .filter(|turn| turn.direction == Direction::ToServer)
+            .map(|turn| turn.data.as_slice())
+    }
+}
+
+// Read every TCP connection to server_socket from a capture file, in the order they were opened.
+// Each direction of a connection is reassembled from its segments by sequence number, so
+// segments which arrive out of order are put back in order and retransmissions are dropped
+pub fn read_connections(pcap_file: &str, server_socket: &str) -> Result<Vec<CapturedConnection>, Error> {
+    let server = server_socket.parse::<SocketAddr>()
+        .map_err(|e| Error::Parse(format!("server socket {}: {}", server_socket, e)))?;
+
+    let mut capture = Capture::from_file(pcap_file).map_err(|e| Error::Pcap(PathBuf::from(pcap_file), e.to_string()))?;
+    let linktype = capture.get_datalink();
+    let mut reassembler = Reassembler::new(server);
+
+    loop {
+        match capture.next() {
+            Ok(packet) => reassembler.add_packet(linktype, packet.data),
+            Err(pcap::Error::NoMorePackets) => break,
+            Err(e) => return Err(Error::Pcap(PathBuf::from(pcap_file), e.to_string())),
+        }
+    }
+
+    Ok(reassembler.finish())
+}
+
+// One MessageSequence per connection, of the requests the client sent over it, for protocols
+// whose requests are exactly what the client sent in each turn
+pub fn request_sequences<P: Protocol>(protocol: &P, connections: &[CapturedConnection]) -> Vec<MessageSequence<P>> {
+    connections.iter().filter_map(|connection| {
+        let messages: Vec<_> = connection.requests().map(|request| protocol.build_message(request)).collect();
+        if messages.is_empty() {
+            return None;
+        }
+        let timings = vec![0.0; messages.len() - 1];
+        Some(MessageSequence::from_messages(messages, timings))
+    }).collect()
+}
+
+// The StateTransitions the captured connections went through: each request leads from the state
+// the server's previous reply, or its greeting, left it in to the state of the reply that answers
+// it. A reply turn holding several responses, as framing splits them, leaves the server in the
+// state of the last one. Requests sent before the server said anything and requests which were
+// never answered have no StateTransition
+pub fn state_transitions<P: Protocol>(protocol: &P, framing: &Framing, connections: &[CapturedConnection]) -> Vec<StateTransition<P::ServerState, P>> {
+    let mut state_transitions = Vec::new();
+
+    for connection in connections {
+        let mut state: Option<P::ServerState> = None;
+        let mut request = None;
+
+        for turn in &connection.turns {
+            match turn.direction {
+                Direction::ToServer => request = Some(protocol.build_message(&turn.data)),
+                Direction::ToClient => {
+                    let response = last_response(framing, &turn.data);
+                    let target_state = protocol.parse_response(&Response::new(response.to_vec()));
+
+                    if let (Some(source_state), Some(message)) = (state.take(), request.take()) {
+                        state_transitions.push(StateTransition {
+                            source_state,
+                            message,
+                            target_state: target_state.clone(),
+                            occurrences: 1,
+                        });
+                    }
+                    state = Some(target_state);
+                }
+            }
+        }
+    }
+
+    state_transitions
+}
+
+// The last of the responses framing splits data into. Bytes after the last complete response
+// are what the server sent before the capture ended, and count as a response of their own
+fn last_response<'a>(framing: &Framing, data: &'a [u8]) -> &'a [u8] {
+    let mut start = 0;
+    let mut last = data;
+    while start < data.len() {
+        match framing.frame_length(&data[start..]) {
+            Some(length) if length > 0 => {
+                last = &data[start..start + length];
+                start += length;
+            }
+            _ => return &data[start..],
+        }
+    }
+    last
+}
+
+// Sorts captured packets into the connections to one server and reassembles them. Packets of
+// other hosts, other protocols than TCP and IP fragments are ignored
+pub struct Reassembler {
+    server: SocketAddr,
+    connections: Vec<ConnectionState>,
+    // The connection each client address currently uses, an index into connections
+    open: HashMap<SocketAddr, usize>,
+}
+
+struct ConnectionState {
+    client: SocketAddr,
+    to_server: StreamState,
+    to_client: StreamState,
+    turns: Vec<Turn>,
+}
+
+// One direction of a connection
+#[derive(Default)]
+struct StreamState {
+    // The sequence number of the first payload byte, once known
+    initial_sequence: Option<u32>,
+    // The offset of the next byte the receiving application would read
+    next_offset: u32,
+    // Segments which arrived ahead of next_offset, by offset
+    pending: BTreeMap<u32, Vec<u8>>,
+}
+
+impl StreamState {
+    // Take in a segment and return the bytes which are now in order and were not seen before
+    fn add_segment(&mut self, sequence: u32, syn: bool, payload: &[u8]) -> Vec<u8> {
+        if syn {
+            // The SYN itself takes up one sequence number
+            self.initial_sequence.get_or_insert(sequence.wrapping_add(1));
+            return Vec::new();
+        }
+        if payload.is_empty() {
+            return Vec::new();
+        }
+
+        // Without the handshake in the capture the stream starts at its first payload
+        let initial_sequence = *self.initial_sequence.get_or_insert(sequence);
+        let offset = sequence.wrapping_sub(initial_sequence);
+
+        // A segment entirely before next_offset is a retransmission. Offsets past half the
+        // sequence space are taken to lie before the start, like TCP does
+        let end = offset.wrapping_add(payload.len() as u32);
+        if end.wrapping_sub(self.next_offset) as i32 <= 0 {
+            return Vec::new();
+        }
+
+        let pending = self.pending.entry(offset).or_default();
+        if payload.len() > pending.len() {
+            *pending = payload.to_vec();
+        }
+
+        let mut in_order = Vec::new();
+        while let Some((&offset, _)) = self.pending.iter().find(|(&offset, _)| (offset.wrapping_sub(self.next_offset) as i32) <= 0) {
+            let segment = self.pending.remove(&offset).unwrap();
+            let already_seen = self.next_offset.wrapping_sub(offset) as usize;
+            if already_seen < segment.len() {
+                in_order.extend_from_slice(&segment[already_seen..]);
+                self.next_offset = self.next_offset.wrapping_add((segment.len() - already_seen) as u32);
+            }
+        }
+        in_order
+    }
+
+    fn has_data(&self) -> bool {
+        self.next_offset > 0 || !self.pending.is_empty()
+    }
+}
+
+impl Reassembler {
+    pub fn new(server: SocketAddr) -> Self {
+        Self {
+            server,
+            connections: Vec::new(),
+            open: HashMap::new(),
+        }
+    }
+
+    // Add one captured packet, starting at the link layer header linktype says
+    pub fn add_packet(&mut self, linktype: Linktype, packet: &[u8]) {
+        let Some((ethertype, network)) = network_layer(linktype, packet) else { return };
+        let Some((source, destination, segment)) = transport_layer(ethertype, network) else { return };
+        let Some(tcp) = TcpPacket::new(segment) else { return };
+
+        let source = SocketAddr::new(source, tcp.get_source());
+        let destination = SocketAddr::new(destination, tcp.get_destination());
+        let (client, direction) = if self.is_server(destination) {
+            (source, Direction::ToServer)
+        } else if self.is_server(source) {
+            (destination, Direction::ToClient)
+        } else {
+            return;
+        };
+
+        let flags = tcp.get_flags();
+        let syn = flags & TcpFlags::SYN != 0;
+
+        // A new handshake from a client port which was used before starts a new connection
+        let reused = syn && flags & TcpFlags::ACK == 0 && self.open.get(&client)
+            .is_some_and(|&index| self.connections[index].to_server.has_data() || self.connections[index].to_client.has_data());
+
+        let index = match self.open.get(&client) {
+            Some(&index) if !reused => index,
+            _ => {
+                self.connections.push(ConnectionState {
+                    client,
+                    to_server: StreamState::default(),
+                    to_client: StreamState::default(),
+                    turns: Vec::new(),
+                });
+                self.open.insert(client, self.connections.len() - 1);
+                self.connections.len() - 1
+            }
+        };
+
+        let connection = &mut self.connections[index];
+        let stream = match direction {
+            Direction::ToServer => &mut connection.to_server,
+            Direction::ToClient => &mut connection.to_client,
+        };
+
+        let data = stream.add_segment(tcp.get_sequence(), syn, tcp.payload());
+        if data.is_empty() {
+            return;
+        }
+
+        // Bytes in the same direction as the last turn continue it
+        match connection.turns.last_mut() {
+            Some(turn) if turn.direction == direction => turn.data.extend(data),
+            _ => connection.turns.push(Turn { direction, data }),
+        }
+    }
+
+    // The connections seen so far, in the order they were opened, leaving out those over which
+    // nothing was sent
+    pub fn finish(self) -> Vec<CapturedConnection> {
+        let server = self.server;
+        self.connections.into_iter()
+            .filter(|connection| !connection.turns.is_empty())
+            .map(|connection| CapturedConnection {
+                client: connection.client,
+                server,
+                turns: connection.turns,
+            })
+            .collect()
+    }
+
+    // A server address with an unspecified IP, e.g. 0.0.0.0:25, stands for the port on every host
+    fn is_server(&self, address: SocketAddr) -> bool {
+        address.port() == self.server.port() && (self.server.ip().is_unspecified() || address.ip() == self.server.ip())
+    }
+}
+
+// The EtherType of the network layer of packet and where it starts, after the link layer
+// header of Ethernet with any VLAN tags, Linux cooked capture, BSD loopback or raw IP
+fn network_layer(linktype: Linktype, packet: &[u8]) -> Option<(u16, &[u8])> {
+    let (mut ethertype, mut offset) = match linktype {
+        Linktype::ETHERNET => (read_u16(packet, 12)?, 14),
+        Linktype::LINUX_SLL => (read_u16(packet, 14)?, 16),
+        Linktype::LINUX_SLL2 => (read_u16(packet, 0)?, 20),
+        // The address family, in the byte order of the capturing host for NULL
+        Linktype::NULL | Linktype::LOOP => {
+            let family: [u8; 4] = packet.get(..4)?.try_into().ok()?;
+            // Families are small, so a big-endian one starts with zeroes
+            let family = if linktype == Linktype::LOOP || family[..2] == [0, 0] {
+                u32::from_be_bytes(family)
+            } else {
+                u32::from_le_bytes(family)
+            };
+            // AF_INET is 2 everywhere, AF_INET6 is 24, 28 or 30 depending on the BSD
+            match family {
+                2 => (ETHERTYPE_IPV4, 4),
+                24 | 28 | 30 => (ETHERTYPE_IPV6, 4),
+                _ => return None,
+            }
+        }
+        Linktype::RAW | Linktype::IPV4 | Linktype::IPV6 => {
+            let ethertype = match packet.first()? >> 4 {
+                4 => ETHERTYPE_IPV4,
+                6 => ETHERTYPE_IPV6,
+                _ => return None,
+            };
+            (ethertype, 0)
+        }
+        _ => return None,
+    };
+
+    // Every VLAN tag is four bytes, ending in the EtherType of what follows it
+    while ETHERTYPE_VLAN.contains(&ethertype) && matches!(linktype, Linktype::ETHERNET | Linktype::LINUX_SLL | Linktype::LINUX_SLL2) {
+        ethertype = read_u16(packet, offset + 2)?;
+        offset += 4;
+    }
+
+    Some((ethertype, packet.get(offset..)?))
+}
+
+// The addresses of an IPv4 or IPv6 packet carrying TCP, and its TCP segment
+fn transport_layer(ethertype: u16, network: &[u8]) -> Option<(IpAddr, IpAddr, &[u8])> {
+    match ethertype {
+        ETHERTYPE_IPV4 => {
+            let ip = Ipv4Packet::new(network)?;
+            // Fragments would have to be put together first, which captures of a fuzzing target rarely need
+            let fragmented = ip.get_fragment_offset() != 0 || ip.get_flags() & 0b001 != 0;
+            if ip.get_next_level_protocol() != IpNextHeaderProtocols::Tcp || fragmented {
+                return None;
+            }
+            let header_length = ip.get_header_length() as usize * 4;
+            let end = (ip.get_total_length() as usize).min(network.len());
+            let segment = network.get(header_length..end)?;
+            Some((IpAddr::from(ip.get_source()), IpAddr::from(ip.get_destination()), segment))
+        }
+        ETHERTYPE_IPV6 => {
+            let ip = Ipv6Packet::new(network)?;
+            let end = (40 + ip.get_payload_length() as usize).min(network.len());
+            let mut next_header = ip.get_next_header();
+            let mut offset = 40;
+
+            while IPV6_EXTENSION_HEADERS.contains(&next_header) {
+                next_header = IpNextHeaderProtocol::new(*network.get(offset)?);
+                offset += (*network.get(offset + 1)? as usize + 1) * 8;
+            }
+
+            if next_header != IpNextHeaderProtocols::Tcp {
+                return None;
+            }
+            let segment = network.get(offset..end)?;
+            Some((IpAddr::from(ip.get_source()), IpAddr::from(ip.get_destination()), segment))
+        }
+        _ => None,
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?))
+}
@@ -0,0 +1,63 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::Protocol;
+use crate::Message;
+use crate::MessageSequence;
+use crate::StateModel;
+use crate::FuzzConfig;
+use crate::FuzzRng;
+use crate::CrashBucket;
+
+
+// The fitness statistics of one generation, as written to fitness.csv
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FitnessRecord {
+    pub generation: usize,
+    pub min_fitness: f32,
+    pub average_fitness: f32,
+    pub max_fitness: f32,
+}
+
+// A Checkpoint is a snapshot of everything Client::fuzz evolves: the corpus, the message pool
+// and the state model, together with the generation the run will continue from, the FuzzConfig
+// it was started with, the fitness history so far, the crashes found so far and the random number
+// generator. Checkpoints are stored as JSON.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Checkpoint<P: Protocol + PartialEq> {
+    pub generation: usize,
+    pub config: FuzzConfig,
+    pub corpus: Vec<MessageSequence<P>>,
+    pub message_pool: Vec<Message<P>>,
+    pub state_model: StateModel<P>,
+    pub fitness_history: Vec<FitnessRecord>,
+    // The unique crashes and hangs found before the checkpoint, so that the summary at the end of
+    // a resumed run still lists them. Older checkpoints have none
+    #[serde(default)]
+    pub crash_buckets: BTreeMap<String, CrashBucket>,
+    // The seed of the run and the state its random number generator had reached
+    pub seed: u64,
+    pub rng: FuzzRng,
+}
+
+impl<P: Protocol + PartialEq> Checkpoint<P> {
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    // The checkpoint is first written next to its destination and then renamed over it, so an
+    // interrupted write never leaves a truncated checkpoint behind
+    pub fn save<T: AsRef<Path>>(&self, path: T) -> Result<(), io::Error> {
+        let path = path.as_ref();
+        let temporary_path = path.with_extension("json.tmp");
+
+        fs::write(&temporary_path, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary_path, path)
+    }
+}
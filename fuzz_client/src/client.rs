@@ -12,29 +12,53 @@ use std::collections::HashSet;
 use std::cmp::PartialEq;
 use std::time::{Instant, Duration};
 use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::net::{TcpStream, Shutdown};
 use std::io::{self, BufRead, BufReader, Write};
-use std::error::Error;
 
 use csv::Writer;
 use csv::Reader;
 
+use serde::{Serialize, Deserialize};
+
 use rand::prelude::*;
 use rand::distributions::WeightedIndex;
 
 use crate::Protocol; 
-use crate::Message;
+use crate::{Message, Delivery};
 use crate::MessageSequence;
+use crate::{Control, ControlStep};
 use crate::StateTransition;
 use crate::StateModel;
 use crate::Response;
 use crate::Transport;
 use crate::TransportProtocol;
+use crate::TransportOptions;
+use crate::supervisor::EXIT_GRACE_PERIOD;
+use crate::Checkpoint;
+use crate::FitnessRecord;
+use crate::Trace;
+use crate::trace::state_after_control;
+use crate::Transcript;
+use crate::{Verdict, Outcome, Reproducer, CrashBucket};
+use crate::Supervisor;
+use crate::{FuzzRng, seeded_rng, random_seed};
+use crate::Error;
+use crate::Framing;
+use crate::Engine;
+use crate::{state_transitions, CapturedConnection};
+use crate::engine::{Sequencer, BlockingConnector, KeptConnection, block_on};
+#[cfg(feature = "async")]
+use crate::engine::AsyncRunner;
 
 use crate::GreetingProtocol;
 use crate::SMTP;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+// Fields a campaign file leaves out of its [fuzz] table keep the tuned hyperparameters
+#[serde(default = "crate::Campaign::default_fuzz_config")]
 pub struct FuzzConfig {
 	pub generations: usize,
 	pub selection_pressure: f32,
@@ -79,31 +103,79 @@ pub struct Client<P: Protocol + Clone + PartialEq> {
 	pub corpus: Vec<MessageSequence<P>>,
 	state_model: StateModel<P>,
 	message_pool: Vec<Message<P>>, 
+	pub output_directory: PathBuf,
+	// The campaign file the run was started from, which replay commands of reproducers refer to
+	pub campaign_file: Option<PathBuf>,
+	// A checkpoint is written to the output directory every checkpoint_interval generations, 0 disables them
+	pub checkpoint_interval: usize,
+	generation: usize,
+	fitness_history: Vec<FitnessRecord>,
+	// When set, the target is launched by the client and restarted whenever it dies
+	pub supervisor: Option<Supervisor>,
+	// Number of MessageSequences run at the same time, each over its own connection
+	pub workers: usize,
+	// How the workers run. Without the async feature, or over a transport the async engine
+	// does not support, the async engine falls back to threads
+	pub engine: Engine,
+	// Number of MessageSequences a worker sends over one connection before it opens a new one
+	pub sequences_per_connection: usize,
+	// Whether the protocol's reset message, if it has one, is sent between two sequences on the same connection
+	pub reset_between_sequences: bool,
+	// Whether mutation also inserts, removes and moves control steps such as closes and reconnects
+	pub mutate_controls: bool,
+	// Whether mutation also changes how messages are written, e.g. in fragments or pipelined
+	// with the next one, and the longest delay it puts between two writes, in seconds
+	pub mutate_delivery: bool,
+	pub max_delivery_delay: f32,
+	// Further instances of the target which the workers are spread over, round robin with server_address
+	pub additional_addresses: Vec<String>,
+	// How responses are split from the stream, the protocol's own framing unless overridden
+	pub framing: Framing,
+	// Settings of the datagram, TLS and pipe transports
+	pub transport_options: TransportOptions,
+	// Whether the greeting the target sends on connecting is read before the first message is
+	// sent, as the protocol says unless overridden
+	pub greeting: bool,
+	// Crashes and hangs of the current fuzzing run, grouped by Outcome::bucket
+	crash_buckets: BTreeMap<String, CrashBucket>,
+	// Every random decision is drawn from rng, which was seeded with seed
+	seed: u64,
+	rng: FuzzRng,
 }
 
 
-const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
-
 impl<P: Protocol + Clone + PartialEq> Client<P> {
-    // Initialize new client with random corpus and message_pool
+    // Initialize new client with random corpus and message_pool, drawn from a random seed
     pub fn new(server_address: String, transport_protocol: TransportProtocol, protocol: P) -> Self {
+        Self::with_seed(server_address, transport_protocol, protocol, random_seed())
+    }
+
+    // Initialize new client with random corpus and message_pool. Two clients with the same 
+    // seed start from the same corpus and make the same random decisions while fuzzing
+    pub fn with_seed(server_address: String, transport_protocol: TransportProtocol, protocol: P, seed: u64) -> Self {
         const MESSAGE_SEQUENCE_LENGTH: usize = 6;
         const MESSAGE_POOL_LENGTH: usize = 10;
         const INITIAL_CORPUS_LENGTH: usize = 5;
 
+        let mut rng = seeded_rng(seed);
+
         let mut corpus = Vec::new();
         for _ in 0..INITIAL_CORPUS_LENGTH {
             corpus.push(MessageSequence::random_message_sequence(
                 protocol.clone(),
                 MESSAGE_SEQUENCE_LENGTH,
+                &mut rng,
             ));
         }
 
         let mut message_pool = Vec::new();
         for _ in 0..MESSAGE_POOL_LENGTH {
-            message_pool.push(Message::random_message(protocol.clone()));
+            message_pool.push(Message::random_message(protocol.clone(), &mut rng));
         }
 
+        let framing = protocol.framing();
+        let greeting = protocol.sends_greeting();
+
         Self {
             server_address,
             transport_protocol,
@@ -111,93 +183,302 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
             corpus,
             state_model: StateModel::new(),
             message_pool,
+            output_directory: PathBuf::from("../resources"),
+            campaign_file: None,
+            checkpoint_interval: 0,
+            generation: 0,
+            fitness_history: Vec::new(),
+            supervisor: None,
+            workers: 1,
+            engine: Engine::default(),
+            sequences_per_connection: 1,
+            reset_between_sequences: true,
+            mutate_controls: false,
+            mutate_delivery: false,
+            max_delivery_delay: 0.05,
+            additional_addresses: Vec::new(),
+            framing,
+            transport_options: TransportOptions::default(),
+            greeting,
+            crash_buckets: BTreeMap::new(),
+            seed,
+            rng,
+        }
+    }
+
+    // Initialize client from a checkpoint written by a previous run of fuzz. The next call to fuzz 
+    // continues that run at the checkpoint's generation, with its corpus, message_pool, state_model 
+    // and fitness history. The random number generator and the crashes found so far continue from
+    // where they were as well
+    pub fn from_checkpoint(server_address: String, transport_protocol: TransportProtocol, protocol: P, checkpoint: Checkpoint<P>) -> Self {
+        let mut client = Self::with_seed(server_address, transport_protocol, protocol, checkpoint.seed);
+
+        client.corpus = checkpoint.corpus;
+        client.message_pool = checkpoint.message_pool;
+        client.state_model = checkpoint.state_model;
+        client.generation = checkpoint.generation;
+        client.fitness_history = checkpoint.fitness_history;
+        client.crash_buckets = checkpoint.crash_buckets;
+        client.rng = checkpoint.rng;
+
+        client
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // The optimizers draw their random numbers from the client's rng as well
+    pub fn rng(&mut self) -> &mut FuzzRng {
+        &mut self.rng
+    }
+
+    pub fn checkpoint_path(&self) -> PathBuf {
+        self.output_directory.join("checkpoint.json")
+    }
+
+    // A snapshot of the run so far, which from_checkpoint turns back into the same Client
+    pub fn checkpoint(&self, config: &FuzzConfig) -> Checkpoint<P> {
+        Checkpoint {
+            generation: self.generation,
+            config: config.clone(),
+            corpus: self.corpus.clone(),
+            message_pool: self.message_pool.clone(),
+            state_model: self.state_model.clone(),
+            fitness_history: self.fitness_history.clone(),
+            crash_buckets: self.crash_buckets.clone(),
+            seed: self.seed,
+            rng: self.rng.clone(),
         }
     }
 
+    fn save_checkpoint(&self, config: &FuzzConfig) -> Result<(), std::io::Error> {
+        self.checkpoint(config).save(self.checkpoint_path())
+    }
+
+    // The unique crashes and hangs of the current run, by Outcome::bucket
+    pub fn crash_buckets(&self) -> &BTreeMap<String, CrashBucket> {
+        &self.crash_buckets
+    }
+
+    // Fill the StateModel with the StateTransitions of connections read from a capture, each
+    // reply parsed by the protocol and each transition counted as often as it was seen, so that
+    // a run starts from the states and their frequencies in recorded traffic instead of from an
+    // empty model
+    pub fn seed_state_model(&mut self, connections: &[CapturedConnection]) {
+        let transitions = state_transitions(&self.protocol, &self.framing, connections);
+        self.update_state_model(transitions);
+    }
+
+    pub fn state_model(&self) -> &StateModel<P> {
+        &self.state_model
+    }
+
+    pub fn protocol(&self) -> &P {
+        &self.protocol
+    }
+
     // Initialize client with corpus pulled from PCAP file
     pub fn new_from_pcap(pcap_file: &str, protocol: P) -> Self {
     	todo!();
     }
 
-	fn initialize_transport(&self) -> Result<Transport, std::io::Error> {
-	    Transport::connect(self.transport_protocol.clone(), &self.server_address)
+	fn initialize_transport(&self, server_address: &str) -> Result<Box<dyn Transport>, Error> {
+	    self.transport_protocol.connect(server_address, &self.transport_options)
 	}
 
-	fn send_message(&mut self, transport: &mut Transport, message: &Message<P>) {
-		let msg = String::from_utf8_lossy(&message.data).to_string();
-	    transport.send(&message.data, &self.server_address);
+	fn send_message(&self, transport: &mut dyn Transport, message: &Message<P>) -> Result<(), Error> {
+	    transport.send(&message.data)
 	}
 
-	fn read_response(&mut self, transport: &mut Transport) -> Result<Response, std::io::Error> {
-	    transport.receive()
+	fn read_response(&self, transport: &mut dyn Transport, timeout: Duration) -> Result<Response, Error> {
+	    transport.receive(&self.framing, timeout)
 	}
 
-	// A new Transport Stream is created and destroyed for each MessageSequence
-	// Send every MessageSequence in the current corpus and collect the Message sent
-	// with the Responses received and return this collection
-	fn run_message_sequence(&mut self, message_sequence: &MessageSequence<P>) -> Vec<(Message<P>, Response)> {
-	    let mut transport = self.initialize_transport().expect("Failed to initialize transport");
-	    let mut message_response: Vec<(Message<P>, Response)> = Vec::new();
+	// Run a MessageSequence against the primary target, starting the target first if it is 
+	// supervised and no longer running, and check afterwards whether it survived
+	fn run_message_sequence(&mut self, message_sequence: &MessageSequence<P>, connection: &mut Option<KeptConnection<Box<dyn Transport>, P::ServerState>>) -> Trace<P> {
+	    // A target which died during the previous sequence is started again first
+	    if let Some(supervisor) = self.supervisor.as_mut() {
+	        if let Err(e) = supervisor.ensure_running() {
+	            *connection = None;
+	            let mut trace: Trace<P> = Trace::new();
+	            trace.outcome.record(Verdict::Refused, 0, format!("start target: {}", e));
+	            return trace;
+	        }
+	    }
 
-	    for (index, original_message) in message_sequence.messages.iter().enumerate() {
-	        self.send_message(&mut transport, original_message);
+	    let mut trace = self.execute_message_sequence(&self.server_address, message_sequence, connection);
+	    self.check_target(&mut trace);
+	    if trace.outcome.verdict != Verdict::Reply {
+	        *connection = None;
+	    }
+	    trace
+	}
 
-	        // Begin timer to track server's response time
-	        let start_time = Instant::now();
+	// Run a MessageSequence with the threads engine, which blocks the calling thread on its
+	// connection. How a sequence is run is up to Sequencer::execute_message_sequence
+	fn execute_message_sequence(&self, server_address: &str, message_sequence: &MessageSequence<P>, connection: &mut Option<KeptConnection<Box<dyn Transport>, P::ServerState>>) -> Trace<P> {
+	    let sequencer = Sequencer {
+	        protocol: &self.protocol,
+	        connector: BlockingConnector {
+	            transport_protocol: &self.transport_protocol,
+	            options: &self.transport_options,
+	            framing: &self.framing,
+	        },
+	        timeouts: &self.transport_options.timeouts,
+	        greeting: self.greeting,
+	        sequences_per_connection: self.sequences_per_connection,
+	        reset_between_sequences: self.reset_between_sequences,
+	    };
+	    block_on(sequencer.execute_message_sequence(server_address, message_sequence, connection))
+	}
 
-	        // Result is returned in case server crashes or hangs and reading from stream was not possible
-	        let response_result: Result<Response, std::io::Error> = self.read_response(&mut transport);
-	        let elapsed_time = start_time.elapsed();
+	// Run every MessageSequence and return their Traces in the same order as the sequences. With 
+	// more than one worker, each worker thread takes the next sequence not yet run and sends it to 
+	// its own target address, so the order the sequences finish in does not matter. A supervised 
+	// target only tells which sequence killed it if sequences are run one at a time
+	fn run_message_sequences(&mut self, message_sequences: &[MessageSequence<P>]) -> Vec<Trace<P>> {
+		let mut target_addresses = vec![self.server_address.clone()];
+		target_addresses.extend(self.additional_addresses.iter().cloned());
+
+		#[cfg(feature = "async")]
+		if self.engine == Engine::Async && self.engine.supports(&self.transport_protocol) && self.supervisor.is_none() {
+			let runner = AsyncRunner {
+				protocol: &self.protocol,
+				transport_protocol: &self.transport_protocol,
+				framing: &self.framing,
+				options: &self.transport_options,
+				greeting: self.greeting,
+				sequences_per_connection: self.sequences_per_connection,
+				reset_between_sequences: self.reset_between_sequences,
+			};
+			return runner.run(&target_addresses, self.workers, message_sequences);
+		}
 
-	        let mut message = original_message.clone(); // Clone the message to create a mutable copy
+		if self.workers <= 1 || self.supervisor.is_some() {
+			let mut connection = None;
+			return message_sequences.iter().map(|message_sequence| self.run_message_sequence(message_sequence, &mut connection)).collect();
+		}
 
-	        match response_result {
-	            Ok(response) => {
-	                message.response_time = elapsed_time.as_secs_f32();
-	                message_response.push((message, response));
-	            }
-	            Err(e) => {
-	                message.response_time = 5.0;
-	                message_response.push((message, Response::new(vec![])));
-	            }
-	        }
+		let next_index = AtomicUsize::new(0);
+		let mut traces: Vec<Option<Trace<P>>> = (0..message_sequences.len()).map(|_| None).collect();
+		let client = &*self;
+
+		thread::scope(|scope| {
+			let workers: Vec<_> = (0..self.workers).map(|worker| {
+				let server_address = &target_addresses[worker % target_addresses.len()];
+				let next_index = &next_index;
+
+				scope.spawn(move || {
+					let mut results = Vec::new();
+					let mut connection = None;
+					loop {
+						let index = next_index.fetch_add(1, Ordering::Relaxed);
+						if index >= message_sequences.len() {
+							break;
+						}
+						results.push((index, client.execute_message_sequence(server_address, &message_sequences[index], &mut connection)));
+					}
+					results
+				})
+			}).collect();
 
-	        // Wait message_sequence.timings[index] many seconds
-	        // before sending the next message
-	        if index < message_sequence.timings.len() {
-	            let sleep_duration: Duration = Duration::from_secs_f32(message_sequence.timings[index]);
-	            thread::sleep(sleep_duration);
-	        }
-	    }
+			for worker in workers {
+				for (index, trace) in worker.join().expect("Worker thread panicked") {
+					traces[index] = Some(trace);
+				}
+			}
+		});
 
-		/* 
-	    if let Err(e) = transport.shutdown() {
-	        eprintln!("Error shutting down transport: {}", e);
-	    }
-		*/
-	    message_response
+		traces.into_iter().map(|trace| trace.unwrap()).collect()
+	}
+
+	// Ask the supervisor, if there is one, whether the target survived the sequence. The kernel
+	// closes the sockets of a dying process, so a closed connection may be a crash as well
+	fn check_target(&mut self, trace: &mut Trace<P>) {
+		if let Some(supervisor) = self.supervisor.as_mut() {
+			let process_exit = if trace.outcome.verdict.is_crash() || trace.outcome.verdict == Verdict::Closed {
+				supervisor.wait_exit()
+			} else {
+				supervisor.poll_exit()
+			};
+
+			if let Some(process_exit) = process_exit {
+				let last_message_index = trace.interactions.len().saturating_sub(1);
+				trace.outcome.record_exit(process_exit, last_message_index);
+			}
+		}
+	}
+
+	// Save a MessageSequence which crashed or hung the target to the crashes/ or hangs/ 
+	// directory in the output directory and return the path of the reproducer
+	fn save_reproducer(&self, message_sequence: &MessageSequence<P>, trace: &Trace<P>) -> Result<PathBuf, std::io::Error> {
+		let mut reproducer = Reproducer {
+			target: self.server_address.clone(),
+			transport: self.transport_protocol.clone(),
+			outcome: trace.outcome.clone(),
+			replay_command: String::new(),
+			sequence: message_sequence.clone(),
+			states: trace.interactions.iter().map(|(_, response)| self.protocol.parse_response(response)).collect(),
+		};
+
+		reproducer.save(&self.output_directory, &self.protocol.name(), self.campaign_file.as_deref())
+	}
+
+	// Send a single MessageSequence with its original timings, e.g. one loaded from a reproducer,
+	// and return what was sent and received. If expected_states is not empty, the Transcript
+	// reports every message whose ServerState differs from it
+	pub fn replay(&mut self, message_sequence: &MessageSequence<P>, expected_states: &[P::ServerState]) -> Transcript<P> {
+		let trace = self.run_message_sequence(message_sequence, &mut None);
+		Transcript::from_trace(&self.protocol, trace, expected_states)
+	}
+
+	// Send every MessageSequence in the corpus once, without evolving it, and return 
+	// the Trace of each one in corpus order
+	pub fn run_corpus(&mut self) -> Vec<Trace<P>> {
+		let corpus = self.corpus.clone();
+		self.run_message_sequences(&corpus)
 	}
 
 	// Take the interection history (Vec<Message<P>, Response)>) of each MessageSequnce sent and 
 	// construct the resultant StateTransitions from this information and return a vector of all the 
-	// StateTransitions
-	fn process_trace(&mut self, corpus_trace: &[Vec<(Message<P>, Response)>]) -> (Vec<StateTransition<P::ServerState, P>>, Vec<usize>) {
+	// StateTransitions. The state the target greeted with is the source state of the first one, and
+	// on a kept connection the state the previous sequence left it in, followed by the reset message
+	fn process_trace(&mut self, corpus_trace: &[Trace<P>]) -> (Vec<StateTransition<P::ServerState, P>>, Vec<usize>) {
 	    let mut state_transitions: Vec<StateTransition<P::ServerState, P>> = Vec::new();
 
 	    // This vector will contain a count of the unique ServerStates prompted by each MessageSequence
 	    let mut unique_server_state_counts: Vec<usize> = Vec::new();
 
 
-	    for interaction_history in corpus_trace {
+	    for trace in corpus_trace {
 	        // Option is used here to represent the possibility of having
 	        // a server state or not since the previous state is unknown
-	        // at the beginning of an interaction history.
-	        let mut previous_server_state: Option<P::ServerState> = None;
+	        // at the beginning of an interaction history, unless the
+	        // target greeted before the first message or the sequence
+	        // continued where the previous one left the connection.
+	        let mut previous_server_state: Option<P::ServerState> = match &trace.greeting {
+	            Some(greeting) => Some(self.protocol.parse_response(greeting)),
+	            None => trace.continued_from.clone(),
+	        };
 
 	        // A HashSet is created for each interaction_history
 	        let mut unique_server_states: HashSet<P::ServerState> = HashSet::new();
+	        unique_server_states.extend(previous_server_state.clone());
+
+	        // The reset message is sent before any control step of the sequence
+	        let interactions = trace.reset.iter().map(|interaction| (None, interaction))
+	            .chain(trace.interactions.iter().enumerate().map(|(index, interaction)| (Some(index), interaction)));
+
+	        for (index, (message, response)) in interactions {
+	            // A control step leaves the target in a state no message led to, so no
+	            // StateTransition is made from the state before it
+	            for (step, received) in trace.controls.iter().filter(|(step, _)| Some(step.before) == index) {
+	                previous_server_state = state_after_control(&self.protocol, &step.control, received, previous_server_state);
+	                unique_server_states.extend(previous_server_state.clone());
+	            }
 
-	        for (message, response) in interaction_history {
 	            let target_state: P::ServerState = self.protocol.parse_response(&response);
 
 	            // The HashSet automatically deduplicates so we need not worry checking for repeats
@@ -210,6 +491,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 	                    source_state,
 	                    message: message.clone(),
 	                    target_state: target_state.clone(),
+	                    occurrences: 1,
 	                };
 	                state_transitions.push(state_transition);
 	            }
@@ -229,7 +511,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 	// Go through each StateTransition in the processed trace and use them to update state_model
     fn update_state_model(&mut self, state_transitions: Vec<StateTransition<P::ServerState, P>>) {
         for transition in state_transitions {
-            self.state_model.add(transition.source_state.clone(), transition.target_state.clone(), &transition.message);
+            self.state_model.insert(transition);
         }
     }
 
@@ -241,7 +523,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 
 	    // This will contain (key, value) pairs where key is defined as a particular ServerState
 	    // and value is the number of times that ServerState has been transitioned to out of all
-	    // transitions recorded in the state_model
+	    // transitions recorded in the state_model, counting every time a transition was seen
 	    let mut server_state_counts = HashMap::new();
 
 	    // This will be the sum of all the number of occurrances of each ServerState
@@ -251,11 +533,11 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 	    for transitions in self.state_model.inner.values() {
 	    	// Iterate over each StateTransition which will contain a "target_state"
 	    	// If that target_state is already present in server_state_counts as a key, increment
-	    	// the value it points to by 1. Otherwise, insert a new (target_state, occurrances)
+	    	// the value it points to by the times it was seen. Otherwise, insert a new (target_state, occurrances)
 	    	// (key, value) pair into the server_state_counts HashMap
 	        for state_transition in transitions {
-	            *server_state_counts.entry(state_transition.target_state.clone()).or_insert(0) += 1;
-	            total_server_state_occurrences += 1;
+	            *server_state_counts.entry(state_transition.target_state.clone()).or_insert(0) += state_transition.occurrences;
+	            total_server_state_occurrences += state_transition.occurrences;
 	        }
 	    }
 
@@ -273,7 +555,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 	    rare_server_states
 	}
 
-	fn tournament_selection(&self, selection_pressure: f32) -> Vec<usize> {
+	fn tournament_selection(&mut self, selection_pressure: f32) -> Vec<usize> {
 		// Selection pressure determines the tournament size
 		// The higher the pressure, the more biased the selection process
 		// is to selecting fitter individuals
@@ -281,7 +563,6 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 		let num_parents: usize = self.corpus.len();
 		let tournament_size: usize = (selection_pressure * (self.corpus.len() as f32)) as usize;
 
-		let mut rng = thread_rng();
     	let mut selected_indices: Vec<usize> = Vec::new();
 
     	// Each tournament gives back one "winner" who will be inserted into the mating pool. 
@@ -291,7 +572,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 
         	// Randomize the ordering of the tournament vector which contains the indices of 
         	// the MessageSequences in the corpus. Then truncate the tournament down to tournament_size
-        	tournament.shuffle(&mut rng);
+        	tournament.shuffle(&mut self.rng);
         	tournament.truncate(tournament_size);
 
 	        // Initialize a best index and best fitness
@@ -324,7 +605,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 	fn evaluate_fitness(
 		&mut self, 
 		corpus: &mut Vec<MessageSequence<P>>, 
-		corpus_trace: &Vec<Vec<(Message<P>, Response)>>,
+		corpus_trace: &Vec<Trace<P>>,
 		unique_server_states_visited: &[usize], 
 		rare_server_states: &HashSet<P::ServerState>,
 		state_coverage_weight: f32,
@@ -336,6 +617,14 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 		let total_unique_states = self.state_model.count_unique_server_states();
 
 	    for (i, message_sequence) in corpus.iter_mut().enumerate() {
+	    	// A sequence which never got a response (e.g. the target refused the connection) 
+	    	// teaches us nothing about the state space
+	    	let interactions = &corpus_trace[i].interactions;
+	    	if interactions.is_empty() || message_sequence.messages.is_empty() {
+	    		message_sequence.fitness = 0.0;
+	    		continue;
+	    	}
+
 	    	// The proportion of unique ServerStates prompted by an individual MessageSequence out of 
 			// all unique ServerStates vistted throughout the running of the program. This evaluates how well
 			// the MessageSequence contributes to exploring the entire state space of the server
@@ -350,17 +639,19 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
  			// at getting the server into rare states. We also get the average server_response_times
 	        let mut rare_states_count = 0;
 	        let mut response_time_score = 0.0;
-	        for (message, response) in &corpus_trace[i] {
-	        	response_time_score += message.response_time / 5.0;
+	        for (message, response) in interactions {
+	        	// Relative to the read timeout the message had, so the score stays between 0 and 1
+	        	let read_timeout = self.transport_options.timeouts.read_timeout(&self.protocol, message);
+	        	response_time_score += message.response_time / read_timeout.as_secs_f32();
 	            let target_state = self.protocol.parse_response(&response);
 	            if rare_server_states.contains(&target_state) {
 	                rare_states_count += 1;
 	            }
 	        }
 
-	        response_time_score = response_time_score / (corpus_trace[i].len() as f32);
+	        response_time_score = response_time_score / (interactions.len() as f32);
 
-	        let rarity_score = rare_states_count as f32 / corpus_trace[i].len() as f32;
+	        let rarity_score = rare_states_count as f32 / interactions.len() as f32;
 
 	        // Combine the three scores with their respective weights to compute the final fitness
 	        let fitness = coverage_score * state_coverage_weight
@@ -374,18 +665,21 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 
     // Go through each MessageSequence within the corpus and mutate it according to the mutation rate
     fn mutate_corpus(&mut self, message_sequence_mutation_rate: f32, message_mutation_rate: f32) {
-        let mut rng = rand::thread_rng();
-
         for message_sequence in &mut self.corpus {
-            if rng.gen::<f32>() < message_sequence_mutation_rate {
-                message_sequence.mutate_message_sequence(self.protocol.clone(), message_mutation_rate, &self.message_pool);
+            if self.rng.gen::<f32>() < message_sequence_mutation_rate {
+                message_sequence.mutate_message_sequence(self.protocol.clone(), message_mutation_rate, &self.message_pool, &mut self.rng);
+                if self.mutate_controls {
+                    message_sequence.mutate_controls(&mut self.rng);
+                }
+                if self.mutate_delivery {
+                    message_sequence.mutate_delivery(self.max_delivery_delay, &mut self.rng);
+                }
             }
         }
     }
 
     // Perform crossover on the MessageSequences within corpus
     fn crossover_corpus(&mut self, message_sequence_crossover_rate: f32, message_crossover_rate: f32) {
-        let mut rng = rand::thread_rng();
         let corpus_len = self.corpus.len();
         
         // Pairs of indices to perform crossover on
@@ -395,7 +689,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
         // all unique ordered pairs to be formed irrespective of ordering in the components
         for i in 0..corpus_len {
             for j in i + 1..corpus_len {
-                if rng.gen::<f32>() < message_sequence_crossover_rate {
+                if self.rng.gen::<f32>() < message_sequence_crossover_rate {
                     crossover_pairs.push((i, j));
                 }
             }
@@ -406,7 +700,7 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
         	let mut parent1 = self.corpus[idx1].clone();
         	let parent2 = self.corpus[idx2].clone();
 
-            let (offspring1, offspring2) = parent1.crossover_message_sequences(&parent2, message_crossover_rate);
+            let (offspring1, offspring2) = parent1.crossover_message_sequences(&parent2, message_crossover_rate, &mut self.rng);
             self.corpus[idx1] = offspring1;
             self.corpus[idx2] = offspring2;
         }
@@ -451,16 +745,27 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 		return (min_fitness, average_fitness, max_fitness);
 	}
 
-	pub fn evaluate(&mut self) -> f32 {
+	pub fn evaluate(&mut self) -> Result<f32, Error> {
 		// Calculate the slope of the best fit line which passes through the average fitness points recorded in the fitness.csv file
-		let mut rdr = Reader::from_path("../resources/fitness.csv").unwrap();
+		let fitness_file = self.output_directory.join("fitness.csv");
+		let io_error = |e: csv::Error| Error::Io(fitness_file.clone(), e.into());
+
+		let mut rdr = Reader::from_path(&fitness_file).map_err(io_error)?;
 		let mut x: Vec<f32> = Vec::new();
 		let mut y: Vec<f32> = Vec::new();
 
 		for result in rdr.records() {
-			let record = result.unwrap();
-			x.push(record[0].parse::<f32>().unwrap());
-			y.push(record[2].parse::<f32>().unwrap());
+			let record = result.map_err(io_error)?;
+			let generation = record.get(0).and_then(|field| field.parse::<f32>().ok());
+			let average_fitness = record.get(2).and_then(|field| field.parse::<f32>().ok());
+
+			match (generation, average_fitness) {
+				(Some(generation), Some(average_fitness)) => {
+					x.push(generation);
+					y.push(average_fitness);
+				}
+				_ => return Err(Error::Parse(format!("{}: malformed record {:?}", fitness_file.display(), record))),
+			}
 		}
 
         let n = x.len() as f32;
@@ -474,39 +779,50 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
         // apply the formula to calculate the slope
         let slope = (n * sum_xy - sum_x * sum_y) / (n * sum_x_squared - sum_x.powi(2));
 
-        slope
+        Ok(slope)
 	}
 
-	pub fn fuzz(&mut self, config: FuzzConfig, print_flag: bool) {
-		// Initialize a CSV writer that writes into a file named "fitness.csv"
-		let mut wtr = Writer::from_path("../resources/fitness.csv").unwrap();
+	// Run the genetic loop for config.generations generations. Crashes and hangs of the target are
+	// saved as reproducers and do not end the run, which only fails if its output can not be written
+	pub fn fuzz(&mut self, config: FuzzConfig, print_flag: bool) -> Result<(), Error> {
+		// Initialize a CSV writer that writes into a file named "fitness.csv" in the output directory
+		std::fs::create_dir_all(&self.output_directory).map_err(|e| Error::Io(self.output_directory.clone(), e))?;
+		let fitness_file = self.output_directory.join("fitness.csv");
+		let mut wtr = Writer::from_path(&fitness_file).map_err(|e| Error::Io(fitness_file.clone(), e.into()))?;
 
 		// Write the header
-		wtr.write_record(&["generation", "min_fitness", "average_fitness", "max_fitness"]).unwrap();
+		wtr.write_record(&["generation", "min_fitness", "average_fitness", "max_fitness"]).map_err(|e| Error::Io(fitness_file.clone(), e.into()))?;
 
-		for j in 0..config.generations {
+		// A fresh run starts at generation 0 while a run resumed from a checkpoint continues
+		// where it left off, so its fitness history is written out again first
+		if self.generation == 0 {
+			self.fitness_history.clear();
+			self.crash_buckets.clear();
+		}
+
+		for record in &self.fitness_history {
+			write_fitness_record(&mut wtr, record).map_err(|e| Error::Io(fitness_file.clone(), e))?;
+		}
+
+		for j in self.generation..config.generations {
 			if print_flag {
 				println!("GENERATION {}", j);
 			}
 
 			// If corpus ever becomes empty, we need to repopulate it
 			if self.corpus.is_empty() {
-				let mut rng = rand::thread_rng();
-				let num_message_sequences = rng.gen_range(2..10);
+				let num_message_sequences = self.rng.gen_range(2..10);
 
 				for _ in 0..num_message_sequences {
-					let sequence_len = rng.gen_range(1..10);
-					let message_sequence = MessageSequence::random_message_sequence(self.protocol.clone(), sequence_len);
+					let sequence_len = self.rng.gen_range(1..10);
+					let message_sequence = MessageSequence::random_message_sequence(self.protocol.clone(), sequence_len, &mut self.rng);
 					self.corpus.push(message_sequence);
 				}
 			}
 
 			let corpus_len: usize = self.corpus.len();
 			let mut message_sequence: MessageSequence<P>;
-			let mut interaction_history: Vec<(Message<P>, Response)>;
-			let mut corpus_trace: Vec<Vec<(Message<P>, Response)>> = Vec::new();
-
-			let mut rng = rand::thread_rng();
+			let mut trace: &Trace<P>;
 
 			// Iterate over the corpus and run each MessageSequence
 
@@ -514,20 +830,39 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 				println!("    RUNNING CORPUS ...");
 			}
 
+			let corpus = self.corpus.clone();
+			let corpus_trace: Vec<Trace<P>> = self.run_message_sequences(&corpus);
+
 			for i in 0..corpus_len {
 				message_sequence = self.corpus[i].clone();
-				interaction_history = self.run_message_sequence(&message_sequence);
-				corpus_trace.push(interaction_history);
+				trace = &corpus_trace[i];
+
+				// Keep a reproducer of every sequence which crashed or hung the target
+				if trace.outcome.is_interesting() {
+					match self.save_reproducer(&message_sequence, trace) {
+						Ok(path) => {
+							let bucket = self.crash_buckets.entry(trace.outcome.bucket())
+								.or_insert_with(|| CrashBucket::new(&trace.outcome, path.clone()));
+							bucket.hits += 1;
+
+							// Only the first crash of a sanitizer bucket is news
+							if print_flag && (bucket.hits == 1 || trace.outcome.sanitizer_report().is_none()) {
+								println!("    {:?} AFTER MESSAGE {}: {} -> {}", trace.outcome.verdict, trace.outcome.message_index, trace.outcome.detail, path.display());
+							}
+						}
+						Err(e) => eprintln!("Failed to save reproducer: {}", e),
+					}
+				}
 
 		        // Update message_pool with a random message from the current message_sequence at the defined rate
-    			if rng.gen_range(0.0..1.0) < config.pool_update_rate {
+    			if self.rng.gen_range(0.0..1.0) < config.pool_update_rate {
     				if self.message_pool.len() == config.message_pool_size && config.message_pool_size > 0 {
-    					let random_index = rng.gen_range(0..config.message_pool_size);
+    					let random_index = self.rng.gen_range(0..config.message_pool_size);
     					self.message_pool.remove(random_index);
     				}
 
 					if message_sequence.messages.len() > 0 {
-        				let random_message_idx = rng.gen_range(0..message_sequence.messages.len());
+        				let random_message_idx = self.rng.gen_range(0..message_sequence.messages.len());
         				let random_message = message_sequence.messages[random_message_idx].clone();
         				self.message_pool.push(random_message);
 					}
@@ -560,12 +895,14 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 
 			// Get fitness stats and save them to CSV.
 			let (min_fitness, avg_fitness, max_fitness) = self.get_fitness_stats(print_flag);
-			wtr.write_record(&[
-				j.to_string(),
-				min_fitness.to_string(),
-				avg_fitness.to_string(),
-				max_fitness.to_string(),
-			]).unwrap();
+			let record = FitnessRecord {
+				generation: j,
+				min_fitness,
+				average_fitness: avg_fitness,
+				max_fitness,
+			};
+			write_fitness_record(&mut wtr, &record).map_err(|e| Error::Io(fitness_file.clone(), e))?;
+			self.fitness_history.push(record);
 
 			// Apply crossover and mutation on the corpus to create the new generation
 			if print_flag {
@@ -573,13 +910,63 @@ impl<P: Protocol + Clone + PartialEq> Client<P> {
 			}
 			self.create_new_generation(&mating_pool, config.sequence_crossover_rate, config.sequence_mutation_rate, 
 									   config.message_crossover_rate, config.message_mutation_rate);
+
+			// The checkpoint holds the new generation, so resuming from it starts at the next one
+			self.generation = j + 1;
+			if self.checkpoint_interval > 0 && self.generation % self.checkpoint_interval == 0 {
+				if print_flag {
+					println!("    WRITING CHECKPOINT ...");
+				}
+				if let Err(e) = self.save_checkpoint(&config) {
+					eprintln!("Failed to write checkpoint: {}", e);
+				}
+			}
 		}
 
+		// The run is complete so the next call to fuzz starts a fresh one
+		self.generation = 0;
+
 		// After running the fuzzer...
 		let dot_string = self.state_model.to_dot_string();
-		std::fs::write("../resources/state_model.dot", dot_string).expect("Unable to write to file");
+		let dot_file = self.output_directory.join("state_model.dot");
+		std::fs::write(&dot_file, dot_string).map_err(|e| Error::Io(dot_file, e))?;
 
 		// Flush the writer to ensure all records are written to the file.
-		wtr.flush().unwrap();
+		wtr.flush().map_err(|e| Error::Io(fitness_file, e))?;
+
+		if print_flag {
+			self.print_crash_summary();
+		}
+
+		Ok(())
+	}
+
+	// Print one row per unique crash or hang of the run, most frequent first
+	fn print_crash_summary(&self) {
+		if self.crash_buckets.is_empty() {
+			println!("NO CRASHES OR HANGS");
+			return;
+		}
+
+		let mut buckets: Vec<(&String, &CrashBucket)> = self.crash_buckets.iter().collect();
+		buckets.sort_by(|a, b| b.1.hits.cmp(&a.1.hits));
+
+		println!("UNIQUE CRASHES AND HANGS: {}", buckets.len());
+		println!("    {:<6} {:<28} {:<40} {}", "HITS", "BUG CLASS", "TOP FRAME", "REPRODUCER");
+		for (_, bucket) in buckets {
+			let top_frame = if bucket.top_frame.is_empty() { "-" } else { &bucket.top_frame };
+			println!("    {:<6} {:<28} {:<40} {}", bucket.hits, bucket.bug_class, top_frame, bucket.reproducer.display());
+		}
 	}
-}
\ No newline at end of file
+}
+
+// Write one row of fitness.csv and flush it right away so the file stays complete if the run is interrupted
+fn write_fitness_record(wtr: &mut Writer<std::fs::File>, record: &FitnessRecord) -> Result<(), std::io::Error> {
+	wtr.write_record(&[
+		record.generation.to_string(),
+		record.min_fitness.to_string(),
+		record.average_fitness.to_string(),
+		record.max_fitness.to_string(),
+	])?;
+	wtr.flush()
+}
@@ -0,0 +1,44 @@
+use serde::{Serialize, Deserialize};
+
+use crate::TransportProtocol;
+
+mod sequence;
+mod threads;
+#[cfg(feature = "async")]
+mod runtime;
+
+pub(crate) use sequence::{Connector, Sequencer, KeptConnection, block_on};
+pub(crate) use threads::BlockingConnector;
+#[cfg(feature = "async")]
+pub(crate) use runtime::AsyncRunner;
+
+
+// How the client runs MessageSequences, set in [execution]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    // Every worker is a thread with a blocking connection, which sleeps through the timings
+    #[default]
+    Threads,
+    // Every worker is a task on a tokio runtime, so waiting for responses and between messages
+    // holds no thread and hundreds of sequences can run at once. Needs the async feature
+    Async,
+}
+
+impl Engine {
+    // Whether this build of the crate includes the engine
+    pub fn is_available(&self) -> bool {
+        match self {
+            Engine::Threads => true,
+            Engine::Async => cfg!(feature = "async"),
+        }
+    }
+
+    // Whether the engine can connect over transport_protocol
+    pub fn supports(&self, transport_protocol: &TransportProtocol) -> bool {
+        match self {
+            Engine::Threads => true,
+            Engine::Async => matches!(transport_protocol, TransportProtocol::TCP | TransportProtocol::UDP | TransportProtocol::UnixStream),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::cell::Cell;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+
+use tokio::time;
+
+use crate::Protocol;
+use crate::Error;
+use crate::MessageSequence;
+use crate::Trace;
+use crate::Response;
+use crate::Framing;
+use crate::{TransportProtocol, TransportOptions};
+use crate::transport::AsyncTransport;
+use super::{Connector, Sequencer};
+
+
+// Runs MessageSequences for a Client like the worker threads do, with the connections, read
+// timeouts and timings awaited on a tokio runtime instead of blocking a thread
+pub(crate) struct AsyncRunner<'a, P: Protocol> {
+    pub protocol: &'a P,
+    pub transport_protocol: &'a TransportProtocol,
+    pub framing: &'a Framing,
+    pub options: &'a TransportOptions,
+    pub greeting: bool,
+    pub sequences_per_connection: usize,
+    pub reset_between_sequences: bool,
+}
+
+// The connections of the async engine
+struct AsyncConnector<'a> {
+    transport_protocol: &'a TransportProtocol,
+    options: &'a TransportOptions,
+    framing: &'a Framing,
+}
+
+impl<P: Protocol> AsyncRunner<'_, P> {
+    // Run every MessageSequence and return their Traces in the same order as the sequences. Like
+    // the worker threads, each of the workers takes the next sequence not yet run and sends it
+    // to its own target address, but all of them share a single thread
+    pub fn run(&self, server_addresses: &[String], workers: usize, message_sequences: &[MessageSequence<P>]) -> Vec<Trace<P>> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to start the async runtime");
+
+        let sequencer = Sequencer {
+            protocol: self.protocol,
+            connector: AsyncConnector {
+                transport_protocol: self.transport_protocol,
+                options: self.options,
+                framing: self.framing,
+            },
+            timeouts: &self.options.timeouts,
+            greeting: self.greeting,
+            sequences_per_connection: self.sequences_per_connection,
+            reset_between_sequences: self.reset_between_sequences,
+        };
+
+        let next_index = Cell::new(0);
+        let workers: Vec<_> = (0..workers.max(1)).map(|worker| {
+            let server_address = &server_addresses[worker % server_addresses.len()];
+            let next_index = &next_index;
+            let sequencer = &sequencer;
+
+            async move {
+                let mut results = Vec::new();
+                let mut connection = None;
+                loop {
+                    let index = next_index.replace(next_index.get() + 1);
+                    if index >= message_sequences.len() {
+                        break;
+                    }
+                    results.push((index, sequencer.execute_message_sequence(server_address, &message_sequences[index], &mut connection).await));
+                }
+                results
+            }
+        }).collect();
+
+        let mut traces: Vec<Option<Trace<P>>> = (0..message_sequences.len()).map(|_| None).collect();
+        for (index, trace) in runtime.block_on(join_all(workers)).into_iter().flatten() {
+            traces[index] = Some(trace);
+        }
+
+        traces.into_iter().map(|trace| trace.unwrap()).collect()
+    }
+}
+
+impl<P: Protocol> Connector<P> for AsyncConnector<'_> {
+    type Connection = AsyncTransport;
+
+    async fn connect(&self, server_address: &str) -> Result<AsyncTransport, Error> {
+        AsyncTransport::connect(self.transport_protocol, server_address, self.options).await
+    }
+
+    async fn send(&self, transport: &mut AsyncTransport, data: &[u8]) -> Result<(), Error> {
+        transport.send(data).await
+    }
+
+    async fn receive(&self, transport: &mut AsyncTransport, timeout: Duration) -> Result<Response, Error> {
+        transport.receive(self.framing, timeout).await
+    }
+
+    async fn shutdown_write(&self, transport: &mut AsyncTransport) -> Result<(), Error> {
+        transport.shutdown_write().await
+    }
+
+    // Only the blocking TcpTransport can go on inside TLS
+    fn can_start_tls(&self) -> bool {
+        false
+    }
+
+    async fn start_tls(&self, _transport: &mut AsyncTransport) -> Result<(), Error> {
+        Err(Error::Parse(String::from("the async engine cannot start TLS")))
+    }
+
+    fn is_datagram(&self, transport: &AsyncTransport) -> bool {
+        transport.is_datagram()
+    }
+
+    // The connection is closed when it is dropped
+    async fn close(&self, _transport: &mut AsyncTransport) {}
+
+    // The async engine does not launch targets
+    fn check_process_exit(&self, _transport: &mut AsyncTransport, _trace: &mut Trace<P>) {}
+
+    // The timer only holds up this sequence, the others go on meanwhile
+    async fn sleep(&self, duration: Duration) {
+        time::sleep(duration).await;
+    }
+}
+
+// Run every future to completion on the task awaiting join_all and collect their outputs in
+// the order of futures. Each wake-up polls the futures which are not done yet
+async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut futures: Vec<Pin<Box<F>>> = futures.into_iter().map(Box::pin).collect();
+    let mut outputs: Vec<Option<F::Output>> = futures.iter().map(|_| None).collect();
+
+    future::poll_fn(|context| {
+        let mut done = true;
+        for (future, output) in futures.iter_mut().zip(outputs.iter_mut()) {
+            if output.is_none() {
+                match future.as_mut().poll(context) {
+                    Poll::Ready(value) => *output = Some(value),
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done { Poll::Ready(()) } else { Poll::Pending }
+    }).await;
+
+    outputs.into_iter().map(|output| output.unwrap()).collect()
+}
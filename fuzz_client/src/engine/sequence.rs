@@ -0,0 +1,389 @@
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::time::{Duration, Instant};
+
+use crate::Protocol;
+use crate::Error;
+use crate::{Message, Delivery};
+use crate::MessageSequence;
+use crate::{Control, ControlStep};
+use crate::Trace;
+use crate::Response;
+use crate::Verdict;
+use crate::Timeouts;
+
+
+// What the engines differ in: how a connection is opened, written, read and closed, and how
+// time is waited out. The threads engine blocks in every method, the async engine awaits.
+// Everything else about running a MessageSequence is done by Sequencer for both of them
+pub(crate) trait Connector<P: Protocol> {
+    type Connection;
+
+    async fn connect(&self, server_address: &str) -> Result<Self::Connection, Error>;
+    async fn send(&self, connection: &mut Self::Connection, data: &[u8]) -> Result<(), Error>;
+    async fn receive(&self, connection: &mut Self::Connection, timeout: Duration) -> Result<Response, Error>;
+    // Stop sending but keep receiving
+    async fn shutdown_write(&self, connection: &mut Self::Connection) -> Result<(), Error>;
+    // Whether the connection can go on inside a TLS session the target agreed to, and doing so
+    fn can_start_tls(&self) -> bool;
+    async fn start_tls(&self, connection: &mut Self::Connection) -> Result<(), Error>;
+    fn is_datagram(&self, connection: &Self::Connection) -> bool;
+    // Close the connection, which is dropped afterwards
+    async fn close(&self, connection: &mut Self::Connection);
+    // Record in trace how a target launched for this connection alone exited, if it did
+    fn check_process_exit(&self, connection: &mut Self::Connection, trace: &mut Trace<P>);
+    async fn sleep(&self, duration: Duration);
+}
+
+// A connection kept open for the next MessageSequence, with the state the last sequence left
+// the target in and the number of sequences sent over the connection so far
+pub(crate) struct KeptConnection<C, S> {
+    connection: C,
+    state: Option<S>,
+    sequences: usize,
+}
+
+// Runs MessageSequences over the connections of connector, the same way for every engine
+pub(crate) struct Sequencer<'a, P: Protocol, C> {
+    pub protocol: &'a P,
+    pub connector: C,
+    pub timeouts: &'a Timeouts,
+    pub greeting: bool,
+    pub sequences_per_connection: usize,
+    pub reset_between_sequences: bool,
+}
+
+impl<P: Protocol, C: Connector<P>> Sequencer<'_, P, C> {
+    // A new connection is opened for each MessageSequence, unless the sequence
+    // continues on the connection kept from the previous one of the same worker.
+    // Send every Message in the MessageSequence and collect the Message sent
+    // with the Response received. Each send and receive is judged by the oracle
+    // and the worst Verdict is kept as the Outcome of the Trace. Once the target
+    // has closed, reset or refused the connection the remaining messages are not sent.
+    // A sequence which takes longer than the sequence timeout is cut short as a hang.
+    // If the target speaks first, its greeting is read before the first message is sent,
+    // and a target which does not greet is judged as if it had not answered message 0.
+    // A connection on which the whole sequence was answered is kept in connection for
+    // the next sequence, until it has carried sequences_per_connection of them.
+    // The control steps of the sequence run before the message they are placed in front of
+    pub async fn execute_message_sequence(&self, server_address: &str, message_sequence: &MessageSequence<P>, connection: &mut Option<KeptConnection<C::Connection, P::ServerState>>) -> Trace<P> {
+        let mut trace: Trace<P> = Trace::new();
+        let timeouts = self.timeouts;
+        let deadline = timeouts.sequence_timeout().map(|sequence_timeout| Instant::now() + sequence_timeout);
+
+        let resumed = match connection.take() {
+            Some(kept) => self.resume_connection(kept, &mut trace, deadline).await,
+            None => None,
+        };
+        let (open, sequences) = match resumed {
+            Some(resumed) => resumed,
+            None => match self.open_connection(server_address, 0, &mut trace, deadline).await {
+                Some((open, greeting)) => {
+                    trace.greeting = greeting;
+                    (open, 0)
+                }
+                None => return trace,
+            },
+        };
+        let mut open = Some(open);
+        // Whether the sending side of the connection was shut down by a half-close
+        let mut half_closed = false;
+        // The index of the last message written so far, which is ahead of the one whose
+        // response is read next while messages are pipelined
+        let mut written_through: Option<usize> = None;
+
+        for index in 0..=message_sequence.messages.len() {
+            let controls = message_sequence.controls_before(index).cloned().collect();
+            if !self.run_controls(server_address, index, controls, &mut open, &mut half_closed, &mut trace, deadline).await {
+                break;
+            }
+
+            let Some(original_message) = message_sequence.messages.get(index) else {
+                break;
+            };
+
+            // A message after a close or half-close goes over a new connection
+            let must_reconnect = open.is_none() || half_closed;
+            if must_reconnect && !self.run_controls(server_address, index, vec![Control::Reconnect], &mut open, &mut half_closed, &mut trace, deadline).await {
+                break;
+            }
+            let Some(current) = open.as_mut() else {
+                break;
+            };
+
+            let mut read_timeout = timeouts.read_timeout(self.protocol, original_message);
+            if let Some(deadline) = deadline {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    trace.outcome.record(Verdict::Hang, index, format!("sequence took longer than {} s", timeouts.sequence.unwrap_or_default()));
+                    break;
+                }
+                read_timeout = read_timeout.min(remaining);
+            }
+
+            if !matches!(written_through, Some(end) if end >= index) {
+                let end = message_sequence.pipeline_end(index);
+                written_through = Some(end);
+                if let Err(e) = self.send_messages(current, &message_sequence.messages[index..=end], deadline).await {
+                    let verdict = self.confirm_verdict(server_address, Verdict::from_error(&e)).await;
+                    trace.outcome.record(verdict, index, e.to_string());
+                    break;
+                }
+            }
+
+            // Begin timer to track server's response time
+            let start_time = Instant::now();
+
+            // Result is returned in case server crashes or hangs and reading from stream was not possible
+            let response_result = self.connector.receive(current, read_timeout).await;
+            let elapsed_time = start_time.elapsed();
+
+            let mut message = original_message.clone();
+            let mut verdict = Verdict::from_response(&response_result, self.connector.is_datagram(current));
+
+            match response_result {
+                Ok(response) => {
+                    message.response_time = elapsed_time.as_secs_f32();
+                    trace.outcome.record(verdict, index, String::from("connection closed by target"));
+                    let starts_tls = verdict == Verdict::Reply && self.protocol.starts_tls(&message, &response);
+                    trace.interactions.push((message, response));
+
+                    // The remaining messages are sent inside the TLS session the target agreed to.
+                    // A connector which cannot do TLS ends the sequence with the reply which
+                    // agreed to it rather than with a false crash
+                    if starts_tls {
+                        if !self.connector.can_start_tls() {
+                            break;
+                        }
+                        if let Err(e) = self.connector.start_tls(current).await {
+                            verdict = self.confirm_verdict(server_address, Verdict::from_error(&e)).await;
+                            trace.outcome.record(verdict, index, e.to_string());
+                        }
+                    }
+                }
+                Err(e) => {
+                    verdict = self.confirm_verdict(server_address, verdict).await;
+                    trace.outcome.record(verdict, index, e.to_string());
+                    message.response_time = read_timeout.as_secs_f32();
+                    trace.interactions.push((message, Response::new(vec![])));
+                }
+            }
+
+            if verdict.is_crash() || verdict == Verdict::Closed {
+                break;
+            }
+
+            // Wait message_sequence.timings[index] many seconds before sending the next message
+            if index < message_sequence.timings.len() {
+                let sleep_duration = within_deadline(Duration::from_secs_f32(message_sequence.timings[index]), deadline);
+                self.connector.sleep(sleep_duration).await;
+            }
+        }
+
+        let Some(mut open) = open else {
+            return trace;
+        };
+        self.connector.check_process_exit(&mut open, &mut trace);
+
+        if trace.outcome.verdict == Verdict::Reply && !half_closed && sequences + 1 < self.sequences_per_connection {
+            *connection = Some(KeptConnection {
+                state: trace.final_state(self.protocol),
+                connection: open,
+                sequences: sequences + 1,
+            });
+        }
+        trace
+    }
+
+    // Connect and read the target's greeting, if it sends one. A failure to do either is
+    // recorded in trace as the outcome of the message at index, which was to be sent next
+    async fn open_connection(&self, server_address: &str, index: usize, trace: &mut Trace<P>, deadline: Option<Instant>) -> Option<(C::Connection, Option<Response>)> {
+        let mut open = match self.connector.connect(server_address).await {
+            Ok(open) => open,
+            Err(e) => {
+                trace.outcome.record(Verdict::from_error(&e), index, e.to_string());
+                return None;
+            }
+        };
+
+        let mut greeting = None;
+        if self.greeting {
+            let greeting_timeout = within_deadline(Duration::from_secs_f32(self.timeouts.read), deadline);
+            let greeting_result = self.connector.receive(&mut open, greeting_timeout).await;
+            let verdict = Verdict::from_response(&greeting_result, self.connector.is_datagram(&open));
+            match greeting_result {
+                Ok(response) if verdict == Verdict::Reply => greeting = Some(response),
+                result => {
+                    let detail = match result {
+                        Ok(_) => String::from("connection closed by target before its greeting"),
+                        Err(e) => format!("no greeting: {}", e),
+                    };
+                    trace.outcome.record(self.confirm_verdict(server_address, verdict).await, index, detail);
+                    self.connector.check_process_exit(&mut open, trace);
+                    return None;
+                }
+            }
+        }
+
+        Some((open, greeting))
+    }
+
+    // Run controls in front of the message at index on the connection in open, which is
+    // None once closed, and record each of them in trace. Returns false if the sequence ends
+    // here, because a reconnect failed or the target was found dead while waiting
+    #[allow(clippy::too_many_arguments)]
+    async fn run_controls(&self, server_address: &str, index: usize, controls: Vec<Control>, open: &mut Option<C::Connection>, half_closed: &mut bool, trace: &mut Trace<P>, deadline: Option<Instant>) -> bool {
+        for control in controls {
+            let mut received = Response::new(vec![]);
+
+            match &control {
+                Control::Close => self.close_connection(open, half_closed, trace).await,
+                Control::HalfClose => {
+                    if let Some(current) = open.as_mut() {
+                        if !*half_closed && self.connector.shutdown_write(current).await.is_err() {
+                            self.close_connection(open, half_closed, trace).await;
+                        }
+                        *half_closed = open.is_some();
+                    }
+                }
+                Control::Reconnect => {
+                    self.close_connection(open, half_closed, trace).await;
+                    match self.open_connection(server_address, index, trace, deadline).await {
+                        Some((new_connection, greeting)) => {
+                            *open = Some(new_connection);
+                            received = greeting.unwrap_or(received);
+                        }
+                        None => {
+                            trace.controls.push((ControlStep { before: index, control }, received));
+                            return false;
+                        }
+                    }
+                }
+                Control::WaitForData(seconds) => {
+                    let wait = within_deadline(Duration::from_secs_f32(*seconds), deadline);
+                    // Nothing is read once the sequence is out of time
+                    match open.as_mut().filter(|_| !wait.is_zero()) {
+                        Some(current) => match self.connector.receive(current, wait).await {
+                            // An empty response on a stream means the target closed the connection
+                            Ok(response) if response.data.is_empty() && !self.connector.is_datagram(current) => {
+                                self.close_connection(open, half_closed, trace).await;
+                            }
+                            Ok(response) => received = response,
+                            Err(e) if Verdict::from_error(&e) == Verdict::Hang => {}
+                            Err(e) => {
+                                let verdict = self.confirm_verdict(server_address, Verdict::from_error(&e)).await;
+                                if verdict == Verdict::Refused {
+                                    trace.outcome.record(verdict, index, e.to_string());
+                                    trace.controls.push((ControlStep { before: index, control }, received));
+                                    return false;
+                                }
+                                self.close_connection(open, half_closed, trace).await;
+                            }
+                        },
+                        None => self.connector.sleep(wait).await,
+                    }
+                }
+            }
+
+            trace.controls.push((ControlStep { before: index, control }, received));
+        }
+
+        true
+    }
+
+    // Close the connection in open, if it is still open, and check how a target launched
+    // for it exited
+    async fn close_connection(&self, open: &mut Option<C::Connection>, half_closed: &mut bool, trace: &mut Trace<P>) {
+        if let Some(mut closed) = open.take() {
+            self.connector.close(&mut closed).await;
+            self.connector.check_process_exit(&mut closed, trace);
+        }
+        *half_closed = false;
+    }
+
+    // Continue on a connection kept from the previous sequence, after sending the protocol's
+    // reset message if there is one. A connection which does not answer the reset is given up
+    // for a new one, since the previous sequence may well have ended it, e.g. with QUIT
+    async fn resume_connection(&self, kept: KeptConnection<C::Connection, P::ServerState>, trace: &mut Trace<P>, deadline: Option<Instant>) -> Option<(C::Connection, usize)> {
+        let KeptConnection { mut connection, state, sequences } = kept;
+
+        if let Some(reset_message) = self.protocol.reset_message().filter(|_| self.reset_between_sequences) {
+            let read_timeout = within_deadline(self.timeouts.read_timeout(self.protocol, &reset_message), deadline);
+            self.connector.send(&mut connection, &reset_message.data).await.ok()?;
+            let response_result = self.connector.receive(&mut connection, read_timeout).await;
+            if Verdict::from_response(&response_result, self.connector.is_datagram(&connection)) != Verdict::Reply {
+                return None;
+            }
+            trace.reset = Some((reset_message, response_result.ok()?));
+        }
+
+        trace.continued_from = state;
+        Some((connection, sequences))
+    }
+
+    // Write messages to the target, in a single write if several are pipelined, otherwise in the
+    // pieces the Delivery of the one message asks for
+    async fn send_messages(&self, connection: &mut C::Connection, messages: &[Message<P>], deadline: Option<Instant>) -> Result<(), Error> {
+        let data: Vec<u8> = messages.iter().flat_map(|message| message.data.iter().copied()).collect();
+        let delivery = match messages {
+            [message] => &message.delivery,
+            _ => &Delivery::Whole,
+        };
+
+        for (number, segment) in delivery.segments(&data).into_iter().enumerate() {
+            if number > 0 {
+                self.connector.sleep(within_deadline(delivery.delay(), deadline)).await;
+            }
+            self.connector.send(connection, segment).await?;
+        }
+        Ok(())
+    }
+
+    // A reset connection alone does not tell whether the target died, so try to connect
+    // again. If that is refused, nothing is listening anymore and the verdict is upgraded
+    async fn confirm_verdict(&self, server_address: &str, verdict: Verdict) -> Verdict {
+        if verdict != Verdict::Reset {
+            return verdict;
+        }
+
+        match self.connector.connect(server_address).await {
+            Err(e) if Verdict::from_error(&e) == Verdict::Refused => Verdict::Refused,
+            _ => verdict,
+        }
+    }
+}
+
+// Cut timeout short where it would run past the deadline of the sequence, if there is one
+pub(crate) fn within_deadline(timeout: Duration, deadline: Option<Instant>) -> Duration {
+    match deadline {
+        Some(deadline) => timeout.min(deadline.saturating_duration_since(Instant::now())),
+        None => timeout,
+    }
+}
+
+// Run a future whose every await completes right away, as those of a blocking Connector do
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = noop_waker();
+    let mut context = Context::from_waker(&waker);
+    match pin!(future).poll(&mut context) {
+        Poll::Ready(output) => output,
+        Poll::Pending => unreachable!("a blocking Connector never waits to be woken"),
+    }
+}
+
+// A waker which does nothing, since block_on never has to be woken. Built by hand rather than
+// with Waker::noop, which needs Rust 1.85
+fn noop_waker() -> Waker {
+    fn clone(_: *const ()) -> RawWaker {
+        noop_raw_waker()
+    }
+    fn noop(_: *const ()) {}
+    fn noop_raw_waker() -> RawWaker {
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    // The vtable functions ignore the data pointer, so a null one is never dereferenced
+    unsafe { Waker::from_raw(noop_raw_waker()) }
+}
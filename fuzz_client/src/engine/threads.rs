@@ -0,0 +1,75 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::Protocol;
+use crate::Error;
+use crate::Trace;
+use crate::Response;
+use crate::Verdict;
+use crate::Framing;
+use crate::{Transport, TransportProtocol, TransportOptions};
+use crate::supervisor::EXIT_GRACE_PERIOD;
+use super::Connector;
+
+
+// The connections of the threads engine, blocking Transports which hold up the worker thread
+// while they connect, read and wait
+pub(crate) struct BlockingConnector<'a> {
+    pub transport_protocol: &'a TransportProtocol,
+    pub options: &'a TransportOptions,
+    pub framing: &'a Framing,
+}
+
+impl<P: Protocol> Connector<P> for BlockingConnector<'_> {
+    type Connection = Box<dyn Transport>;
+
+    async fn connect(&self, server_address: &str) -> Result<Box<dyn Transport>, Error> {
+        self.transport_protocol.connect(server_address, self.options)
+    }
+
+    async fn send(&self, transport: &mut Box<dyn Transport>, data: &[u8]) -> Result<(), Error> {
+        transport.send(data)
+    }
+
+    async fn receive(&self, transport: &mut Box<dyn Transport>, timeout: Duration) -> Result<Response, Error> {
+        transport.receive(self.framing, timeout)
+    }
+
+    async fn shutdown_write(&self, transport: &mut Box<dyn Transport>) -> Result<(), Error> {
+        transport.shutdown_write()
+    }
+
+    fn can_start_tls(&self) -> bool {
+        true
+    }
+
+    async fn start_tls(&self, transport: &mut Box<dyn Transport>) -> Result<(), Error> {
+        transport.start_tls()
+    }
+
+    fn is_datagram(&self, transport: &Box<dyn Transport>) -> bool {
+        transport.is_datagram()
+    }
+
+    async fn close(&self, transport: &mut Box<dyn Transport>) {
+        let _ = transport.shutdown();
+    }
+
+    // A target launched for this connection alone is checked for how it exited, like a supervised one
+    fn check_process_exit(&self, transport: &mut Box<dyn Transport>, trace: &mut Trace<P>) {
+        let grace_period = if trace.outcome.verdict.is_crash() || trace.outcome.verdict == Verdict::Closed { EXIT_GRACE_PERIOD } else { Duration::ZERO };
+        if let Some(process_exit) = transport.process_exit(grace_period) {
+            if process_exit.is_crash() {
+                let last_message_index = trace.interactions.len().saturating_sub(1);
+                trace.outcome.record_exit(process_exit, last_message_index);
+            }
+        }
+    }
+
+    // An in-process handler only sees the order of its requests, so it is not kept waiting
+    async fn sleep(&self, duration: Duration) {
+        if *self.transport_protocol != TransportProtocol::InProcess {
+            thread::sleep(duration);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::ConfigError;
+
+
+// Everything the library can fail with. Nothing in it aborts the process: a failed connect,
+// send or receive is handed to the caller, which decides whether to retry, skip the sequence
+// or give up. While fuzzing, these are judged by the oracle rather than treated as errors.
+#[derive(Debug)]
+pub enum Error {
+    // Connecting to the address failed
+    Connect(String, io::Error),
+    Send(io::Error),
+    Receive(io::Error),
+    // Input which could not be understood, e.g. a server address or a record in fitness.csv
+    Parse(String),
+    // A PCAP file which could not be read
+    Pcap(PathBuf, String),
+    Config(ConfigError),
+    // Reading or writing one of the files of a run, e.g. fitness.csv or state_model.dot
+    Io(PathBuf, io::Error),
+    // An optimizer arrived at hyperparameters it cannot sample from
+    Optimizer(String),
+    // TLS settings which cannot be turned into a client configuration, e.g. an unreadable ca_file
+    Tls(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    // The I/O error behind a failed connect, send or receive, which tells the oracle what
+    // happened to the target
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            Error::Connect(_, e) | Error::Send(e) | Error::Receive(e) | Error::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Connect(address, e) => write!(f, "connect to {}: {}", address, e),
+            Error::Send(e) => write!(f, "send: {}", e),
+            Error::Receive(e) => write!(f, "receive: {}", e),
+            Error::Parse(e) => write!(f, "parse: {}", e),
+            Error::Pcap(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Config(e) => write!(f, "{}", e),
+            Error::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            Error::Optimizer(e) => write!(f, "optimizer: {}", e),
+            Error::Tls(e) => write!(f, "tls: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Config(e) => Some(e),
+            _ => self.io_error().map(|e| e as &(dyn std::error::Error + 'static)),
+        }
+    }
+}
+
+impl From<ConfigError> for Error {
+    fn from(e: ConfigError) -> Self {
+        Error::Config(e)
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+
+// Framing tells a stream Transport where one response ends and the next one begins. Each
+// Protocol picks the Framing its replies use, and a campaign can override it in [target.framing].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Framing {
+    // A response ends with the delimiter, which is kept in the response
+    Delimiter { delimiter: String },
+    // SMTP-style replies: "250-..." lines continue the reply until a "250 ..." line ends it
+    SmtpMultiline,
+    // A header of header_size bytes holding the length of the body in length_size bytes at
+    // length_offset, e.g. the 4-byte type and 8-byte big-endian length of srv's messages
+    LengthPrefixed {
+        length_offset: usize,
+        length_size: usize,
+        header_size: usize,
+        #[serde(default)]
+        little_endian: bool,
+        // Whether the length counts the header as well as the body
+        #[serde(default)]
+        length_includes_header: bool,
+    },
+    // Every response is exactly size bytes long
+    FixedSize { size: usize },
+    // A response ends once the target has sent nothing for quiet_period seconds
+    Idle { quiet_period: f32 },
+}
+
+impl Framing {
+    pub fn line() -> Self {
+        Framing::Delimiter { delimiter: String::from("\n") }
+    }
+
+    // The length of the first complete response at the start of buffer, or None if more
+    // bytes are needed. Idle framing never knows, the response ends when the target goes quiet.
+    // A framing which fails validate never panics here, it just finds no response, and an
+    // empty response is never complete
+    pub fn frame_length(&self, buffer: &[u8]) -> Option<usize> {
+        let frame_length = match self {
+            Framing::Delimiter { delimiter } if delimiter.is_empty() => None,
+            Framing::Delimiter { delimiter } => {
+                let delimiter = delimiter.as_bytes();
+                buffer.windows(delimiter.len())
+                    .position(|window| window == delimiter)
+                    .map(|position| position + delimiter.len())
+            }
+            Framing::SmtpMultiline => {
+                let mut start = 0;
+                while let Some(newline) = buffer[start..].iter().position(|&byte| byte == b'\n') {
+                    let line = &buffer[start..start + newline];
+                    start += newline + 1;
+
+                    // Only a three digit code followed by a hyphen continues the reply
+                    let continues = line.len() >= 4 && line[..3].iter().all(u8::is_ascii_digit) && line[3] == b'-';
+                    if !continues {
+                        return Some(start);
+                    }
+                }
+                None
+            }
+            Framing::LengthPrefixed { length_offset, length_size, header_size, little_endian, length_includes_header } => {
+                if buffer.len() < *header_size {
+                    return None;
+                }
+
+                let mut length_bytes = buffer.get(*length_offset..length_offset.checked_add(*length_size)?)?.to_vec();
+                if *little_endian {
+                    length_bytes.reverse();
+                }
+                let length = length_bytes.iter().fold(0u64, |length, &byte| (length << 8) | byte as u64);
+
+                let frame_length = if *length_includes_header {
+                    length.max(*header_size as u64)
+                } else {
+                    length.saturating_add(*header_size as u64)
+                };
+
+                // A length the target cannot mean is read until the target stops sending
+                usize::try_from(frame_length).ok().filter(|&frame_length| buffer.len() >= frame_length)
+            }
+            Framing::FixedSize { size } => Some(*size).filter(|&size| buffer.len() >= size),
+            Framing::Idle { .. } => None,
+        };
+        frame_length.filter(|&frame_length| frame_length > 0)
+    }
+
+    // How long the target may stay silent in the middle of a response before it is considered complete
+    pub fn quiet_period(&self) -> Option<Duration> {
+        match self {
+            Framing::Idle { quiet_period } => Some(Duration::from_secs_f32(*quiet_period)),
+            _ => None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Framing::Delimiter { delimiter } if delimiter.is_empty() => Err(String::from("delimiter must not be empty")),
+            Framing::LengthPrefixed { length_size, .. } if !(1..=8).contains(length_size) => {
+                Err(format!("length_size must be between 1 and 8 bytes, not {}", length_size))
+            }
+            Framing::LengthPrefixed { length_offset, length_size, header_size, .. } if length_offset + length_size > *header_size => {
+                Err(format!("the length at offset {} with {} bytes does not fit in a {} byte header", length_offset, length_size, header_size))
+            }
+            Framing::FixedSize { size } if *size == 0 => Err(String::from("size must be positive")),
+            Framing::Idle { quiet_period } if !(*quiet_period > 0.0) => Err(format!("quiet_period must be positive, not {}", quiet_period)),
+            _ => Ok(()),
+        }
+    }
+}
@@ -7,6 +7,19 @@ mod state_transition;
 mod state_model;
 mod transport;
 mod optimization;
+mod campaign;
+mod checkpoint;
+mod oracle;
+mod trace;
+mod transcript;
+mod minimize;
+mod supervisor;
+mod sanitizer;
+mod rng;
+mod error;
+mod framing;
+mod capture;
+mod engine;
 
 pub use protocols::Protocol;
 pub use protocols::GreetingProtocol;
@@ -15,11 +28,27 @@ pub use protocols::SMTP;
 pub use optimization::Swarm;
 pub use optimization::BayesianOptimizer;
 
-pub use transport::{Transport, TransportProtocol};
-pub use message::Message;
-pub use message_sequence::MessageSequence;
+pub use transport::{Transport, TransportProtocol, TransportOptions, TransportFactory, UdpSettings, Timeouts};
+pub use transport::{TcpTransport, UdpTransport, UnixStreamTransport, UnixDatagramTransport, PipeTransport, InProcessTransport};
+pub use transport::{TlsSettings, TlsConnector};
+pub use transport::{Handler, HandlerFactory};
+pub use framing::Framing;
+pub use capture::{read_connections, request_sequences, state_transitions, Reassembler, CapturedConnection, Turn, Direction};
+pub use engine::Engine;
+pub use message::{Message, Delivery};
+pub use message_sequence::{MessageSequence, Control, ControlStep};
 pub use client::Client;
 pub use response::Response;
 pub use state_transition::StateTransition;
 pub use state_model::StateModel;
 pub use client::FuzzConfig;
+pub use campaign::{Campaign, TargetSettings, CorpusSettings, OptimizerSettings, PsoSettings, BayesianSettings, ExecutionSettings, ConfigError};
+pub use checkpoint::{Checkpoint, FitnessRecord};
+pub use oracle::{Verdict, Outcome, Reproducer, CrashBucket};
+pub use trace::Trace;
+pub use transcript::{Transcript, TranscriptEntry};
+pub use minimize::{minimize, MinimizationGoal};
+pub use supervisor::{Supervisor, ProcessSettings, ProcessExit};
+pub use sanitizer::SanitizerReport;
+pub use rng::{FuzzRng, seeded_rng, random_seed};
+pub use error::{Error, Result};
@@ -2,9 +2,10 @@
 #![allow(unused_imports)]
 #![allow(unused_variables)]
 
-use std::num;
-use std::str::from_utf8;
-use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command as ProcessCommand;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 use fuzz_client::Client;
 use fuzz_client::GreetingProtocol;
@@ -12,109 +13,758 @@ use fuzz_client::SMTP;
 use fuzz_client::FuzzConfig;
 use fuzz_client::MessageSequence;
 use fuzz_client::TransportProtocol;
+use fuzz_client::Engine;
+use fuzz_client::TlsConnector;
+use fuzz_client::TransportOptions;
 use fuzz_client::Protocol;
 use fuzz_client::Swarm;
 use fuzz_client::BayesianOptimizer;
+use fuzz_client::Campaign;
+use fuzz_client::Checkpoint;
+use fuzz_client::Reproducer;
+use fuzz_client::{minimize, MinimizationGoal};
+use fuzz_client::Supervisor;
+use fuzz_client::ProcessSettings;
+use fuzz_client::random_seed;
+use fuzz_client::Error;
+use fuzz_client::read_connections;
 
 
-fn optimize_hyperparameters<P: Protocol+PartialEq>(client: &mut Client<P>) -> FuzzConfig {
-    // Create instance of Swarm
-    let pso_swarm_size = 10;
-    let pso_fuzzer_generations = 10;
-    let pso_iterations = 10;
-    let message_pool_size = 50;
-    let inertial_weight = 1.0;
-    let cognitive_weight = 1.0;
-    let social_weight = 1.0;
-    let regularization_strength = 0.1;
-    let vmax = 0.1;
-
-    let mut swarm = Swarm::new(
-        pso_swarm_size,
-        pso_iterations, 
-        pso_fuzzer_generations, 
-        message_pool_size,
-        inertial_weight,
-        cognitive_weight,
-        social_weight,
-        regularization_strength,
-        vmax,
-    );
+#[derive(Parser)]
+#[command(name = "fuzz_client", version, about = "Netic: genetic network protocol fuzzer")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the two-stage (PSO then Bayesian) hyperparameter optimization and print the result
+    Optimize(OptimizeArgs),
+    /// Run the genetic fuzzing loop with the given hyperparameters
+    Fuzz(FuzzArgs),
+    /// Send every MessageSequence of the seed corpus, or a saved reproducer, once and print the exchanges
+    Replay(ReplayArgs),
+    /// Shrink a reproducer to the fewest messages, bytes and delays which still trigger the same outcome
+    Minimize(MinimizeArgs),
+    /// Render a state model DOT file written by the fuzzer into an image with Graphviz
+    ExportGraph(ExportGraphArgs),
+}
+
+/// Arguments shared by every subcommand which talks to the target. Any argument given here
+/// overrides the corresponding setting of the campaign file
+#[derive(Args, Clone)]
+struct CampaignArgs {
+    /// Campaign file (.toml or .json) describing the target, corpus and hyperparameters
+    #[arg(short = 'C', long)]
+    config: Option<PathBuf>,
+
+    /// Address of the server under test, e.g. 127.0.0.1:8025
+    #[arg(short, long)]
+    target: Option<String>,
+
+    #[arg(long, value_enum)]
+    transport: Option<TransportArg>,
+
+    #[arg(short, long, value_enum)]
+    protocol: Option<ProtocolArg>,
+
+    /// Launch this program as the target and restart it whenever it dies. With --transport pipe it is launched for every connection instead
+    #[arg(long, value_name = "PROGRAM")]
+    launch: Option<String>,
+
+    /// Argument passed to the launched target, repeat for several
+    #[arg(long = "launch-arg", value_name = "ARG", allow_hyphen_values = true)]
+    launch_args: Vec<String>,
+
+    /// Environment variable set for the launched target, repeat for several
+    #[arg(long = "launch-env", value_name = "KEY=VALUE", value_parser = parse_env)]
+    launch_env: Vec<(String, String)>,
+
+    /// PCAP files used as the seed corpus. Without one, a random corpus is generated
+    #[arg(short, long = "corpus")]
+    corpus: Vec<String>,
+
+    /// Directory which receives fitness.csv, state_model.dot and the other run artifacts
+    #[arg(short, long, default_value = "../resources")]
+    output_dir: PathBuf,
+
+    /// Number of fuzzing generations
+    #[arg(short, long)]
+    generations: Option<usize>,
+
+    /// Seed of the random number generator. Runs with the same seed against a deterministic
+    /// target make the same decisions. Without one, a random seed is chosen and printed
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Number of message sequences run against the target at the same time
+    #[arg(short = 'j', long)]
+    workers: Option<usize>,
+
+    /// Whether workers are threads, or tasks on an async runtime if built with the async feature
+    #[arg(long, value_enum)]
+    engine: Option<EngineArg>,
+
+    /// Number of message sequences each worker sends over one connection before opening a new one
+    #[arg(long, value_name = "N")]
+    sequences_per_connection: Option<usize>,
+
+    /// Let mutation close, half-close and reopen the connection and wait for data between messages
+    #[arg(long)]
+    mutate_controls: bool,
+
+    /// Let mutation send messages in fragments, byte by byte or pipelined with the next one
+    #[arg(long)]
+    mutate_delivery: bool,
+
+    /// Address of a further instance of the target to spread workers over, repeat for several
+    #[arg(long = "additional-target", value_name = "ADDRESS")]
+    additional_targets: Vec<String>,
+
+    /// Seconds to wait for a connection to the target
+    #[arg(long, value_name = "SECONDS")]
+    connect_timeout: Option<f32>,
+
+    /// Seconds to wait for each response before the target is considered hung
+    #[arg(long, value_name = "SECONDS")]
+    read_timeout: Option<f32>,
+
+    /// Seconds a whole message sequence may take before it is cut short as a hang
+    #[arg(long, value_name = "SECONDS")]
+    sequence_timeout: Option<f32>,
+}
+
+#[derive(Args)]
+struct OptimizeArgs {
+    #[command(flatten)]
+    campaign: CampaignArgs,
+
+    #[arg(long)]
+    swarm_size: Option<usize>,
+
+    #[arg(long)]
+    pso_iterations: Option<usize>,
+
+    /// Fuzzer generations run to evaluate each particle
+    #[arg(long)]
+    pso_generations: Option<usize>,
+
+    #[arg(long)]
+    message_pool_size: Option<usize>,
+
+    #[arg(long)]
+    inertial_weight: Option<f32>,
+
+    #[arg(long)]
+    cognitive_weight: Option<f32>,
+
+    #[arg(long)]
+    social_weight: Option<f32>,
+
+    #[arg(long)]
+    regularization_strength: Option<f32>,
+
+    #[arg(long)]
+    vmax: Option<f32>,
+
+    #[arg(long)]
+    bayesian_iterations: Option<usize>,
+
+    /// Fuzzer generations run to evaluate each Bayesian sample
+    #[arg(long)]
+    bayesian_generations: Option<usize>,
+
+    /// Where to save the optimized campaign. Defaults to campaign.toml in the output directory
+    #[arg(long)]
+    save: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    #[command(flatten)]
+    campaign: CampaignArgs,
+
+    /// Crash or hang reproducer written by the fuzzer. Replayed instead of the seed corpus
+    #[arg(long)]
+    sequence: Option<PathBuf>,
+
+    /// Check the observed server states against the ones recorded in the reproducer and
+    /// exit with status 1 if they differ
+    #[arg(long, requires = "sequence")]
+    check_states: bool,
+}
+
+#[derive(Args)]
+struct MinimizeArgs {
+    #[command(flatten)]
+    campaign: CampaignArgs,
+
+    /// Crash or hang reproducer written by the fuzzer
+    #[arg(long)]
+    sequence: PathBuf,
+
+    /// Keep the server state transition into the state recorded for this message instead of
+    /// the reproducer's crash or hang
+    #[arg(long, value_name = "MESSAGE_INDEX")]
+    transition: Option<usize>,
+
+    /// Where to write the minimized reproducer. Defaults to <sequence>.min.json
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+/// Hyperparameters not given here or in the campaign file default to the ones found by a
+/// previous optimization run against SMTP
+#[derive(Args)]
+struct FuzzArgs {
+    #[command(flatten)]
+    campaign: CampaignArgs,
+
+    /// Continue the run saved in this checkpoint instead of starting from the seed corpus
+    #[arg(long)]
+    resume: Option<PathBuf>,
+
+    /// Write a checkpoint to the output directory every this many generations, 0 disables them
+    #[arg(long, default_value_t = 1)]
+    checkpoint_interval: usize,
+
+    #[arg(long)]
+    selection_pressure: Option<f32>,
+
+    #[arg(long)]
+    sequence_mutation_rate: Option<f32>,
+
+    #[arg(long)]
+    sequence_crossover_rate: Option<f32>,
+
+    #[arg(long)]
+    message_mutation_rate: Option<f32>,
+
+    #[arg(long)]
+    message_crossover_rate: Option<f32>,
+
+    #[arg(long)]
+    message_pool_size: Option<usize>,
+
+    #[arg(long)]
+    pool_update_rate: Option<f32>,
+
+    #[arg(long)]
+    state_rarity_threshold: Option<f32>,
+
+    #[arg(long)]
+    state_coverage_weight: Option<f32>,
+
+    #[arg(long)]
+    response_time_weight: Option<f32>,
+
+    #[arg(long)]
+    state_roc_weight: Option<f32>,
+
+    #[arg(long)]
+    state_rarity_weight: Option<f32>,
+}
+
+#[derive(Args)]
+struct ExportGraphArgs {
+    /// Directory the fuzzer wrote state_model.dot into
+    #[arg(short, long, default_value = "../resources")]
+    output_dir: PathBuf,
+
+    /// Image format passed to Graphviz, e.g. png or svg
+    #[arg(short, long, default_value = "png")]
+    format: String,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TransportArg {
+    Tcp,
+    Udp,
+    Tls,
+    UnixStream,
+    UnixDatagram,
+    Pipe,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EngineArg {
+    Threads,
+    Async,
+}
 
+#[derive(Clone, Copy, ValueEnum)]
+enum ProtocolArg {
+    Smtp,
+    Greeting,
+}
+
+impl From<TransportArg> for TransportProtocol {
+    fn from(transport: TransportArg) -> Self {
+        match transport {
+            TransportArg::Tcp => TransportProtocol::TCP,
+            TransportArg::Udp => TransportProtocol::UDP,
+            TransportArg::Tls => TransportProtocol::TLS,
+            TransportArg::UnixStream => TransportProtocol::UnixStream,
+            TransportArg::UnixDatagram => TransportProtocol::UnixDatagram,
+            TransportArg::Pipe => TransportProtocol::Pipe,
+        }
+    }
+}
+
+impl From<EngineArg> for Engine {
+    fn from(engine: EngineArg) -> Self {
+        match engine {
+            EngineArg::Threads => Engine::Threads,
+            EngineArg::Async => Engine::Async,
+        }
+    }
+}
+
+impl ProtocolArg {
+    fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+fn parse_env(argument: &str) -> Result<(String, String), String> {
+    match argument.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got {:?}", argument)),
+    }
+}
+
+// Overwrite the setting with the command line value, if one was given
+fn set<T: Clone>(setting: &mut T, argument: &Option<T>) {
+    if let Some(value) = argument {
+        *setting = value.clone();
+    }
+}
+
+impl CampaignArgs {
+    // Start from the campaign file, if any, and layer the command line arguments on top
+    fn resolve(&self) -> Result<Campaign, String> {
+        let mut campaign = match (&self.config, &self.target, &self.protocol) {
+            (Some(config), _, _) => Campaign::load(config).map_err(|e| e.to_string())?,
+            (None, Some(target), Some(protocol)) => Campaign::new(target.clone(), TransportProtocol::TCP, protocol.name()),
+            (None, _, _) => return Err(String::from("either --config or both --target and --protocol are required")),
+        };
+
+        set(&mut campaign.target.address, &self.target);
+        set(&mut campaign.target.transport, &self.transport.map(TransportProtocol::from));
+        set(&mut campaign.target.protocol, &self.protocol.map(|protocol| protocol.name()));
+        set(&mut campaign.fuzz.generations, &self.generations);
+        set(&mut campaign.execution.workers, &self.workers);
+        set(&mut campaign.execution.engine, &self.engine.map(Engine::from));
+        set(&mut campaign.execution.sequences_per_connection, &self.sequences_per_connection);
+        if self.mutate_controls {
+            campaign.execution.mutate_controls = true;
+        }
+        if self.mutate_delivery {
+            campaign.execution.mutate_delivery = true;
+        }
+        if self.seed.is_some() {
+            campaign.execution.seed = self.seed;
+        }
+
+        if !self.additional_targets.is_empty() {
+            campaign.target.additional_addresses = self.additional_targets.clone();
+        }
+
+        if !self.corpus.is_empty() {
+            campaign.corpus.pcap_files = self.corpus.clone();
+        }
+
+        set(&mut campaign.target.timeouts.connect, &self.connect_timeout);
+        set(&mut campaign.target.timeouts.read, &self.read_timeout);
+        if self.sequence_timeout.is_some() {
+            campaign.target.timeouts.sequence = self.sequence_timeout;
+        }
+
+        // A pipe target talks over its stdin and stdout, so it is launched per connection rather than supervised
+        let launched = if campaign.target.transport == TransportProtocol::Pipe {
+            &mut campaign.target.pipe
+        } else {
+            &mut campaign.target.process
+        };
+
+        if let Some(program) = &self.launch {
+            *launched = Some(ProcessSettings::new(program.clone()));
+        }
+
+        if !self.launch_args.is_empty() || !self.launch_env.is_empty() {
+            let process = launched.as_mut()
+                .ok_or_else(|| String::from("--launch-arg and --launch-env need --launch, or a [target.process] or [target.pipe] section"))?;
+
+            if !self.launch_args.is_empty() {
+                process.args = self.launch_args.clone();
+            }
+            process.env.extend(self.launch_env.iter().cloned());
+        }
+
+        Ok(campaign)
+    }
+}
+
+impl FuzzArgs {
+    fn resolve(&self) -> Result<Campaign, String> {
+        let mut campaign = self.campaign.resolve()?;
+        self.override_config(&mut campaign.fuzz);
+        Ok(campaign)
+    }
+
+    // Replace the hyperparameters given on the command line
+    fn override_config(&self, fuzz: &mut FuzzConfig) {
+        set(&mut fuzz.generations, &self.campaign.generations);
+        set(&mut fuzz.selection_pressure, &self.selection_pressure);
+        set(&mut fuzz.sequence_mutation_rate, &self.sequence_mutation_rate);
+        set(&mut fuzz.sequence_crossover_rate, &self.sequence_crossover_rate);
+        set(&mut fuzz.message_mutation_rate, &self.message_mutation_rate);
+        set(&mut fuzz.message_crossover_rate, &self.message_crossover_rate);
+        set(&mut fuzz.message_pool_size, &self.message_pool_size);
+        set(&mut fuzz.pool_update_rate, &self.pool_update_rate);
+        set(&mut fuzz.state_rarity_threshold, &self.state_rarity_threshold);
+        set(&mut fuzz.state_coverage_weight, &self.state_coverage_weight);
+        set(&mut fuzz.response_time_weight, &self.response_time_weight);
+        set(&mut fuzz.state_roc_weight, &self.state_roc_weight);
+        set(&mut fuzz.state_rarity_weight, &self.state_rarity_weight);
+    }
+}
+
+impl OptimizeArgs {
+    fn resolve(&self) -> Result<Campaign, String> {
+        let mut campaign = self.campaign.resolve()?;
+        let pso = &mut campaign.optimizer.pso;
+
+        set(&mut pso.swarm_size, &self.swarm_size);
+        set(&mut pso.iterations, &self.pso_iterations);
+        set(&mut pso.fuzzer_generations, &self.pso_generations);
+        set(&mut pso.message_pool_size, &self.message_pool_size);
+        set(&mut pso.inertial_weight, &self.inertial_weight);
+        set(&mut pso.cognitive_weight, &self.cognitive_weight);
+        set(&mut pso.social_weight, &self.social_weight);
+        set(&mut pso.regularization_strength, &self.regularization_strength);
+        set(&mut pso.vmax, &self.vmax);
+
+        let bayesian = &mut campaign.optimizer.bayesian;
+        set(&mut bayesian.iterations, &self.bayesian_iterations);
+        set(&mut bayesian.fuzzer_generations, &self.bayesian_generations);
+
+        Ok(campaign)
+    }
+}
+
+impl Command {
+    fn campaign_args(&self) -> Option<&CampaignArgs> {
+        match self {
+            Command::Optimize(args) => Some(&args.campaign),
+            Command::Fuzz(args) => Some(&args.campaign),
+            Command::Replay(args) => Some(&args.campaign),
+            Command::Minimize(args) => Some(&args.campaign),
+            Command::ExportGraph(_) => None,
+        }
+    }
+
+    fn resolve(&self) -> Result<Campaign, String> {
+        let campaign = match self {
+            Command::Optimize(args) => args.resolve()?,
+            Command::Fuzz(args) => args.resolve()?,
+            Command::Replay(args) => args.campaign.resolve()?,
+            Command::Minimize(args) => args.campaign.resolve()?,
+            Command::ExportGraph(_) => unreachable!(),
+        };
+
+        campaign.validate().map_err(|e| e.to_string())?;
+        Ok(campaign)
+    }
+}
+
+fn optimize_hyperparameters<P: Protocol+PartialEq>(client: &mut Client<P>, campaign: &Campaign) -> Result<FuzzConfig, Error> {
     // Run swarm and get set configs to swarm's global best
-    swarm.run_swarm(client);
-    let mut pso_optimized_configs = swarm.global_best_position;
-
-    // Create instance of BayesianOptimizer
-    let bayesian_iterations = 20;
-    let fuzzer_generations = 20;
-
-    pso_optimized_configs.generations = fuzzer_generations;
-    
-    let mut bayesian_optimizer = BayesianOptimizer::new(
-        &pso_optimized_configs,
-        bayesian_iterations,
-        pso_swarm_size,
-        pso_iterations,
-        pso_fuzzer_generations,
-    );
+    let mut swarm = campaign.swarm(client.rng());
+    swarm.run_swarm(client)?;
+    let pso_optimized_configs = swarm.global_best_position;
 
     // Run Bayesian optimization and get set configs to optimizer's global best
-    bayesian_optimizer.run_optimization(client);
+    let mut bayesian_optimizer = campaign.bayesian_optimizer(&pso_optimized_configs);
+    bayesian_optimizer.run_optimization(client)?;
 
     // Return the optimized configs
-    bayesian_optimizer.get_optimized_hyperparameters()
+    Ok(bayesian_optimizer.get_optimized_hyperparameters())
 }
 
-fn main() {
-    // User-provided server address and transport protocol
-    let server_address = String::from("10.0.0.92:8025");
-    let transport_protocol: TransportProtocol = TransportProtocol::TCP;
-    let target_protocol: SMTP = SMTP {};
-
-    // Optimized hyperparameters on smaller PCAP file to save time
-    let pcap_file = String::from("../resources/smtp.pcap");
-    let pcap_corpus = target_protocol.parse_pcap(pcap_file.as_str(), server_address.as_str().clone());
-
-    // Create instance of Client
-    let mut client = Client::new(server_address.clone(), transport_protocol, target_protocol);
-    client.corpus = pcap_corpus;
-
-    let mut optimized_configs = optimize_hyperparameters(&mut client);
-
-    // Run fuzzing with configs from swarm
-    let generations = 30;
-    optimized_configs.generations = generations;
-
-    // Reset client's corpus to "official" corpus by parsing bigger PCAP
-    let pcap_file = String::from("../resources/new_smtp.pcap");
-    let pcap_corpus = target_protocol.clone().parse_pcap(pcap_file.as_str(), server_address.as_str().clone());
-
-    client.corpus = pcap_corpus;
-
-    println!("Optimized Hyperparameters: ({:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4})\n", 
-        optimized_configs.generations,
-        optimized_configs.selection_pressure,
-        optimized_configs.sequence_mutation_rate,
-        optimized_configs.sequence_crossover_rate,
-        optimized_configs.message_mutation_rate,
-        optimized_configs.message_crossover_rate,
-        optimized_configs.message_pool_size,
-        optimized_configs.pool_update_rate,
-        optimized_configs.state_rarity_threshold,
-        optimized_configs.state_coverage_weight,
-        optimized_configs.response_time_weight,
-        optimized_configs.state_roc_weight,
-        optimized_configs.state_rarity_weight
+// Apply the campaign's run settings which are not part of the fuzzing state to a Client
+fn configure_client<P: Protocol+PartialEq>(client: &mut Client<P>, campaign: &Campaign, campaign_args: &CampaignArgs) -> Result<(), Error> {
+    let target = &campaign.target;
+    client.output_directory = campaign_args.output_dir.clone();
+    client.campaign_file = campaign_args.config.clone();
+    client.supervisor = target.process.clone().map(|settings| Supervisor::new(settings, target.address.clone(), target.transport.clone()));
+    client.workers = campaign.execution.workers;
+    client.engine = campaign.execution.engine;
+    // The async engine has no TLS, so a sequence it runs ends at the reply agreeing to STARTTLS
+    if client.engine == Engine::Async && client.protocol().can_start_tls() {
+        eprintln!("warning: execution.engine async cannot upgrade connections to TLS, sequences of {} end where the target agrees to it", campaign.target.protocol);
+    }
+    client.sequences_per_connection = campaign.execution.sequences_per_connection;
+    client.reset_between_sequences = campaign.execution.reset_between_sequences;
+    client.mutate_controls = campaign.execution.mutate_controls;
+    client.mutate_delivery = campaign.execution.mutate_delivery;
+    client.max_delivery_delay = campaign.execution.max_delivery_delay;
+    client.additional_addresses = target.additional_addresses.clone();
+    if let Some(framing) = &target.framing {
+        client.framing = framing.clone();
+    }
+    if let Some(greeting) = target.greeting {
+        client.greeting = greeting;
+    }
+    client.transport_options = TransportOptions {
+        udp: target.udp.clone(),
+        tls: Some(TlsConnector::new(&target.tls)?),
+        pipe: target.pipe.clone(),
+        timeouts: target.timeouts.clone(),
+        ..TransportOptions::default()
+    };
+    Ok(())
+}
+
+// Build a Client for the campaign's target and seed its corpus, and its StateModel with the
+// replies of the target, from the campaign's PCAP files
+fn build_client<P: Protocol+PartialEq>(campaign: &Campaign, campaign_args: &CampaignArgs, protocol: P) -> Result<Client<P>, Error> {
+    let target = &campaign.target;
+    let seed = campaign.execution.seed.unwrap_or_else(random_seed);
+    let mut client = Client::with_seed(target.address.clone(), target.transport.clone(), protocol.clone(), seed);
+    configure_client(&mut client, campaign, campaign_args)?;
+
+    if !campaign.corpus.pcap_files.is_empty() {
+        client.corpus = Vec::new();
+        for pcap_file in &campaign.corpus.pcap_files {
+            let connections = read_connections(pcap_file, &target.address)?;
+            client.corpus.extend(protocol.parse_connections(&connections));
+            client.seed_state_model(&connections);
+        }
+    }
+
+    Ok(client)
+}
+
+fn print_config(configs: &FuzzConfig) {
+    println!("Optimized Hyperparameters: ({:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4}, {:.4})\n",
+        configs.generations,
+        configs.selection_pressure,
+        configs.sequence_mutation_rate,
+        configs.sequence_crossover_rate,
+        configs.message_mutation_rate,
+        configs.message_crossover_rate,
+        configs.message_pool_size,
+        configs.pool_update_rate,
+        configs.state_rarity_threshold,
+        configs.state_coverage_weight,
+        configs.response_time_weight,
+        configs.state_roc_weight,
+        configs.state_rarity_weight
     );
+}
+
+fn run<P: Protocol+PartialEq>(command: Command, mut campaign: Campaign, protocol: P) -> Result<(), Error> {
+    let campaign_args = command.campaign_args().unwrap().clone();
+    let output_dir = campaign_args.output_dir.clone();
+
+    // A resumed run continues with the corpus, state model, seed and random state of its
+    // checkpoint, so it is built from the checkpoint rather than from the seed corpus
+    let checkpoint_file = match &command {
+        Command::Fuzz(args) => args.resume.clone(),
+        _ => None,
+    };
+    let mut resumed_config = None;
+    let mut client = match checkpoint_file {
+        Some(checkpoint_file) => {
+            let checkpoint: Checkpoint<P> = match Checkpoint::load(&checkpoint_file) {
+                Ok(checkpoint) => checkpoint,
+                Err(e) => {
+                    eprintln!("{}: {}", checkpoint_file.display(), e);
+                    std::process::exit(1);
+                }
+            };
+
+            println!("Resuming at generation {} with seed {} from {}", checkpoint.generation, checkpoint.seed, checkpoint_file.display());
+            resumed_config = Some(checkpoint.config.clone());
+
+            let target = &campaign.target;
+            let mut client = Client::from_checkpoint(target.address.clone(), target.transport.clone(), protocol.clone(), checkpoint);
+            configure_client(&mut client, &campaign, &campaign_args)?;
+            client
+        }
+        None => build_client(&campaign, &campaign_args, protocol.clone())?,
+    };
+
+    if resumed_config.is_none() && matches!(command, Command::Fuzz(_) | Command::Optimize(_)) {
+        println!("Seed: {}", client.seed());
+    }
+
+    match command {
+        Command::Optimize(args) => {
+            let generations = campaign.fuzz.generations;
+            campaign.fuzz = optimize_hyperparameters(&mut client, &campaign)?;
+            campaign.fuzz.generations = generations;
+            print_config(&campaign.fuzz);
+
+            let save_path = args.save.unwrap_or_else(|| output_dir.join("campaign.toml"));
+            campaign.save(&save_path)?;
+            println!("Saved optimized campaign to {}", save_path.display());
+        }
+        Command::Fuzz(args) => {
+            // A resumed run keeps the hyperparameters it was started with, except for the ones
+            // given again on the command line
+            let config = match resumed_config {
+                Some(mut config) => {
+                    args.override_config(&mut config);
+                    config
+                }
+                None => campaign.fuzz.clone(),
+            };
+
+            client.checkpoint_interval = args.checkpoint_interval;
+            client.fuzz(config, true)?;
+        }
+        Command::Replay(args) => {
+            let Some(sequence_file) = &args.sequence else {
+                for (index, sequence) in client.corpus.clone().iter().enumerate() {
+                    println!("SEQUENCE {}", index);
+                    print!("{}", client.replay(sequence, &[]));
+                }
+                return Ok(());
+            };
+
+            let reproducer: Reproducer<P> = match Reproducer::load(sequence_file) {
+                Ok(reproducer) => reproducer,
+                Err(e) => {
+                    eprintln!("{}: {}", sequence_file.display(), e);
+                    std::process::exit(1);
+                }
+            };
+
+            println!("Reproducing {:?} after message {}: {}", reproducer.outcome.verdict, reproducer.outcome.message_index, reproducer.outcome.detail);
+
+            let expected_states = if args.check_states { &reproducer.states[..] } else { &[] };
+            if args.check_states && expected_states.is_empty() {
+                eprintln!("{} has no recorded server states to check against", sequence_file.display());
+                std::process::exit(1);
+            }
+
+            let transcript = client.replay(&reproducer.sequence, expected_states);
+            print!("{}", transcript);
+
+            if args.check_states {
+                let mismatches = transcript.mismatches();
+                if !mismatches.is_empty() {
+                    println!("Server states differ from the recording at messages {:?}", mismatches);
+                    std::process::exit(1);
+                }
+                println!("Server states match the recording");
+            }
+        }
+        Command::Minimize(args) => {
+            let mut reproducer: Reproducer<P> = match Reproducer::load(&args.sequence) {
+                Ok(reproducer) => reproducer,
+                Err(e) => {
+                    eprintln!("{}: {}", args.sequence.display(), e);
+                    std::process::exit(1);
+                }
+            };
+
+            let goal = match args.transition {
+                Some(index) if index == 0 || index >= reproducer.states.len() => {
+                    eprintln!("{} records server states for messages 0 to {}, a transition needs a message after the first", args.sequence.display(), reproducer.states.len() as isize - 1);
+                    std::process::exit(1);
+                }
+                Some(index) => MinimizationGoal::Transition(reproducer.states[index - 1].clone(), reproducer.states[index].clone()),
+                None => MinimizationGoal::Outcome(reproducer.outcome.clone()),
+            };
+
+            let original_bytes: usize = reproducer.sequence.messages.iter().map(|message| message.data.len()).sum();
+            println!("Minimizing {} messages ({} bytes)", reproducer.sequence.messages.len(), original_bytes);
+
+            let Some(minimized) = minimize(&mut client, &reproducer.sequence, &goal) else {
+                eprintln!("{} no longer reproduces, nothing to minimize", args.sequence.display());
+                std::process::exit(1);
+            };
+
+            // Record what the minimized sequence does now, so it can be replayed and checked like any other reproducer
+            let transcript = client.replay(&minimized, &[]);
+            reproducer.outcome = transcript.outcome.clone();
+            reproducer.states = transcript.entries.iter().map(|entry| entry.state.clone()).collect();
+            reproducer.sequence = minimized;
+
+            let minimized_bytes: usize = reproducer.sequence.messages.iter().map(|message| message.data.len()).sum();
+            println!("Minimized to {} messages ({} bytes)", reproducer.sequence.messages.len(), minimized_bytes);
+            print!("{}", transcript);
+
+            let output = args.output.clone().unwrap_or_else(|| args.sequence.with_extension("min.json"));
+            match reproducer.save_as(&output, &protocol.name(), client.campaign_file.as_deref()) {
+                Ok(()) => println!("Saved minimized reproducer to {}", output.display()),
+                Err(e) => {
+                    eprintln!("{}: {}", output.display(), e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::ExportGraph(_) => unreachable!(),
+    }
+
+    Ok(())
+}
+
+fn export_graph(args: &ExportGraphArgs) {
+    let dot_file = args.output_dir.join("state_model.dot");
+    let image_file = args.output_dir.join(format!("state_model.{}", args.format));
+
+    let status = ProcessCommand::new("dot")
+        .arg(format!("-T{}", args.format))
+        .arg(&dot_file)
+        .arg("-o")
+        .arg(&image_file)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => println!("Wrote {}", image_file.display()),
+        Ok(status) => {
+            eprintln!("Graphviz exited with {}", status);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Could not run Graphviz 'dot': {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
 
-    print!("\nPRESS ENTER TO RUN FUZZER ... \n");
-    io::stdout().flush().unwrap();
+    if let Command::ExportGraph(args) = &cli.command {
+        export_graph(args);
+        return;
+    }
 
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).unwrap();
+    let campaign = match cli.command.resolve() {
+        Ok(campaign) => campaign,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
 
-    // (20, 0.4208, 0.4129, 0.2749, 0.6024, 0.3268, 50, 0.4425, 0.4724, 0.3726, 0.7373, 0.2529, 0.5072)
+    let result = match ProtocolArg::from_str(&campaign.target.protocol, true) {
+        Ok(ProtocolArg::Smtp) => run(cli.command, campaign, SMTP),
+        Ok(ProtocolArg::Greeting) => run(cli.command, campaign, GreetingProtocol),
+        Err(_) => {
+            eprintln!("invalid campaign: unknown target.protocol {:?}", campaign.target.protocol);
+            std::process::exit(2);
+        }
+    };
 
-    client.fuzz(optimized_configs, true);
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 }
@@ -6,9 +6,14 @@ use std::collections::HashMap;
 use std::default::Default;
 use std::hash::Hash;
 use std::fmt;
+use std::time::Duration;
 
+use rand::Rng;
+
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
 
 use crate::Protocol; 
+use crate::FuzzRng;
 use crate::protocols::GreetingProtocol;
 
 
@@ -19,12 +24,89 @@ use crate::protocols::GreetingProtocol;
 // to store data that is specific to the SIPProtocol. Additionally, when
 // methods like build_message, validate_message, etc, need to be used, the 
 // Message struct will use the implementations provided by SIPProtocol.
+//
+// When a Message is serialized the protocol instance is skipped and it is
+// restored with P::default() on deserialization.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Message<P: Protocol> {
+	#[serde(skip)]
 	pub protocol: P, // This gives us an instance of the type implementing the Protocol trait
 	pub data: Vec<u8>,
 	pub message_type: P::MessageType,
 	pub response_time: f32,
+	#[serde(with = "section_pairs")]
 	pub sections: HashMap<P::MessageSectionsKey, P::MessageSectionsValue>,
+	// How data is written to the target
+	#[serde(default, skip_serializing_if = "Delivery::is_whole")]
+	pub delivery: Delivery,
+}
+
+// How the bytes of a Message are written to the target. Targets which buffer or parse
+// incrementally often only go wrong when a request arrives in pieces or together with the next
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Delivery {
+	// In a single write
+	#[default]
+	Whole,
+	// In several writes, cut at these offsets, with delay seconds between two of them.
+	// Offsets past the end of the data are ignored
+	Fragmented { offsets: Vec<usize>, delay: f32 },
+	// One byte per write, with delay seconds between two of them
+	Slow { delay: f32 },
+	// In the same write as the next message, before the response to this one is read
+	Pipelined,
+}
+
+impl Delivery {
+	pub fn is_whole(&self) -> bool {
+		*self == Delivery::Whole
+	}
+
+	// A random Delivery for a message of length bytes, with delays of at most max_delay seconds
+	pub fn random(length: usize, max_delay: f32, rng: &mut FuzzRng) -> Self {
+		let delay = rng.gen_range(0.0..=max_delay);
+		match rng.gen_range(0..4) {
+			0 => Delivery::Whole,
+			1 if length > 1 => {
+				let mut offsets: Vec<usize> = (0..rng.gen_range(1..=3)).map(|_| rng.gen_range(1..length)).collect();
+				offsets.sort_unstable();
+				offsets.dedup();
+				Delivery::Fragmented { offsets, delay }
+			}
+			1 | 2 => Delivery::Slow { delay },
+			_ => Delivery::Pipelined,
+		}
+	}
+
+	// The pieces data is written in, in order. There is always at least one, if only an empty one
+	pub fn segments<'a>(&self, data: &'a [u8]) -> Vec<&'a [u8]> {
+		match self {
+			Delivery::Fragmented { offsets, .. } => {
+				let mut segments = Vec::new();
+				let mut start = 0;
+				for &offset in offsets.iter().filter(|&&offset| offset > 0 && offset < data.len()) {
+					if offset > start {
+						segments.push(&data[start..offset]);
+						start = offset;
+					}
+				}
+				segments.push(&data[start..]);
+				segments
+			}
+			Delivery::Slow { .. } if !data.is_empty() => data.chunks(1).collect(),
+			_ => vec![data],
+		}
+	}
+
+	// How long to wait between two segments
+	pub fn delay(&self) -> Duration {
+		match self {
+			Delivery::Fragmented { delay, .. } | Delivery::Slow { delay } => Duration::from_secs_f32(delay.max(0.0)),
+			Delivery::Whole | Delivery::Pipelined => Duration::ZERO,
+		}
+	}
 }
 
 impl<P: Protocol> Message<P> {
@@ -37,6 +119,7 @@ impl<P: Protocol> Message<P> {
 			message_type: Default::default(),
 			response_time: 0.0,
 			sections: HashMap::new(),
+			delivery: Delivery::Whole,
 		}
 	}
 
@@ -48,16 +131,16 @@ impl<P: Protocol> Message<P> {
 	// from_bytes and random_message are responsible for creating new Message
 	// instances and they don't need to be called on an existing instance. Instead
 	// they take protocol as an argument.
-	pub fn random_message(protocol: P) -> Self {
-		protocol.random_message()
+	pub fn random_message(protocol: P, rng: &mut FuzzRng) -> Self {
+		protocol.random_message(rng)
 	}
 
 	pub fn from_bytes(protocol: P, message_bytes: &[u8]) -> Self {
 		protocol.build_message(message_bytes)
 	}
 
-	pub fn mutate_message(&mut self) {
-		let mutated_message = self.protocol.mutate_message(self);
+	pub fn mutate_message(&mut self, rng: &mut FuzzRng) {
+		let mutated_message = self.protocol.mutate_message(self, rng);
 
 		self.data = mutated_message.data;
 		self.message_type = mutated_message.message_type;
@@ -65,8 +148,12 @@ impl<P: Protocol> Message<P> {
 		self.sections = mutated_message.sections;
 	}
 
-	pub fn crossover_messages(&self, other: &Self) -> (Self, Self) {
-		self.protocol.crossover_messages(self, other)
+	pub fn mutate_delivery(&mut self, max_delay: f32, rng: &mut FuzzRng) {
+		self.delivery = Delivery::random(self.data.len(), max_delay, rng);
+	}
+
+	pub fn crossover_messages(&self, other: &Self, rng: &mut FuzzRng) -> (Self, Self) {
+		self.protocol.crossover_messages(self, other, rng)
 	}
 }
 
@@ -79,6 +166,7 @@ impl<P: Protocol> PartialEq for Message<P> {
         self.message_type == other.message_type
             && self.data == other.data
             && self.sections == other.sections
+            && self.delivery == other.delivery
     }
 }
 
@@ -90,6 +178,7 @@ impl<P: Protocol + Clone> Clone for Message<P> {
         	message_type: self.message_type.clone(),
         	response_time: self.response_time.clone(),
         	sections: self.sections.clone(),
+        	delivery: self.delivery.clone(),
         }
     }
 }
@@ -107,4 +196,30 @@ where
     }
 }
 
+// JSON only allows strings as object keys, so the sections of a Message are
+// serialized as a list of (key, value) pairs instead of as a map
+mod section_pairs {
+	use std::collections::HashMap;
+	use std::hash::Hash;
+
+	use super::{Serialize, Deserialize, Serializer, Deserializer};
+
+	pub fn serialize<K, V, S>(sections: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		K: Serialize,
+		V: Serialize,
+		S: Serializer,
+	{
+		serializer.collect_seq(sections.iter())
+	}
 
+	pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+	where
+		K: Deserialize<'de> + Eq + Hash,
+		V: Deserialize<'de>,
+		D: Deserializer<'de>,
+	{
+		let pairs: Vec<(K, V)> = Vec::deserialize(deserializer)?;
+		Ok(pairs.into_iter().collect())
+	}
+}
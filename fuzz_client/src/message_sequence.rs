@@ -3,16 +3,71 @@
 #![allow(unused_variables)]
 #![allow(unused_assignments)]
 
+use std::fmt;
+
 use rand::prelude::*;
 use rand::Rng;
 
+use serde::{Serialize, Deserialize};
+
 use crate::Protocol;
-use crate::message::Message;
+use crate::message::{Message, Delivery};
+use crate::FuzzRng;
+
+// Something done to the connection in the middle of a MessageSequence instead of sending a message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Control {
+    // Close the connection. A message after it goes over a new connection
+    Close,
+    // Shut down the sending side only, so the target reads the end of the stream but can still
+    // answer. A message after it goes over a new connection
+    HalfClose,
+    // Close the connection and open a new one right away, reading the greeting if there is one
+    Reconnect,
+    // Wait up to this many seconds for anything the target sends on its own
+    WaitForData(f32),
+}
 
+impl Control {
+    pub fn random(rng: &mut FuzzRng) -> Self {
+        match rng.gen_range(0..4) {
+            0 => Control::Close,
+            1 => Control::HalfClose,
+            2 => Control::Reconnect,
+            _ => Control::WaitForData(rng.gen_range(0.1..1.0)),
+        }
+    }
+}
+
+impl fmt::Display for Control {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Control::Close => write!(f, "close"),
+            Control::HalfClose => write!(f, "half-close"),
+            Control::Reconnect => write!(f, "reconnect"),
+            Control::WaitForData(seconds) => write!(f, "wait {:.2} s", seconds),
+        }
+    }
+}
+
+// A Control run before the message at index before, or after the last message if before is
+// the number of messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ControlStep {
+    pub before: usize,
+    pub control: Control,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct MessageSequence<P: Protocol> {
     pub messages: Vec<Message<P>>,
     pub timings: Vec<f32>,
     pub fitness: f32,
+    // Ordered by the message they run before. Steps before the same message run in their order here
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub controls: Vec<ControlStep>,
 }
 
 impl<P: Protocol> MessageSequence<P> {
@@ -21,6 +76,7 @@ impl<P: Protocol> MessageSequence<P> {
             messages: Vec::new(),
             timings: Vec::new(),
             fitness: 0.0,
+            controls: Vec::new(),
         }
     }
 
@@ -29,16 +85,75 @@ impl<P: Protocol> MessageSequence<P> {
             messages,
             timings,
             fitness: 0.0,
+            controls: Vec::new(),
+        }
+    }
+
+    // The Controls run before the message at index, in order
+    pub fn controls_before(&self, index: usize) -> impl Iterator<Item = &Control> {
+        self.controls.iter().filter(move |step| step.before == index).map(|step| &step.control)
+    }
+
+    // Add a Control before the message at index, after the steps already there
+    pub fn insert_control(&mut self, before: usize, control: Control) {
+        let position = self.controls.partition_point(|step| step.before <= before);
+        self.controls.insert(position, ControlStep { before, control });
+    }
+
+    // The index of the last message written together with the one at index: the messages
+    // pipelined one after the other, up to a control step which has to run in between
+    pub fn pipeline_end(&self, index: usize) -> usize {
+        let mut end = index;
+        while end + 1 < self.messages.len()
+            && self.messages[end].delivery == Delivery::Pipelined
+            && self.controls_before(end + 1).next().is_none()
+        {
+            end += 1;
         }
+        end
     }
 
-    pub fn random_message_sequence(protocol: P, sequence_length: usize) -> Self {
-        let mut rng = rand::thread_rng();
+    // Give a random message a new Delivery, with delays of at most max_delay seconds
+    pub fn mutate_delivery(&mut self, max_delay: f32, rng: &mut FuzzRng) {
+        if let Some(message) = self.messages.choose_mut(rng) {
+            message.mutate_delivery(max_delay, rng);
+        }
+    }
+
+    // Insert, remove or move one control step
+    pub fn mutate_controls(&mut self, rng: &mut FuzzRng) {
+        match rng.gen_range(0..3) {
+            0 => {
+                // Insertion of a random control step anywhere, including after the last message
+                let before = rng.gen_range(0..=self.messages.len());
+                let control = Control::random(rng);
+                self.insert_control(before, control);
+            }
+            1 => {
+                // Deletion of a random control step
+                if !self.controls.is_empty() {
+                    let step_index = rng.gen_range(0..self.controls.len());
+                    self.controls.remove(step_index);
+                }
+            }
+            _ => {
+                // Move a random control step in front of another message
+                if !self.controls.is_empty() {
+                    let step_index = rng.gen_range(0..self.controls.len());
+                    let step = self.controls.remove(step_index);
+                    let before = rng.gen_range(0..=self.messages.len());
+                    self.insert_control(before, step.control);
+                }
+            }
+        }
+    }
+
+    pub fn random_message_sequence(protocol: P, sequence_length: usize, rng: &mut FuzzRng) -> Self {
         let mut messages: Vec<Message<P>> = Vec::new();
         let mut timings: Vec<f32> = Vec::new();
 
         for _ in 0..sequence_length {
-            messages.push(Message::random_message(protocol.clone()));
+            messages.push(Message::random_message(protocol.clone(), rng));
         }
 
         for _ in 0..sequence_length - 1 {
@@ -49,12 +164,12 @@ impl<P: Protocol> MessageSequence<P> {
             messages,
             timings,
             fitness: 0.0,
+            controls: Vec::new(),
         }
     }
 
-    pub fn mutate_message_sequence(&mut self, protocol: P, message_mutation_rate: f32, message_pool: &[Message<P>]) {
+    pub fn mutate_message_sequence(&mut self, protocol: P, message_mutation_rate: f32, message_pool: &[Message<P>], rng: &mut FuzzRng) {
         // Randomly select which mutation type the MessageSequence will undergo
-        let mut rng = rand::thread_rng();
         let mutation_type = rng.gen_range(0..5);
 
         match mutation_type {
@@ -63,6 +178,13 @@ impl<P: Protocol> MessageSequence<P> {
                 if !self.messages.is_empty() {
                     let message_index = rng.gen_range(0..self.messages.len());
                     self.messages.remove(message_index);
+
+                    // Steps before a later message stay with it
+                    for step in &mut self.controls {
+                        if step.before > message_index {
+                            step.before -= 1;
+                        }
+                    }
                 }
             }
             1 => {
@@ -70,29 +192,35 @@ impl<P: Protocol> MessageSequence<P> {
                 // or recent message from message_pool
                 let message_index = rng.gen_range(0..=self.messages.len());
                 let message_to_add = if rng.gen_bool(0.5) {
-                    Message::random_message(protocol)
+                    Message::random_message(protocol.clone(), rng)
                 } else if !message_pool.is_empty() {
-                    message_pool.choose(&mut rng).unwrap().clone()
+                    message_pool.choose(rng).unwrap().clone()
                 } else {
-                    Message::random_message(protocol)
+                    Message::random_message(protocol.clone(), rng)
                 };
 
                 self.messages.insert(message_index, message_to_add);
+
+                for step in &mut self.controls {
+                    if step.before >= message_index {
+                        step.before += 1;
+                    }
+                }
             }
             2 => {
                 // Shuffle message order in sequence
-                self.messages.shuffle(&mut rng);
+                self.messages.shuffle(rng);
             }
             3 => {
                 // Substitute random message with stock message or one from pool
                 if !self.messages.is_empty() {
                     let message_index = rng.gen_range(0..self.messages.len());
                     let message_to_add = if rng.gen_bool(0.5) {
-                        Message::random_message(protocol)
+                        Message::random_message(protocol.clone(), rng)
                     } else if !message_pool.is_empty() {
-                        message_pool.choose(&mut rng).unwrap().clone()
+                        message_pool.choose(rng).unwrap().clone()
                     } else {
-                        Message::random_message(protocol)
+                        Message::random_message(protocol.clone(), rng)
                     };
 
                     self.messages[message_index] = message_to_add;
@@ -112,15 +240,13 @@ impl<P: Protocol> MessageSequence<P> {
         // Run through each message in the sequence and determine if it gets mutated
         for i in 0..self.messages.len() {
             if rng.gen_range(0.0..1.0) < message_mutation_rate {
-                self.messages[i].mutate_message();
+                self.messages[i].mutate_message(rng);
             }
         }
     }
 
-    pub fn crossover_message_sequences(&mut self, other: &MessageSequence<P>, message_crossover_rate: f32) -> (MessageSequence<P>, MessageSequence<P>) {
+    pub fn crossover_message_sequences(&mut self, other: &MessageSequence<P>, message_crossover_rate: f32, rng: &mut FuzzRng) -> (MessageSequence<P>, MessageSequence<P>) {
         // Two-point crossover method
-        let mut rng = rand::thread_rng();
-
         let (small_parent, big_parent) = if self.messages.len() < other.messages.len() {
             (self.clone(), other.clone())
         } else {
@@ -153,10 +279,19 @@ impl<P: Protocol> MessageSequence<P> {
             }
         }
 
+        // The control steps in front of the transplanted messages go with them
+        let in_section = |step: &ControlStep| (crossover_point1..=crossover_point2).contains(&step.before);
+        small_offspring.controls = merge_controls(&small_parent.controls, &big_parent.controls, in_section);
+        big_offspring.controls = merge_controls(&big_parent.controls, &small_parent.controls, in_section);
+
         // Perform crossover on individual messages based on the message_crossover_rate
         for i in crossover_point1..=crossover_point2 {
             if rng.gen::<f32>() < message_crossover_rate {
-                let (new_small_msg, new_big_msg) = small_offspring.messages[i].crossover_messages(&big_offspring.messages[i]);
+                let (mut new_small_msg, mut new_big_msg) = small_offspring.messages[i].crossover_messages(&big_offspring.messages[i], rng);
+
+                // The protocol builds the offspring from bytes, which are delivered as before
+                new_small_msg.delivery = small_offspring.messages[i].delivery.clone();
+                new_big_msg.delivery = big_offspring.messages[i].delivery.clone();
                 small_offspring.messages[i] = new_small_msg;
                 big_offspring.messages[i] = new_big_msg;
             }
@@ -175,6 +310,7 @@ impl<P: Protocol> PartialEq for MessageSequence<P> {
         self.messages == other.messages
             && self.timings == other.timings
             && self.fitness == other.fitness
+            && self.controls == other.controls
     }
 }
 
@@ -184,6 +320,17 @@ impl<P: Protocol> Clone for MessageSequence<P> {
             messages: self.messages.clone(),
             timings: self.timings.clone(),
             fitness: self.fitness.clone(),
+            controls: self.controls.clone(),
         }
     }
 }
+
+// The steps of own outside the crossover section and those of other inside it, in order
+fn merge_controls(own: &[ControlStep], other: &[ControlStep], in_section: impl Fn(&ControlStep) -> bool) -> Vec<ControlStep> {
+    let mut controls: Vec<ControlStep> = own.iter().filter(|step| !in_section(step))
+        .chain(other.iter().filter(|step| in_section(step)))
+        .cloned()
+        .collect();
+    controls.sort_by_key(|step| step.before);
+    controls
+}
@@ -0,0 +1,221 @@
+use crate::Protocol;
+use crate::Client;
+use crate::{Message, Delivery};
+use crate::MessageSequence;
+use crate::Outcome;
+use crate::Transcript;
+
+
+// How many times a timing is halved while looking for the shortest delay which still works
+const TIMING_STEPS: usize = 6;
+
+// What a minimized MessageSequence has to keep doing to the target
+pub enum MinimizationGoal<P: Protocol> {
+	// The same verdict in the same crash bucket, i.e. the same crash or a hang
+	Outcome(Outcome),
+	// The target moves from the first ServerState to the second somewhere in the sequence
+	Transition(P::ServerState, P::ServerState),
+}
+
+impl<P: Protocol + PartialEq> MinimizationGoal<P> {
+	pub fn is_met(&self, transcript: &Transcript<P>) -> bool {
+		match self {
+			MinimizationGoal::Outcome(outcome) => {
+				transcript.outcome.verdict == outcome.verdict && transcript.outcome.bucket() == outcome.bucket()
+			}
+			MinimizationGoal::Transition(source_state, target_state) => {
+				transcript.entries.windows(2).any(|pair| pair[0].state == *source_state && pair[1].state == *target_state)
+			}
+		}
+	}
+}
+
+// Cut a MessageSequence down with delta debugging while it still meets the goal: first to the
+// fewest messages, then to the fewest control steps, the simplest delivery and the fewest bytes
+// of every message and last to the shortest timings.
+// Every candidate is sent to the client's target, so a supervised target is restarted between
+// candidates that kill it. Returns None if the sequence does not meet the goal to begin with.
+pub fn minimize<P: Protocol + PartialEq>(client: &mut Client<P>, message_sequence: &MessageSequence<P>, goal: &MinimizationGoal<P>) -> Option<MessageSequence<P>> {
+	let mut reproduces = |candidate: &MessageSequence<P>| goal.is_met(&client.replay(candidate, &[]));
+
+	let mut current = message_sequence.clone();
+	current.timings.truncate(current.messages.len().saturating_sub(1));
+	if !reproduces(&current) {
+		return None;
+	}
+
+	// Most sequences do not depend on their delays, and without them every later run is quicker
+	let mut without_delays = current.clone();
+	without_delays.timings.iter_mut().for_each(|timing| *timing = 0.0);
+	if without_delays.timings != current.timings && reproduces(&without_delays) {
+		current = without_delays;
+	}
+
+	// Fewest messages. A timing is the delay after its message, so the two are removed together
+	let indices: Vec<usize> = (0..current.messages.len()).collect();
+	let kept = ddmin(indices, |subset| reproduces(&select_messages(&current, subset)));
+	current = select_messages(&current, &kept);
+
+	// Fewest control steps. ddmin never tries none at all, so that comes first
+	if !current.controls.is_empty() {
+		let mut without_controls = current.clone();
+		without_controls.controls.clear();
+		if reproduces(&without_controls) {
+			current = without_controls;
+		} else {
+			let controls = current.controls.clone();
+			let kept = ddmin(controls, |subset| {
+				let mut candidate = current.clone();
+				candidate.controls = subset.to_vec();
+				reproduces(&candidate)
+			});
+			current.controls = kept;
+		}
+	}
+
+	// Simplest delivery of every message: whole if that works, otherwise cut at the fewest
+	// offsets and without delays if those are not needed
+	for index in 0..current.messages.len() {
+		if current.messages[index].delivery.is_whole() {
+			continue;
+		}
+
+		let mut candidate = current.clone();
+		candidate.messages[index].delivery = Delivery::Whole;
+		if reproduces(&candidate) {
+			current = candidate;
+			continue;
+		}
+
+		if let Delivery::Fragmented { offsets, delay } = current.messages[index].delivery.clone() {
+			let kept = ddmin(offsets, |subset| {
+				let mut candidate = current.clone();
+				candidate.messages[index].delivery = Delivery::Fragmented { offsets: subset.to_vec(), delay };
+				reproduces(&candidate)
+			});
+			current.messages[index].delivery = Delivery::Fragmented { offsets: kept, delay };
+		}
+
+		let without_delay = match &current.messages[index].delivery {
+			Delivery::Fragmented { offsets, .. } => Delivery::Fragmented { offsets: offsets.clone(), delay: 0.0 },
+			Delivery::Slow { .. } => Delivery::Slow { delay: 0.0 },
+			delivery => delivery.clone(),
+		};
+		if without_delay != current.messages[index].delivery {
+			let mut candidate = current.clone();
+			candidate.messages[index].delivery = without_delay;
+			if reproduces(&candidate) {
+				current = candidate;
+			}
+		}
+	}
+
+	// Fewest bytes of every message. build_message always builds a whole message, so each
+	// candidate keeps the delivery of the message it was cut from
+	for index in 0..current.messages.len() {
+		let protocol = current.messages[index].protocol.clone();
+		let bytes = current.messages[index].data.clone();
+		let delivery = current.messages[index].delivery.clone();
+		let rebuild = |bytes: &[u8]| {
+			let mut message = Message::from_bytes(protocol.clone(), bytes);
+			message.delivery = delivery.clone();
+			message
+		};
+
+		let kept = ddmin(bytes, |subset| {
+			let mut candidate = current.clone();
+			candidate.messages[index] = rebuild(subset);
+			reproduces(&candidate)
+		});
+		current.messages[index] = rebuild(&kept);
+	}
+
+	// Shortest timings. Each one is searched between zero, which did not work, and its current value
+	for index in 0..current.timings.len() {
+		let mut candidate = current.clone();
+		candidate.timings[index] = 0.0;
+		if reproduces(&candidate) {
+			current = candidate;
+			continue;
+		}
+
+		let (mut too_short, mut long_enough) = (0.0, current.timings[index]);
+		for _ in 0..TIMING_STEPS {
+			let timing = (too_short + long_enough) / 2.0;
+			candidate.timings[index] = timing;
+			if reproduces(&candidate) {
+				long_enough = timing;
+			} else {
+				too_short = timing;
+			}
+		}
+		current.timings[index] = long_enough;
+	}
+
+	Some(current)
+}
+
+fn select_messages<P: Protocol + PartialEq>(message_sequence: &MessageSequence<P>, indices: &[usize]) -> MessageSequence<P> {
+	let messages = indices.iter().map(|&index| message_sequence.messages[index].clone()).collect();
+	let mut timings: Vec<f32> = indices.iter().filter_map(|&index| message_sequence.timings.get(index).copied()).collect();
+
+	// The delay after the last message is never waited
+	timings.truncate(indices.len().saturating_sub(1));
+	let mut selected = MessageSequence::from_messages(messages, timings);
+
+	// A control step in front of a removed message moves on to the next message which is kept
+	for step in &message_sequence.controls {
+		let before = indices.iter().filter(|&&index| index < step.before).count();
+		selected.insert_control(before, step.control.clone());
+	}
+	selected
+}
+
+// Zeller's ddmin: find a 1-minimal subsequence of items for which test holds, assuming it holds
+// for all of items. Chunks are removed in turn, and whenever neither a chunk nor its complement
+// passes the test the items are split into twice as many chunks
+fn ddmin<T: Clone>(mut items: Vec<T>, mut test: impl FnMut(&[T]) -> bool) -> Vec<T> {
+	let mut chunks = 2;
+
+	while items.len() >= 2 {
+		let chunk_size = (items.len() + chunks - 1) / chunks;
+		let bounds: Vec<(usize, usize)> = (0..items.len())
+			.step_by(chunk_size)
+			.map(|start| (start, (start + chunk_size).min(items.len())))
+			.collect();
+
+		let mut reduced = false;
+
+		for &(start, end) in &bounds {
+			let subset = items[start..end].to_vec();
+			if test(&subset) {
+				items = subset;
+				chunks = 2;
+				reduced = true;
+				break;
+			}
+		}
+
+		// With two chunks every complement is the other chunk, which was just tested
+		if !reduced && bounds.len() > 2 {
+			for &(start, end) in &bounds {
+				let complement: Vec<T> = items[..start].iter().chain(&items[end..]).cloned().collect();
+				if test(&complement) {
+					items = complement;
+					chunks = (chunks - 1).max(2);
+					reduced = true;
+					break;
+				}
+			}
+		}
+
+		if !reduced {
+			if chunks >= items.len() {
+				break;
+			}
+			chunks = (chunks * 2).min(items.len());
+		}
+	}
+
+	items
+}
@@ -6,6 +6,8 @@ use rand_distr::{Normal, Distribution};
 use crate::FuzzConfig;
 use crate::Client;
 use crate::Protocol;
+use crate::FuzzRng;
+use crate::Error;
 
 
 pub struct BayesianOptimizer {
@@ -54,7 +56,7 @@ impl BayesianOptimizer {
     }
     
     // Runs the Bayesian optimization for a specified number of iterations
-    pub fn run_optimization<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>) {
+    pub fn run_optimization<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>) -> Result<(), Error> {
         println!("\n");
         for i in 0..self.iterations {
             println!("Bayesian Optimization Iteration: {}", i);
@@ -64,14 +66,16 @@ impl BayesianOptimizer {
                 print_info(&self.hyperparameters, &self.variances, &self.observed_fitnesses[i-1]);
             }
 
-            self.iterate(client);
+            self.iterate(client)?;
         }
+
+        Ok(())
     }
     
     // Performs one iteration of the Bayesian optimization process
-    fn iterate<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>) {
+    fn iterate<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>) -> Result<(), Error> {
         // Calculate new hyperparameters
-        self.calculate_new_hyperparameters();
+        self.calculate_new_hyperparameters(client.rng())?;
 
         // Predict the fitness based on the updated hyperparameters
         let predicted_fitness = self.predict_fitness();
@@ -94,19 +98,20 @@ impl BayesianOptimizer {
         };
 
         // Run the fuzzer with the new configs and get the fitness score
-        client.fuzz(new_configs, false);
-        let observed_fitness = client.evaluate();
+        client.fuzz(new_configs, false)?;
+        let observed_fitness = client.evaluate()?;
 
         // Update the variances based on the fitness score
-        self.update_variances(predicted_fitness, observed_fitness);
+        self.update_variances(predicted_fitness, observed_fitness, client.rng());
 
         // Update the observed fitnesses
         self.observed_fitnesses.push(observed_fitness);
 
+        Ok(())
     }
     
     // Calculates the new hyperparameters based on the current ones and their variances
-    fn calculate_new_hyperparameters(&mut self) {
+    fn calculate_new_hyperparameters(&mut self, rng: &mut FuzzRng) -> Result<(), Error> {
         for i in 0..self.hyperparameters.len() {
             // Skip hyperparameters that are not being optimized (generations and message pool size)
             if i == 0 || i == 6 {
@@ -114,18 +119,21 @@ impl BayesianOptimizer {
             }
 
             // Create a normal distribution with the current hyperparameter as the mean and its variance as the standard deviation
-            let normal = Normal::new(self.hyperparameters[i] as f64, self.variances[i] as f64).unwrap();
-            let sample = normal.sample(&mut rand::thread_rng()) as f32;
+            let normal = Normal::new(self.hyperparameters[i] as f64, self.variances[i] as f64)
+                .map_err(|e| Error::Optimizer(format!("hyperparameter {} with variance {}: {}", i, self.variances[i], e)))?;
+            let sample = normal.sample(rng) as f32;
 
             // Clip the sample to [0, 1] to ensure it stays within the bounds of the hyperparameter
             let clipped_sample = sample.max(0.0).min(1.0);
 
             self.hyperparameters[i] = clipped_sample;
         }
+
+        Ok(())
     }
 
     // Update the variances based on the fitness scores of the hyperparameters
-    fn update_variances(&mut self, predicted_fitness: f32, observed_fitness: f32) {
+    fn update_variances(&mut self, predicted_fitness: f32, observed_fitness: f32, rng: &mut FuzzRng) {
         // Set the min and max slope to normalize the difference term
         let min_slope = -5.0;
         let max_slope = 5.0;
@@ -140,7 +148,7 @@ impl BayesianOptimizer {
             let mut difference = (observed_fitness - predicted_fitness).abs() / (max_slope - min_slope);
 
             // Add slight noise to the difference to encourage exploration
-            let noise = rand::thread_rng().gen_range(0.00..0.06);
+            let noise = rng.gen_range(0.00..0.06);
             difference = difference + noise;
 
             // Clip the difference to [0, 1] to ensure it stays within the bounds of the variance
@@ -4,6 +4,8 @@ use rand;
 use crate::FuzzConfig;
 use crate::Client;
 use crate::Protocol;
+use crate::FuzzRng;
+use crate::Error;
 
 
 #[derive(Clone, Debug)]
@@ -28,9 +30,7 @@ pub struct Swarm {
 }
 
 impl Particle {
-    fn random(generations: usize, message_pool_size: usize, vmax: f32) -> Particle {
-        let mut rng = rand::thread_rng();
-
+    fn random(generations: usize, message_pool_size: usize, vmax: f32, rng: &mut FuzzRng) -> Particle {
         let velocity = FuzzConfig {
             generations:                0,
             selection_pressure:         rng.gen_range(-vmax..vmax),
@@ -71,10 +71,10 @@ impl Particle {
         }
     }
 
-    fn evaluate_fitness<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>, regularization_strength: f32) -> f32 {
-        client.fuzz(self.position.clone(), false);
+    fn evaluate_fitness<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>, regularization_strength: f32) -> Result<f32, Error> {
+        client.fuzz(self.position.clone(), false)?;
 
-        let slope_of_best_fit_line = client.evaluate();
+        let slope_of_best_fit_line = client.evaluate()?;
 
         let l2_norm = self.position.selection_pressure.powi(2) 
                      + self.position.sequence_mutation_rate.powi(2)
@@ -91,7 +91,7 @@ impl Particle {
         
         let fitness = slope_of_best_fit_line - regularization_term;
         
-        fitness
+        Ok(fitness)
     }
 
     fn update_particle<P: Protocol+PartialEq>(
@@ -103,8 +103,8 @@ impl Particle {
         global_best_position: &FuzzConfig,
         regularization_strength: f32,
         vmax: f32,
-    ) {
-        let fitness = self.evaluate_fitness(client, regularization_strength);
+    ) -> Result<(), Error> {
+        let fitness = self.evaluate_fitness(client, regularization_strength)?;
 
         if fitness > self.personal_best_fitness {
             self.personal_best_fitness = fitness;
@@ -112,7 +112,7 @@ impl Particle {
         }
 
         // Update velocity and position
-        let mut rng = rand::thread_rng();
+        let rng = client.rng();
         
         self.velocity.selection_pressure = inertial_weight * self.velocity.selection_pressure
         + cognitive_weight * rng.gen::<f32>() * (self.personal_best_position.selection_pressure - self.position.selection_pressure)
@@ -231,6 +231,8 @@ impl Particle {
         self.position.response_time_weight = self.position.response_time_weight.max(0.0).min(1.0);
         self.position.state_roc_weight = self.position.state_roc_weight.max(0.0).min(1.0);
         self.position.state_rarity_weight = self.position.state_rarity_weight.max(0.0).min(1.0);
+
+        Ok(())
     }
 }
 
@@ -245,10 +247,11 @@ impl Swarm {
         social_weight: f32,
         regularization_strength: f32,
         vmax: f32,
+        rng: &mut FuzzRng,
     ) -> Swarm {
         let mut particles = Vec::new();
         for _ in 0..num_particles {
-            particles.push(Particle::random(generations, message_pool_size, vmax));
+            particles.push(Particle::random(generations, message_pool_size, vmax, rng));
         }
 
         Swarm {
@@ -278,7 +281,8 @@ impl Swarm {
         }
     }
 
-    pub fn run_swarm<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>) {
+    // Every particle runs a full fuzzing campaign, so the first one which fails ends the search
+    pub fn run_swarm<P: Protocol+PartialEq>(&mut self, client: &mut Client<P>) -> Result<(), Error> {
         let mut count: usize = 0;
         let initial_inertia = self.inertial_weight;
         let final_inertia = self.inertial_weight * 0.2;
@@ -304,7 +308,7 @@ impl Swarm {
                     &self.global_best_position, 
                     self.regularization_strength,
                     self.vmax,
-                );
+                )?;
     
                 // Update the global best position if necessary
                 if particle.personal_best_fitness > self.global_best_fitness {
@@ -318,6 +322,8 @@ impl Swarm {
         }
         println!("\nPSO Complete!");
         print_position(&self.global_best_position);
+
+        Ok(())
     }
 }
 
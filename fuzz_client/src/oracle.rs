@@ -0,0 +1,287 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Serialize, Deserialize};
+
+use crate::Protocol;
+use crate::MessageSequence;
+use crate::Delivery;
+use crate::Response;
+use crate::TransportProtocol;
+use crate::Transport;
+use crate::ProcessExit;
+use crate::SanitizerReport;
+use crate::sanitizer::Fnv1a;
+use crate::Error;
+
+
+// The oracle's judgement of what happened to the target after a message was sent. The
+// variants are ordered by severity so the worst verdict of a sequence is simply the maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Verdict {
+    // The target answered the message
+    Reply,
+    // The target closed the connection in an orderly way, e.g. after QUIT
+    Closed,
+    // No answer arrived before the read timed out
+    Hang,
+    // The connection was reset or aborted, or writing to it failed
+    Reset,
+    // The target refused the connection, i.e. nothing is listening anymore
+    Refused,
+    // The supervised target process exited
+    Died,
+}
+
+impl Verdict {
+    pub fn is_crash(&self) -> bool {
+        matches!(self, Verdict::Reset | Verdict::Refused | Verdict::Died)
+    }
+
+    pub fn is_hang(&self) -> bool {
+        *self == Verdict::Hang
+    }
+
+    // Classify the result of reading a response. An empty TCP read means the peer sent FIN,
+    // while an empty datagram response only means the target did not answer that datagram.
+    pub fn from_receive(result: &Result<Response, Error>, transport: &dyn Transport) -> Self {
+        Verdict::from_response(result, transport.is_datagram())
+    }
+
+    // Classify the result of reading a response from a connection which is not a Transport,
+    // e.g. one of the async engine, given whether it carries datagrams
+    pub fn from_response(result: &Result<Response, Error>, datagram: bool) -> Self {
+        match result {
+            Ok(response) if response.data.is_empty() && !datagram => Verdict::Closed,
+            Ok(_) => Verdict::Reply,
+            Err(e) => Verdict::from_error(e),
+        }
+    }
+
+    // Classify an error returned by connect, send or receive
+    pub fn from_error(error: &Error) -> Self {
+        match error.io_error().map(|e| e.kind()) {
+            Some(io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => Verdict::Hang,
+            // A Unix socket whose file is gone or a pipe target which cannot be launched is not listening either
+            Some(io::ErrorKind::ConnectionRefused | io::ErrorKind::NotFound) => Verdict::Refused,
+            _ => Verdict::Reset,
+        }
+    }
+}
+
+// The worst Verdict seen while running a MessageSequence, the index of the message which
+// caused it and a description of what the oracle observed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    pub verdict: Verdict,
+    pub message_index: usize,
+    pub detail: String,
+    // Exit status and stderr of the target, if it is supervised and died
+    #[serde(default)]
+    pub process_exit: Option<ProcessExit>,
+}
+
+impl Outcome {
+    pub fn new() -> Self {
+        Self {
+            verdict: Verdict::Reply,
+            message_index: 0,
+            detail: String::new(),
+            process_exit: None,
+        }
+    }
+
+    // Keep the first occurrence of the most severe verdict
+    pub fn record(&mut self, verdict: Verdict, message_index: usize, detail: String) {
+        if verdict > self.verdict {
+            self.verdict = verdict;
+            self.message_index = message_index;
+            self.detail = detail;
+        }
+    }
+
+    // The supervisor saw the target die, which confirms whatever the socket suggested. A crash
+    // seen on the socket keeps its message index, otherwise the last message sent is blamed
+    pub fn record_exit(&mut self, process_exit: ProcessExit, last_message_index: usize) {
+        if !self.verdict.is_crash() {
+            self.message_index = last_message_index;
+        }
+
+        self.detail = if self.detail.is_empty() {
+            process_exit.describe()
+        } else {
+            format!("{} ({})", process_exit.describe(), self.detail)
+        };
+        self.verdict = Verdict::Died;
+        self.process_exit = Some(process_exit);
+    }
+
+    pub fn is_interesting(&self) -> bool {
+        self.verdict.is_crash() || self.verdict.is_hang()
+    }
+
+    pub fn sanitizer_report(&self) -> Option<&SanitizerReport> {
+        self.process_exit.as_ref()?.sanitizer.as_ref()
+    }
+
+    // The key crashes are grouped under: the sanitizer's stack hash if the target printed a
+    // report, otherwise only the verdict and the signal the target died of
+    pub fn bucket(&self) -> String {
+        if let Some(report) = self.sanitizer_report() {
+            return report.bucket();
+        }
+
+        match self.process_exit.as_ref().and_then(|process_exit| process_exit.signal) {
+            Some(signal) => format!("{:?}-signal-{}", self.verdict, signal).to_lowercase(),
+            None => format!("{:?}", self.verdict).to_lowercase(),
+        }
+    }
+}
+
+// All crashes of one bucket seen during a fuzzing run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrashBucket {
+    pub bug_class: String,
+    pub top_frame: String,
+    pub hits: usize,
+    pub reproducer: PathBuf,
+}
+
+impl CrashBucket {
+    pub fn new(outcome: &Outcome, reproducer: PathBuf) -> Self {
+        let (bug_class, top_frame) = match outcome.sanitizer_report() {
+            Some(report) => (report.bug_class.clone(), report.frames.first().cloned().unwrap_or_default()),
+            None => (format!("{:?}", outcome.verdict).to_lowercase(), String::new()),
+        };
+
+        Self {
+            bug_class,
+            top_frame,
+            hits: 0,
+            reproducer,
+        }
+    }
+}
+
+// Everything needed to send a crashing or hanging MessageSequence to the target again
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Reproducer<P: Protocol> {
+    pub target: String,
+    pub transport: TransportProtocol,
+    pub outcome: Outcome,
+    pub replay_command: String,
+    pub sequence: MessageSequence<P>,
+    // The ServerState parsed from each response when the sequence was saved, which a replay
+    // can be checked against
+    #[serde(default)]
+    pub states: Vec<P::ServerState>,
+}
+
+impl<P: Protocol> Reproducer<P> {
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+
+    // Write the reproducer into crashes/ or hangs/ below output_directory. The file is named
+    // after a hash of the sequence's bytes and timings so the same sequence is only saved once.
+    // A crash with a sanitizer report gets a directory per unique bug instead, holding the first
+    // reproducer of that bug and the target's stderr.
+    pub fn save(&mut self, output_directory: &Path, protocol_name: &str, campaign_file: Option<&Path>) -> Result<PathBuf, io::Error> {
+        let directory = if self.outcome.verdict.is_crash() {
+            output_directory.join("crashes")
+        } else {
+            output_directory.join("hangs")
+        };
+        fs::create_dir_all(&directory)?;
+
+        let path = match self.outcome.sanitizer_report() {
+            Some(report) => {
+                let bug_directory = directory.join(report.bucket());
+                fs::create_dir_all(&bug_directory)?;
+
+                let stderr_path = bug_directory.join("stderr.txt");
+                if !stderr_path.exists() {
+                    fs::write(&stderr_path, &self.outcome.process_exit.as_ref().unwrap().stderr)?;
+                }
+                bug_directory.join("reproducer.json")
+            }
+            None => directory.join(format!("{:?}-{:016x}.json", self.outcome.verdict, sequence_hash(&self.sequence)).to_lowercase()),
+        };
+
+        if !path.exists() {
+            self.save_as(&path, protocol_name, campaign_file)?;
+        }
+
+        Ok(path)
+    }
+
+    // Write the reproducer to path, replacing any file there, with a replay command for that path
+    pub fn save_as(&mut self, path: &Path, protocol_name: &str, campaign_file: Option<&Path>) -> Result<(), io::Error> {
+        self.replay_command = self.command_for(path, protocol_name, campaign_file);
+
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    // The command line which sends the sequence saved at path again. Only the plain socket
+    // transports are fully described by the command line, pipe and TLS targets also need the
+    // settings of their campaign file, and in-process and custom transports only exist in the
+    // program which registered them
+    fn command_for(&self, path: &Path, protocol_name: &str, campaign_file: Option<&Path>) -> String {
+        let config = match (&self.transport, campaign_file) {
+            (TransportProtocol::InProcess | TransportProtocol::Custom(_), _) => {
+                return format!("replay via library: Client::replay with the sequence of {} over the {} transport", path.display(), self.transport);
+            }
+            (_, Some(campaign_file)) => format!(" --config {}", campaign_file.display()),
+            (TransportProtocol::Pipe | TransportProtocol::TLS, None) => {
+                return format!("fuzz_client replay --config <campaign file of the run> --sequence {}", path.display());
+            }
+            (_, None) => String::new(),
+        };
+
+        format!(
+            "fuzz_client replay{} --protocol {} --target {} --transport {} --sequence {}",
+            config,
+            protocol_name,
+            self.target,
+            self.transport,
+            path.display(),
+        )
+    }
+}
+
+// Reproducer names must keep matching their sequences across Rust versions, so they are hashed
+// with FNV-1a like the sanitizer buckets rather than with the standard library's hasher
+fn sequence_hash<P: Protocol>(sequence: &MessageSequence<P>) -> u64 {
+    let mut hasher = Fnv1a::new();
+    for message in &sequence.messages {
+        hasher.write_piece(&message.data);
+        // A crash may need its message written in pieces, so the delivery is part of the sequence
+        match &message.delivery {
+            Delivery::Whole => hasher.write(&[0]),
+            Delivery::Fragmented { offsets, delay } => {
+                hasher.write(&[1]);
+                hasher.write(&(offsets.len() as u64).to_le_bytes());
+                for offset in offsets {
+                    hasher.write(&(*offset as u64).to_le_bytes());
+                }
+                hasher.write(&delay.to_bits().to_le_bytes());
+            }
+            Delivery::Slow { delay } => {
+                hasher.write(&[2]);
+                hasher.write(&delay.to_bits().to_le_bytes());
+            }
+            Delivery::Pipelined => hasher.write(&[3]),
+        }
+    }
+    for timing in &sequence.timings {
+        hasher.write(&timing.to_bits().to_le_bytes());
+    }
+    for step in &sequence.controls {
+        hasher.write(&(step.before as u64).to_le_bytes());
+        hasher.write_piece(step.control.to_string().as_bytes());
+    }
+    hasher.finish()
+}
@@ -12,14 +12,18 @@ use std::fmt::Formatter;
 use std::fmt;
 use std::fmt::Debug;
 
+use serde::{Serialize, Deserialize};
+
 use crate::Protocol;
-use crate::Message;
+use crate::FuzzRng;
+use crate::Error;
+use crate::{Message, Delivery};
 use crate::Response;
 use crate::MessageSequence;
 use crate::Transport;
 
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Default)]
 pub struct GreetingProtocol;
 
 
@@ -32,11 +36,10 @@ impl Protocol for GreetingProtocol {
 
 	type ServerState = GreetingServerState;
 
-	fn random_message(&self) -> Message<Self> {
-		let mut rng = rand::thread_rng();
+	fn random_message(&self, rng: &mut FuzzRng) -> Message<Self> {
 		let possible_payloads: [&str; 3] = ["Hello!\n", "What time is it?\n", "Goodbye!\n"];
 	
-		let payload = possible_payloads.choose(&mut rng).unwrap();
+		let payload = possible_payloads.choose(rng).unwrap();
 	    let (message_type, header) = match *payload {
 	        "Hello!\n" => (GreetingMessageType::Hello, [0x48, 0x45, 0x4C, 0x4F]),
 	        "What time is it?\n" => (GreetingMessageType::TimeRequest, [0x54, 0x49, 0x4D, 0x45]),
@@ -68,6 +71,7 @@ impl Protocol for GreetingProtocol {
 	        message_type,
 	        response_time,
 	        sections,
+	        delivery: Delivery::Whole,
 	    }
 	}
 
@@ -92,16 +96,16 @@ impl Protocol for GreetingProtocol {
 
 	    let payload_str = std::str::from_utf8(payload);
 
-	    // If the payload does not match any of the standard payloads, 
-	    // a random MessageType is selected
+	    // If the payload does not match any of the standard payloads, the 
+	    // MessageType is picked from the bytes themselves. Building a message
+	    // draws nothing from the FuzzRng, so the same bytes always give the same message
 	    let message_type = match payload_str {
 	        Ok("Hello!\n") => GreetingMessageType::Hello,
 	        Ok("What time is it?\n") => GreetingMessageType::TimeRequest,
 	        Ok("Goodbye!\n") => GreetingMessageType::Goodbye,
 	        _ => {
-	            let mut rng = rand::thread_rng();
-	            let random_type = rng.gen_range(0..3);
-	            match random_type {
+	            let byte_sum = message_bytes.iter().fold(0usize, |sum, byte| sum.wrapping_add(*byte as usize));
+	            match byte_sum % 3 {
 	                0 => GreetingMessageType::Hello,
 	                1 => GreetingMessageType::TimeRequest,
 	                2 => GreetingMessageType::Goodbye,
@@ -135,15 +139,17 @@ impl Protocol for GreetingProtocol {
 	        message_type,
 	        response_time,
 	        sections,
+	        delivery: Delivery::Whole,
 	    }
 	}
 
 	fn parse_response(&self, response: &Response) -> GreetingServerState {
-	    let response_str: String = String::from_utf8(response.data.clone()).unwrap();
+	    // The target may answer with anything, so malformed responses are read as leniently as possible
+	    let response_str: String = String::from_utf8_lossy(&response.data).into_owned();
 	    let response_parts: Vec<&str> = response_str.split(";").collect();
+	    let response_code = response_parts.first().and_then(|response_code_str| response_code_str.parse::<u16>().ok());
 
-	    if let [response_code_str, status_message, payload] = response_parts.as_slice() {
-	        let response_code = response_code_str.parse::<u16>().unwrap();
+	    if let ([_, status_message, payload], Some(response_code)) = (response_parts.as_slice(), response_code) {
 	        let status_message: String = String::from(*status_message);
 	        let payload: String = String::from(*payload);
 
@@ -162,38 +168,38 @@ impl Protocol for GreetingProtocol {
 	    }
 	}
 
-	fn mutate_message(&self, message: &Message<Self>) -> Message<Self> {
+	fn mutate_message(&self, message: &Message<Self>, rng: &mut FuzzRng) -> Message<Self> {
 		// Randomly choose between byte-level mutation or section-level mutation
-		let mut rng = rand::thread_rng();
 		let mutation_level = rng.gen_range(0..2);  
 
 		match mutation_level {
-			0 => mutate_bytes(message),
-			1 => mutate_sections(message),
+			0 => mutate_bytes(message, rng),
+			1 => mutate_sections(message, rng),
 			_ => panic!("Unexpected mutation_level value"),
 		}
 	}
 
-	fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>) -> (Message<Self>, Message<Self>) {
+	fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>, rng: &mut FuzzRng) -> (Message<Self>, Message<Self>) {
 		// Randomly choose between byte-level or section-level crossover
-		let mut rng = rand::thread_rng();
 		let crossover_level = rng.gen_range(0..2);
 
 		match crossover_level {
-			0 => crossover_bytes(message1, message2),
-			1 => crossover_sections(message1, message2),
+			0 => crossover_bytes(message1, message2, rng),
+			1 => crossover_sections(message1, message2, rng),
 			_ => panic!("Unexpected crossover_level value"),
 		}
 	}
 
-	fn parse_pcap(&self, pcap_file: &str, server_socket: &str) -> Vec<MessageSequence<Self>> {
-		todo!();
+	fn name(&self) -> String {
+		String::from("greeting")
 	}
+
+	// Every message the client sent in one go is a request, so each connection is a sequence,
+	// as parse_connections does by default
 }
 
 // Mutation helper functions
-fn mutate_bytes(message: &Message<GreetingProtocol>) -> Message<GreetingProtocol> {
-	let mut rng = rand::thread_rng();
+fn mutate_bytes(message: &Message<GreetingProtocol>, rng: &mut FuzzRng) -> Message<GreetingProtocol> {
 	let mutation_type = rng.gen_range(0..5);
 
 	let mut mutated_data = message.data.clone();
@@ -207,13 +213,13 @@ fn mutate_bytes(message: &Message<GreetingProtocol>) -> Message<GreetingProtocol
 		0 => {
 			// Byte substitution
 			let byte_index = rng.gen_range(0..mutated_data.len());
-			let random_byte = rand::random::<u8>();
+			let random_byte = rng.gen::<u8>();
 			mutated_data[byte_index] = random_byte;
 		}
 		1 => {
 			// Byte insertion
 			let byte_index = rng.gen_range(0..=mutated_data.len());
-			let random_byte = rand::random::<u8>();
+			let random_byte = rng.gen::<u8>();
 			mutated_data.insert(byte_index, random_byte);
 		}
 		2 => {
@@ -240,8 +246,7 @@ fn mutate_bytes(message: &Message<GreetingProtocol>) -> Message<GreetingProtocol
 	return mutated_message;
 }
 
-fn mutate_sections(message: &Message<GreetingProtocol>) -> Message<GreetingProtocol> {
-	let mut rng = rand::thread_rng();
+fn mutate_sections(message: &Message<GreetingProtocol>, rng: &mut FuzzRng) -> Message<GreetingProtocol> {
 	let mutation_type = rng.gen_range(0..3);
 
 	let mut mutated_sections = message.sections.clone();
@@ -301,7 +306,7 @@ fn mutate_sections(message: &Message<GreetingProtocol>) -> Message<GreetingProto
 			// Replace random byte into payload
 			let mut payload = mutated_sections.get(&GreetingMessageSectionsKey::Payload).unwrap().payload.clone();
 			let byte_index = rng.gen_range(0..payload.len());
-			let random_byte = rand::random::<u8>();
+			let random_byte = rng.gen::<u8>();
 			payload[byte_index] = random_byte;
 			
 			mutated_sections.insert(
@@ -324,9 +329,8 @@ fn mutate_sections(message: &Message<GreetingProtocol>) -> Message<GreetingProto
 }
 
 // Crossover helper functions
-fn crossover_bytes(message1: &Message<GreetingProtocol>, message2: &Message<GreetingProtocol>) -> (Message<GreetingProtocol>, Message<GreetingProtocol>) {
+fn crossover_bytes(message1: &Message<GreetingProtocol>, message2: &Message<GreetingProtocol>, rng: &mut FuzzRng) -> (Message<GreetingProtocol>, Message<GreetingProtocol>) {
 	// Logic for two-point crossover 
-	let mut rng = rand::thread_rng();
 
 	// This instance is needed to access the methods within the Protocol implementation
 	// of GreetingProtocol
@@ -360,8 +364,7 @@ fn crossover_bytes(message1: &Message<GreetingProtocol>, message2: &Message<Gree
 	return (offspring1, offspring2);
 }
 
-fn crossover_sections(message1: &Message<GreetingProtocol>, message2: &Message<GreetingProtocol>) -> (Message<GreetingProtocol>, Message<GreetingProtocol>) {
-    let mut rng = rand::thread_rng();
+fn crossover_sections(message1: &Message<GreetingProtocol>, message2: &Message<GreetingProtocol>, rng: &mut FuzzRng) -> (Message<GreetingProtocol>, Message<GreetingProtocol>) {
     
 	// This instance is needed to access the methods within the Protocol implementation
 	// of GreetingProtocol
@@ -401,21 +404,21 @@ fn crossover_sections(message1: &Message<GreetingProtocol>, message2: &Message<G
     (offspring1, offspring2)
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub enum GreetingMessageType {
 	Hello,
 	TimeRequest,
 	Goodbye,
 }
 
-#[derive(Clone, Hash, Eq, PartialEq, Debug)]
+#[derive(Clone, Hash, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum GreetingMessageSectionsKey {
 	Header,
 	Length,
 	Payload,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
 pub struct GreetingMessageSectionsValue {
 	pub header: [u8; 4],
 	pub length: u64,
@@ -444,7 +447,7 @@ impl Default for GreetingMessageSectionsValue {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct GreetingServerState {
 	response_code: u16,
 	status_message: String,
@@ -6,11 +6,18 @@ use std::hash::Hash;
 use std::cmp::PartialEq;
 use std::fmt::Debug;
 
+use serde::{Serialize, Deserialize};
+
 use crate::Message;
+use crate::FuzzRng;
 use crate::Response;
 use crate::MessageSequence;
+use crate::Error;
+use crate::CapturedConnection;
 
 
+// Protocol requires Clone and Default, the latter is used to restore Messages from checkpoints
+#[derive(Clone, PartialEq, Default)]
 pub struct YourProtocol {
     // Add any required fields and states for your protocol here.
 }
@@ -22,22 +29,24 @@ impl Protocol for YourProtocol {
 
     type ServerState = YourProtocolServerState;
 
-    fn random_message(&self) -> Message<Self> {
-        // Generate a random message for your protocol and return it.
+    fn random_message(&self, rng: &mut FuzzRng) -> Message<Self> {
+        // Generate a random message for your protocol and return it. Draw every random
+        // choice from rng so that seeded runs can be reproduced.
         todo!();
     }
 
     fn build_message(&self, message_bytes: &[u8]) -> Message<Self> {
         // Build a message for your protocol from the given byte slice and return it.
+        // The same bytes must always give the same message.
         todo!();
     }
 
-    fn mutate_message(&self, message: &Message<Self>) -> Message<Self> {
+    fn mutate_message(&self, message: &Message<Self>, rng: &mut FuzzRng) -> Message<Self> {
         // Mutate the given message for your protocol and return the mutated message.
         todo!();
     }
 
-    fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>) -> (Message<Self>, Message<Self>) {
+    fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>, rng: &mut FuzzRng) -> (Message<Self>, Message<Self>) {
         // Perform crossover on the given messages for your protocol and return the resulting pair of messages.
         todo!();
     }
@@ -47,29 +56,31 @@ impl Protocol for YourProtocol {
         todo!();
     }
 
-    fn parse_pcap(&self, pcap_file: &str) -> Vec<MessageSequence<Self>> {
+    // The connections of a capture, reassembled. Without this method every message the client
+    // sent in one go is a request and each connection is a sequence, see request_sequences
+    fn parse_connections(&self, connections: &[CapturedConnection]) -> Vec<MessageSequence<Self>> {
         todo!();
     }
 }
 
 // Define your protocol-specific types below.
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct YourProtocolMessageType {
     // Add any required fields for your protocol message type here.
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize)]
 pub struct YourProtocolMessageSectionsKey {
     // Add any required fields for your protocol message sections key here.
 }
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct YourProtocolMessageSectionsValue {
     // Add any required fields for your protocol message sections value here.
 }
 
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
 pub struct YourProtocolServerState {
     // Add any required fields for your protocol server state here.
 }
@@ -95,7 +106,7 @@ impl Default for YourProtocolMessageSectionsValue {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct YourProtocolServerState {
     // Fields which make up structure of YourProtocol's server responses
 }
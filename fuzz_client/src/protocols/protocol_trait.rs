@@ -5,22 +5,37 @@
 use std::hash::Hash;
 use std::cmp::PartialEq;
 use std::fmt::Debug;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 
 use crate::Message;
 use crate::Response;
 use crate::MessageSequence;
+use crate::FuzzRng;
+use crate::Error;
+use crate::Framing;
+use crate::{read_connections, request_sequences, CapturedConnection};
 
 
 // The Protocol trait is a common interface which defines a set of shared behavior 
 // across all types which have an implementation of the Prtotocol trait.
 //
 // The declaration states that any type implementing the Protocol trait must 
-// also implement the Clone trait.
-pub trait Protocol: Sized+Clone {
-	type MessageType: PartialEq + Clone + Debug;
-	type MessageSectionsKey: PartialEq + Eq + Hash + Clone + Debug;
-	type MessageSectionsValue: PartialEq + Clone + Debug;
-	type ServerState: Clone + Eq + PartialEq + Hash + Debug;
+// also implement the Clone and Default traits. Default is needed to restore a 
+// Message from a checkpoint, since the protocol instance itself is not saved.
+//
+// The associated types must be serializable so that corpora, message pools and 
+// state models can be written to and read back from checkpoints.
+//
+// Everything must be Send and Sync since MessageSequences are run by several 
+// worker threads at once.
+pub trait Protocol: Sized+Clone+Default+Send+Sync {
+	type MessageType: PartialEq + Clone + Debug + Send + Sync + Serialize + DeserializeOwned;
+	type MessageSectionsKey: PartialEq + Eq + Hash + Clone + Debug + Send + Sync + Serialize + DeserializeOwned;
+	type MessageSectionsValue: PartialEq + Clone + Debug + Send + Sync + Serialize + DeserializeOwned;
+	type ServerState: Clone + Eq + PartialEq + Hash + Debug + Send + Sync + Serialize + DeserializeOwned;
 
 	// Note that Self is a type alias that refers to the implementing type, whereas
 	// &self is a reference to the instance of the implementing type. Here, by type
@@ -36,10 +51,66 @@ pub trait Protocol: Sized+Clone {
 	// you to write a method signature that is generic and works for any type implementing 
 	// the Protocol trait.
 
-	fn random_message(&self) -> Message<Self>;
+	//
+	// Every random choice is drawn from the rng passed in, never from thread_rng, 
+	// so that a run can be repeated from its seed. build_message takes no rng 
+	// since the same bytes must always build the same Message.
+
+	fn random_message(&self, rng: &mut FuzzRng) -> Message<Self>;
 	fn build_message(&self, message_bytes: &[u8]) -> Message<Self>;
-	fn mutate_message(&self, message: &Message<Self>) -> Message<Self>;
-	fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>) -> (Message<Self>, Message<Self>);
+	fn mutate_message(&self, message: &Message<Self>, rng: &mut FuzzRng) -> Message<Self>;
+	fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>, rng: &mut FuzzRng) -> (Message<Self>, Message<Self>);
 	fn parse_response(&self, response: &Response) -> Self::ServerState;
-	fn parse_pcap(&self, pcap_file: &str, server_socket: &str) -> Vec<MessageSequence<Self>>;
+
+	// The seed corpus found in the TCP connections to server_socket in a capture file
+	fn parse_pcap(&self, pcap_file: &str, server_socket: &str) -> Result<Vec<MessageSequence<Self>>, Error> {
+		Ok(self.parse_connections(&read_connections(pcap_file, server_socket)?))
+	}
+
+	// The seed corpus found in connections read from a capture. Defaults to one sequence per
+	// connection, with every message the client sent in one go as a request
+	fn parse_connections(&self, connections: &[CapturedConnection]) -> Vec<MessageSequence<Self>> {
+		request_sequences(self, connections)
+	}
+
+	// How the target's responses are split from the stream. Defaults to one response per line.
+	fn framing(&self) -> Framing {
+		Framing::line()
+	}
+
+	// Whether the target agreed to switch the connection to TLS in its response to message, e.g.
+	// with a 220 reply to STARTTLS. The rest of the sequence is then sent inside the TLS session.
+	fn starts_tls(&self, message: &Message<Self>, response: &Response) -> bool {
+		false
+	}
+
+	// Whether starts_tls can ever return true, i.e. whether the protocol has such an upgrade at all
+	fn can_start_tls(&self) -> bool {
+		false
+	}
+
+	// Whether the target speaks first, e.g. with SMTP's 220 banner. The greeting is then read
+	// right after connecting and gives the state the first message is sent in.
+	fn sends_greeting(&self) -> bool {
+		false
+	}
+
+	// A message which brings the target back to a known state between two sequences sent over
+	// the same connection, e.g. SMTP's RSET. None if the protocol has none.
+	fn reset_message(&self) -> Option<Message<Self>> {
+		None
+	}
+
+	// How long to wait for the response to message, given the campaign's read timeout. Lets a
+	// protocol give slow commands, e.g. the end of an SMTP mail body, a longer budget.
+	fn read_timeout(&self, message: &Message<Self>, default: Duration) -> Duration {
+		default
+	}
+
+	// The name the protocol is selected by on the command line, used in replay commands. 
+	// Defaults to the lowercased name of the implementing type.
+	fn name(&self) -> String {
+		let type_name = std::any::type_name::<Self>();
+		type_name.rsplit("::").next().unwrap_or(type_name).to_lowercase()
+	}
 }
\ No newline at end of file
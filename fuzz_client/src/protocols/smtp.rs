@@ -14,27 +14,23 @@ use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use std::fmt::Formatter;
 use std::fmt;
-use pcap::Capture;
-use pnet::packet::ethernet::EthernetPacket;
-use pnet::packet::ipv4::Ipv4Packet;
-use pnet::packet::ipv4::Ipv4;
-use pnet::packet::Packet;
-use pnet::packet::tcp::TcpPacket;
-use std::net::Ipv4Addr;
 use rand;
 use rand::distributions::Alphanumeric;
 
-use pnet::packet::ip::IpNextHeaderProtocols;
-use pnet::packet::ethernet::EtherTypes;
+use serde::{Serialize, Deserialize};
 
 use crate::Protocol;
-use crate::Message;
+use crate::FuzzRng;
+use crate::Error;
+use crate::Framing;
+use crate::{Message, Delivery};
 use crate::Response;
 use crate::MessageSequence;
 use crate::Transport;
+use crate::CapturedConnection;
 
 
-#[derive(Clone, PartialEq, Copy)]
+#[derive(Clone, PartialEq, Copy, Default)]
 pub struct SMTP;
 
 impl Protocol for SMTP {
@@ -44,14 +40,12 @@ impl Protocol for SMTP {
 
     type ServerState = SMTPServerState;
 
-    fn random_message(&self) -> Message<Self> {
-        let mut rng = rand::thread_rng();
+    fn random_message(&self, rng: &mut FuzzRng) -> Message<Self> {
         let message_types = SMTPMessageType::iter().collect::<Vec<_>>();
         let index = rng.gen_range(0..message_types.len());
         let selected_message_type = message_types[index].clone();
 
         // Generate random domains and email addresses
-        let mut rng = rand::thread_rng();
         let sender_local_length = rng.gen_range(1..=100);
         let recipient_local_length = rng.gen_range(1..=100);
         let sender_domain_length = rng.gen_range(1..=100);
@@ -137,7 +131,6 @@ impl Protocol for SMTP {
                 let subject = format!("Subject: {}{}\r\n", sender_domain, recipient_domain);
             
                 // Generate a random body.
-                let mut rng = rand::thread_rng();
                 let body_length = rng.gen_range(1..=1000);
                 let mut body: String = (0..body_length)
                     .map(|_| {
@@ -172,6 +165,12 @@ impl Protocol for SMTP {
                     SMTPMessageSectionsValue::CommandValue(String::from("RSET\r\n")),
                 );
             }
+            SMTPMessageType::STARTTLS => {
+                sections.insert(
+                    SMTPMessageSectionsKey::Command,
+                    SMTPMessageSectionsValue::CommandValue(String::from("STARTTLS\r\n")),
+                );
+            }
             SMTPMessageType::VRFY => {
                 sections.insert(
                     SMTPMessageSectionsKey::Command,
@@ -201,7 +200,9 @@ impl Protocol for SMTP {
         let response_time = 0.0;
         let mut data: Vec<u8> = Vec::new();
 
-        for (_, value) in sections.iter() {
+        // The sections are concatenated in the order they appear on the wire, which is 
+        // the order SMTPMessageSectionsKey declares them in
+        for value in SMTPMessageSectionsKey::iter().filter_map(|key| sections.get(&key)) {
             match value {
                 SMTPMessageSectionsValue::CommandValue(s)
                 | SMTPMessageSectionsValue::DomainValue(s)
@@ -218,6 +219,7 @@ impl Protocol for SMTP {
             message_type: selected_message_type,
             response_time,
             sections,
+            delivery: Delivery::Whole,
         }
     }
 
@@ -242,6 +244,8 @@ impl Protocol for SMTP {
             SMTPMessageType::QUIT
         } else if lines[0].to_uppercase().starts_with("RSET") {
             SMTPMessageType::RSET
+        } else if lines[0].to_uppercase().starts_with("STARTTLS") {
+            SMTPMessageType::STARTTLS
         } else if lines[0].to_uppercase().starts_with("VRFY") {
             SMTPMessageType::VRFY
         } else if lines[0].to_uppercase().starts_with("EXPN") {
@@ -360,6 +364,12 @@ impl Protocol for SMTP {
                     SMTPMessageSectionsValue::CommandValue(String::from(lines[0]) + "\r\n"),
                 );
             },
+            SMTPMessageType::STARTTLS => {
+                sections.insert(
+                    SMTPMessageSectionsKey::Command,
+                    SMTPMessageSectionsValue::CommandValue(String::from(lines[0]) + "\r\n"),
+                );
+            },
             SMTPMessageType::VRFY => {
                 sections.insert(
                     SMTPMessageSectionsKey::Command,
@@ -395,29 +405,28 @@ impl Protocol for SMTP {
             message_type,
             response_time,
             sections,
+            delivery: Delivery::Whole,
         }
     }
 
-    fn mutate_message(&self, message: &Message<Self>) -> Message<Self> {
+    fn mutate_message(&self, message: &Message<Self>, rng: &mut FuzzRng) -> Message<Self> {
         // Randomly choose between byte-level or section-level mutation
-		let mut rng = rand::thread_rng();
 		let mutation_level = rng.gen_range(0..2);  
 
 		match mutation_level {
-			0 => mutate_bytes(message),
-			1 => mutate_sections(message),
+			0 => mutate_bytes(message, rng),
+			1 => mutate_sections(message, rng),
 			_ => panic!("Unexpected mutation_level value"),
 		}
     }
 
-    fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>) -> (Message<Self>, Message<Self>) {
+    fn crossover_messages(&self, message1: &Message<Self>, message2: &Message<Self>, rng: &mut FuzzRng) -> (Message<Self>, Message<Self>) {
 		// Randomly choose between byte-level or section-level crossover
-		let mut rng = rand::thread_rng();
 		let crossover_level = rng.gen_range(0..2);
 
 		match crossover_level {
-			0 => crossover_bytes(message1, message2),
-			1 => crossover_sections(message1, message2),
+			0 => crossover_bytes(message1, message2, rng),
+			1 => crossover_sections(message1, message2, rng),
 			_ => panic!("Unexpected crossover_level value"),
 		}
     }
@@ -445,112 +454,61 @@ impl Protocol for SMTP {
         }
     }
      
+    fn name(&self) -> String {
+        String::from("smtp")
+    }
 
-    // This method takes a path to a file path to a pcap file as an argument and extracts out 
-    // the SMTP messages from the pcap file, then processes the messages in order to determine
-    // which seqeunces to bundle together as a MessageSequence. Finally, the collection
-    // of all MessageSequences are returned as a vector.
-    fn parse_pcap(&self, pcap_file: &str, server_socket: &str) -> Vec<MessageSequence<Self>> {
-        
-        // Get the server address and port from the given socket and convert them to Ipv4Addr and u16 types, respectively.
-        let server_ip_str = server_socket.split(":").collect::<Vec<_>>()[0];
-        let server_address = server_ip_str.parse::<Ipv4Addr>().unwrap();
-        let server_port = server_socket.split(":").collect::<Vec<_>>()[1].parse::<u16>().unwrap();
-
-        // HashMap to store the payloads of each request.
-        let mut request_payloads: HashMap<u32, Vec<Vec<u8>>> = HashMap::new();
-
-        let mut cap = Capture::from_file(pcap_file).unwrap();
-
-        while let Ok(packet) = cap.next() {
-
-            let packet_data = packet.data.to_owned();
-            
-            // Parse Ethernet, IP, and TCP headers to get application layer data.
-            let ethernet = EthernetPacket::new(&packet_data).unwrap();  
-            let ip = Ipv4Packet::new(ethernet.payload()).unwrap();         
-
-            if let Some(tcp) = TcpPacket::new(ip.payload()) {
-                let dst_ip = ip.get_destination();
-                let dst_port = tcp.get_destination();
-
-                // Check if the packet is a request or response.
-                let is_request = dst_ip == server_address && dst_port == server_port;
-
-                // If the packet is a request, then we need to get the data from the TCP payload.
-                // We want to group the TCP payloads based on the TCP sequence number.
-                // After all the payloads with the same sequence number have been collected, we 
-                // can then combine them into a single payload and create a Message from it by 
-                // sending the combined data to the build_message method.
-                //
-                // We will use request_payloads HashMap to store the payloads. The key will be the sequence
-                // number and the value will be a vector of the payloads with that sequence number.
-
-                if is_request {
-                    let seq_num = tcp.get_sequence();
-                    let payload = tcp.payload().to_owned();
-
-                    // Check if the sequence number is already in the map.
-                    if let Some(payloads) = request_payloads.get_mut(&seq_num) {
-                        // If the sequence number is already in the map, then we need to append the payload to the existing vector.
-                        payloads.push(payload);
-                    } else {
-                        // If the sequence number is not in the map, then we need to create a new vector and add it to the map.
-                        let payloads = vec![payload];
-                        request_payloads.insert(seq_num, payloads);
-                    }
-                } 
-            }
-            else {
-                continue;
-            }
-        }
+    // Replies like the EHLO capability list span several "250-" lines
+    fn framing(&self) -> Framing {
+        Framing::SmtpMultiline
+    }
 
-        // We can now combine the payload groups into a single payloads and create a Messages from it.
-        let mut messages: Vec<Message<Self>> = Vec::new();
+    // Every connection starts with the server's 220 banner
+    fn sends_greeting(&self) -> bool {
+        true
+    }
 
-        // Iterate through the request_payloads HashMap and combine the payloads into a single payload.
-        for (_, payloads) in request_payloads.iter() {
-            let mut data: Vec<u8> = Vec::new();
+    // RSET ends the current mail transaction and keeps the session
+    fn reset_message(&self) -> Option<Message<Self>> {
+        Some(self.build_message(b"RSET\r\n"))
+    }
 
-            for payload in payloads.iter() {
-                data.extend(payload);
-            }
+    // The 220 reply to STARTTLS is the last thing sent in plain text
+    fn starts_tls(&self, message: &Message<Self>, response: &Response) -> bool {
+        message.message_type == SMTPMessageType::STARTTLS && response.data.starts_with(b"220")
+    }
 
-            // Create a Message from the combined payload.
-            let message = self.build_message(&data);
-            messages.push(message);
-        }
+    fn can_start_tls(&self) -> bool {
+        true
+    }
 
+    // This method takes a path to a file path to a pcap file as an argument and extracts out 
+    // the SMTP commands the clients sent to server_socket, connection by connection, then
+    // processes the messages in order to determine which seqeunces to bundle together as a
+    // MessageSequence. Finally, the collection of all MessageSequences are returned as a vector.
+    fn parse_connections(&self, connections: &[CapturedConnection]) -> Vec<MessageSequence<Self>> {
         let mut message_sequences: Vec<MessageSequence<Self>> = Vec::new();
-        let mut current_sequence: Vec<Message<Self>> = Vec::new();
-
-        // Iterate through the messages and use the MAIL_FROM command to denote the start of a new sequence.
-        // If the message is not a MAIL_FROM command, the message gets added to the current sequence. 
-        // Once a MAIL_FROM command is encountered, the current sequence is pushed to the message_sequences vector
-        // and a new sequence is started.
-        for message in messages {
-            match message.message_type {
-                SMTPMessageType::MAIL_FROM => {
-                    // If there's an ongoing sequence, push it to the sequences list
-                    if !current_sequence.is_empty() {
-                        let timings: Vec<f32> = vec![1.0; current_sequence.len()];
-                        message_sequences.push(MessageSequence::from_messages(current_sequence, timings));
-                    }
-                    // Start a new sequence with the current message
-                    current_sequence = vec![message];
-                }
-                _ => {
-                    // If it's not a MAIL FROM message, just add it to the current sequence
-                    current_sequence.push(message);
+
+        // Each connection starts a new sequence. Within a connection, the MAIL_FROM command 
+        // denotes the start of a new sequence as well: once one is encountered, the current 
+        // sequence is pushed to the message_sequences vector and a new sequence is started.
+        for connection in connections {
+            let mut current_sequence: Vec<Message<Self>> = Vec::new();
+
+            for request in connection.requests() {
+                let message = self.build_message(request);
+                if message.message_type == SMTPMessageType::MAIL_FROM && !current_sequence.is_empty() {
+                    let timings: Vec<f32> = vec![1.0; current_sequence.len()];
+                    message_sequences.push(MessageSequence::from_messages(current_sequence, timings));
+                    current_sequence = Vec::new();
                 }
+                current_sequence.push(message);
             }
-        }
 
-        // Check if the last sequence is empty. If it's not empty, then add it to the message_sequences vector.
-        if !current_sequence.is_empty() {
-            let timings: Vec<f32> = vec![1.0; current_sequence.len()];
-            message_sequences.push(MessageSequence::from_messages(current_sequence, timings));
+            if !current_sequence.is_empty() {
+                let timings: Vec<f32> = vec![1.0; current_sequence.len()];
+                message_sequences.push(MessageSequence::from_messages(current_sequence, timings));
+            }
         }
 
         message_sequences
@@ -558,8 +516,7 @@ impl Protocol for SMTP {
 }
 
 // Mutation helper functions
-fn mutate_bytes(message: &Message<SMTP>) -> Message<SMTP> {
-	let mut rng = rand::thread_rng();
+fn mutate_bytes(message: &Message<SMTP>, rng: &mut FuzzRng) -> Message<SMTP> {
 	let mutation_type = rng.gen_range(0..5);
 
 	let mut mutated_data = message.data.clone();
@@ -577,13 +534,13 @@ fn mutate_bytes(message: &Message<SMTP>) -> Message<SMTP> {
 		0 => {
 			// Byte substitution
 			let byte_index = rng.gen_range(0..mutated_data.len());
-			let random_byte = rand::random::<u8>();
+			let random_byte = rng.gen::<u8>();
 			mutated_data[byte_index] = random_byte;
 		}
 		1 => {
 			// Byte insertion
 			let byte_index = rng.gen_range(0..=mutated_data.len());
-			let random_byte = rand::random::<u8>();
+			let random_byte = rng.gen::<u8>();
 			mutated_data.insert(byte_index, random_byte);
 		}
 		2 => {
@@ -610,53 +567,55 @@ fn mutate_bytes(message: &Message<SMTP>) -> Message<SMTP> {
 	return mutated_message;
 }
 
-fn mutate_sections(message: &Message<SMTP>) -> Message<SMTP> {
+fn mutate_sections(message: &Message<SMTP>, rng: &mut FuzzRng) -> Message<SMTP> {
     // TODO: Determine which message type we are mutating and this will dictate which sections
     //       we can mutate. For example, we can't mutate the MAIL_FROM section of a DATA message.
     match message.message_type {
         SMTPMessageType::HELO => {
-            mutate_helo_ehlo(&message)
+            mutate_helo_ehlo(&message, rng)
         }
         SMTPMessageType::EHLO => {
-            mutate_helo_ehlo(&message)
+            mutate_helo_ehlo(&message, rng)
         }
         SMTPMessageType::MAIL_FROM => {
-            mutate_mail_from_rcpt_to(&message)
+            mutate_mail_from_rcpt_to(&message, rng)
         }
         SMTPMessageType::RCPT_TO => {
-            mutate_mail_from_rcpt_to(&message)
+            mutate_mail_from_rcpt_to(&message, rng)
         }
         SMTPMessageType::DATA => {
-            mutate_command_only(&message)
+            mutate_command_only(&message, rng)
         }
         SMTPMessageType::EMAIL_CONTENT => {
-            mutate_email_content(&message)
+            mutate_email_content(&message, rng)
         }
         SMTPMessageType::QUIT => {
-            mutate_command_only(&message)
+            mutate_command_only(&message, rng)
         }
         SMTPMessageType::RSET => {
-            mutate_command_only(&message)
+            mutate_command_only(&message, rng)
+        }
+        SMTPMessageType::STARTTLS => {
+            mutate_command_only(&message, rng)
         }
         SMTPMessageType::VRFY => {
-            mutate_command_only(&message)
+            mutate_command_only(&message, rng)
         }
         SMTPMessageType::EXPN => {
-            mutate_command_only(&message)
+            mutate_command_only(&message, rng)
         }
         SMTPMessageType::HELP => {
-            mutate_command_only(&message)
+            mutate_command_only(&message, rng)
         }
         SMTPMessageType::NOOP => {
-            mutate_command_only(&message)
+            mutate_command_only(&message, rng)
         }
     }
 }
 
 // Crossover helper functions
-fn crossover_bytes(message1: &Message<SMTP>, message2: &Message<SMTP>) -> (Message<SMTP>, Message<SMTP>) {
+fn crossover_bytes(message1: &Message<SMTP>, message2: &Message<SMTP>, rng: &mut FuzzRng) -> (Message<SMTP>, Message<SMTP>) {
 	// Logic for two-point crossover 
-	let mut rng = rand::thread_rng();
 
 	// This instance is needed to access the methods within the Protocol implementation
 	// of SMTP
@@ -694,12 +653,11 @@ fn crossover_bytes(message1: &Message<SMTP>, message2: &Message<SMTP>) -> (Messa
 	return (offspring1, offspring2);
 }
 
-fn crossover_sections(message1: &Message<SMTP>, message2: &Message<SMTP>) -> (Message<SMTP>, Message<SMTP>) {
+fn crossover_sections(message1: &Message<SMTP>, message2: &Message<SMTP>, rng: &mut FuzzRng) -> (Message<SMTP>, Message<SMTP>) {
     if message1.message_type != message2.message_type {
         return (message1.clone(), message2.clone());
     }
 
-    let mut rng = rand::thread_rng();
 
 	// This instance is needed to access the methods within the Protocol implementation
 	// of GreetingProtocol
@@ -734,7 +692,7 @@ fn crossover_sections(message1: &Message<SMTP>, message2: &Message<SMTP>) -> (Me
                 }
             };
         },
-        SMTPMessageType::DATA | SMTPMessageType::QUIT | SMTPMessageType::RSET => {
+        SMTPMessageType::DATA | SMTPMessageType::QUIT | SMTPMessageType::RSET | SMTPMessageType::STARTTLS => {
             for key in &[SMTPMessageSectionsKey::Command] {
                 if rng.gen_bool(0.5) {
                     offspring1_sections.insert(key.clone(), message1.sections.get(key).unwrap().clone());
@@ -804,7 +762,7 @@ fn crossover_sections(message1: &Message<SMTP>, message2: &Message<SMTP>) -> (Me
                 let new_data: Vec<u8> = [&command[..], &formatting_mark1[..], &email_address[..], &formatting_mark2[..]].concat();
                 protocol_instance.build_message(&new_data)
             },
-            SMTPMessageType::DATA | SMTPMessageType::QUIT | SMTPMessageType::RSET => {
+            SMTPMessageType::DATA | SMTPMessageType::QUIT | SMTPMessageType::RSET | SMTPMessageType::STARTTLS => {
                 let command = match sections.get(&SMTPMessageSectionsKey::Command) {
                     Some(SMTPMessageSectionsValue::CommandValue(command)) => command.clone().into_bytes(),
                     _ => panic!("Expected a CommandValue in sections."),
@@ -843,8 +801,7 @@ fn crossover_sections(message1: &Message<SMTP>, message2: &Message<SMTP>) -> (Me
     (offspring1, offspring2)
 }
 
-fn mutate_helo_ehlo(message: &Message<SMTP>) -> Message<SMTP> {
-	let mut rng = rand::thread_rng();
+fn mutate_helo_ehlo(message: &Message<SMTP>, rng: &mut FuzzRng) -> Message<SMTP> {
 	let mutation_type = rng.gen_range(0..3);
 
 	let mut mutated_sections = message.sections.clone();
@@ -1000,8 +957,7 @@ fn mutate_helo_ehlo(message: &Message<SMTP>) -> Message<SMTP> {
 	return mutated_message;
 }
 
-fn mutate_mail_from_rcpt_to(message: &Message<SMTP>) -> Message<SMTP> {
-	let mut rng = rand::thread_rng();
+fn mutate_mail_from_rcpt_to(message: &Message<SMTP>, rng: &mut FuzzRng) -> Message<SMTP> {
 	let mutation_type = rng.gen_range(0..3);
 
 	let mut mutated_sections = message.sections.clone();
@@ -1201,8 +1157,7 @@ fn mutate_mail_from_rcpt_to(message: &Message<SMTP>) -> Message<SMTP> {
 	return mutated_message;
 }
 
-fn mutate_command_only(message: &Message<SMTP>) -> Message<SMTP> {
-	let mut rng = rand::thread_rng();
+fn mutate_command_only(message: &Message<SMTP>, rng: &mut FuzzRng) -> Message<SMTP> {
 
 	let mut mutated_sections = message.sections.clone();
 	let mutated_message: Message<SMTP>;
@@ -1220,6 +1175,9 @@ fn mutate_command_only(message: &Message<SMTP>) -> Message<SMTP> {
         SMTPMessageType::RSET => {
             String::from("RSET")
         }
+        SMTPMessageType::STARTTLS => {
+            String::from("STARTTLS")
+        }
         SMTPMessageType::VRFY => {
             String::from("VRFY")
         }
@@ -1327,7 +1285,7 @@ fn mutate_command_only(message: &Message<SMTP>) -> Message<SMTP> {
     let command = command_string.into_bytes();
 
     match message.message_type {
-        SMTPMessageType::DATA | SMTPMessageType::QUIT | SMTPMessageType::RSET => {
+        SMTPMessageType::DATA | SMTPMessageType::QUIT | SMTPMessageType::RSET | SMTPMessageType::STARTTLS => {
             let crnl = "\r\n".to_string().into_bytes();
             let new_data: Vec<u8> = [&command[..], &crnl].concat();
             mutated_message = protocol_instance.build_message(&new_data);
@@ -1345,8 +1303,7 @@ fn mutate_command_only(message: &Message<SMTP>) -> Message<SMTP> {
 	return mutated_message;
 }
 
-fn mutate_email_content(message: &Message<SMTP>) -> Message<SMTP> {
-	let mut rng = rand::thread_rng();
+fn mutate_email_content(message: &Message<SMTP>, rng: &mut FuzzRng) -> Message<SMTP> {
 
 	let mut mutated_sections = message.sections.clone();
 	let mutated_message: Message<SMTP>;
@@ -1398,7 +1355,7 @@ fn mutate_email_content(message: &Message<SMTP>) -> Message<SMTP> {
 
 // Define your protocol-specific types below.
 
-#[derive(EnumIter, PartialEq, Clone, Debug)]
+#[derive(EnumIter, PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub enum SMTPMessageType {
     HELO,
     EHLO,
@@ -1408,13 +1365,14 @@ pub enum SMTPMessageType {
     EMAIL_CONTENT,
     QUIT,
     RSET,
+    STARTTLS,
     VRFY,
     EXPN,
     HELP,
     NOOP,
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+#[derive(EnumIter, PartialEq, Eq, Hash, Clone, Debug, Serialize, Deserialize)]
 pub enum SMTPMessageSectionsKey {
     Command,
     Domain,
@@ -1422,7 +1380,7 @@ pub enum SMTPMessageSectionsKey {
     PlainText,
 }
 
-#[derive(PartialEq, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub enum SMTPMessageSectionsValue {
     CommandValue(String),
     DomainValue(String),
@@ -1451,7 +1409,7 @@ impl Default for SMTPMessageSectionsValue {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct SMTPServerState {
     // Fields which make up structure of SMTP's server responses
     pub status_code: u16,
@@ -0,0 +1,19 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+
+// Every random decision of a run, from the initial corpus over mutation and crossover to the
+// hyperparameter optimizers, is drawn from one FuzzRng owned by the Client. Seeding it with
+// the same value makes two runs against a deterministic target produce the same corpora and
+// state models. ChaCha12 is the algorithm behind rand's StdRng, but unlike StdRng it is
+// guaranteed to stay the same across rand versions and its state can be saved in a checkpoint.
+pub type FuzzRng = ChaCha12Rng;
+
+pub fn seeded_rng(seed: u64) -> FuzzRng {
+    FuzzRng::seed_from_u64(seed)
+}
+
+// A seed for runs where the user did not choose one. It is printed so the run can be repeated
+pub fn random_seed() -> u64 {
+    rand::random()
+}
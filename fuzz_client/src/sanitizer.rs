@@ -0,0 +1,146 @@
+use serde::{Serialize, Deserialize};
+
+
+// The bug class and the top stack frames of an AddressSanitizer or UndefinedBehaviorSanitizer
+// report found in the stderr of the target. Crashes with the same bug class and top frames
+// are the same bug, so they share a stack_hash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SanitizerReport {
+    pub sanitizer: String,
+    pub bug_class: String,
+    pub frames: Vec<String>,
+    pub stack_hash: u64,
+}
+
+impl SanitizerReport {
+    // Parse the first sanitizer report in stderr, keeping at most stack_depth frames.
+    // Returns None if stderr does not contain a report.
+    pub fn parse(stderr: &str, stack_depth: usize) -> Option<Self> {
+        let lines: Vec<&str> = stderr.lines().collect();
+
+        let (index, sanitizer, bug_class, location) = lines.iter().enumerate().find_map(|(index, line)| {
+            parse_asan_header(line)
+                .map(|(sanitizer, bug_class)| (index, sanitizer, bug_class, None))
+                .or_else(|| parse_ubsan_header(line).map(|(bug_class, location)| {
+                    (index, String::from("UndefinedBehaviorSanitizer"), bug_class, Some(location))
+                }))
+        })?;
+
+        // The first stack trace after the header is where the bug happened. Later ones tell
+        // where the memory was allocated or freed and are not part of the signature
+        let mut frames = Vec::new();
+        for line in &lines[index + 1..] {
+            match parse_frame(line) {
+                Some(frame) => frames.push(frame),
+                None if !frames.is_empty() => break,
+                None => continue,
+            }
+            if frames.len() == stack_depth {
+                break;
+            }
+        }
+
+        // UBSan prints no stack trace unless print_stacktrace=1, the source location is all there is
+        if frames.is_empty() {
+            frames.extend(location);
+        }
+
+        let mut signature = bug_class.clone();
+        for frame in &frames {
+            signature.push('\n');
+            signature.push_str(frame);
+        }
+
+        Some(Self {
+            sanitizer,
+            bug_class,
+            frames,
+            stack_hash: fnv1a(signature.as_bytes()),
+        })
+    }
+
+    // Name of the directory crashes of this bug are collected in, e.g. heap-buffer-overflow-<hash>
+    pub fn bucket(&self) -> String {
+        let bug_class: String = self.bug_class
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+
+        format!("{}-{:016x}", bug_class.trim_matches('-'), self.stack_hash)
+    }
+}
+
+// ==1234==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x602000000011 at pc ...
+// ==1234==ERROR: AddressSanitizer: SEGV on unknown address 0x000000000000 ...
+fn parse_asan_header(line: &str) -> Option<(String, String)> {
+    let (_, report) = line.split_once("ERROR: ")?;
+    let (sanitizer, description) = report.split_once(": ")?;
+    if !sanitizer.ends_with("Sanitizer") {
+        return None;
+    }
+
+    let bug_class = description.split_whitespace().next()?.to_string();
+    Some((sanitizer.to_string(), bug_class))
+}
+
+// smtpd.c:120:9: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+fn parse_ubsan_header(line: &str) -> Option<(String, String)> {
+    let (location, description) = line.split_once(": runtime error: ")?;
+    let bug_class = description.split(':').next()?.trim().to_string();
+    Some((bug_class, location.trim().to_string()))
+}
+
+//     #3 0x55d0c8e4a1b2 in parse_command /src/smtpd.c:120:9
+//     #4 0x7f3a1c029d8f  (/lib/x86_64-linux-gnu/libc.so.6+0x29d8f)
+// The frame number and the address are dropped since they change with ASLR
+fn parse_frame(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let rest = line.strip_prefix('#')?;
+    let (number, rest) = rest.split_once(' ')?;
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let rest = rest.trim_start();
+    let rest = match rest.split_once(' ') {
+        Some((address, rest)) if address.starts_with("0x") => rest,
+        _ => rest,
+    };
+
+    let frame = rest.trim().strip_prefix("in ").unwrap_or(rest.trim());
+    Some(frame.to_string())
+}
+
+// The stack hash names directories which must stay the same across runs and Rust versions,
+// which the standard library's hasher does not promise
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+// FNV-1a over bytes written in several pieces, for names of files and directories
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // Prefixed with its length, so that two pieces cannot run into each other
+    pub fn write_piece(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -4,6 +4,8 @@ use std::cmp::PartialEq;
 use std::fmt::Write;
 use std::fmt::Debug;
 
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
 use crate::Protocol;
 use crate::StateTransition;
 use crate::Message;
@@ -25,29 +27,34 @@ impl<T: Protocol + PartialEq> StateModel<T> {
         }
     }
 
-    // This method is used to add a StateTransition to the StateModel
+    // This method is used to add a StateTransition, seen once, to the StateModel
     pub fn add(&mut self, source_state: T::ServerState, target_state: T::ServerState, message: &Message<T>) {
+        self.insert(StateTransition {
+            source_state,
+            target_state,
+            message: message.clone(),
+            occurrences: 1,
+        });
+    }
+
+    // Add a StateTransition with the number of times it was seen
+    pub fn insert(&mut self, new_transition: StateTransition<T::ServerState, T>) {
     	// This line retreives the transitions associated with the current_state
     	// If there are no transistions associated to current_state, a Vec::new() 
     	// is inserted in its place
         let transitions = self
             .inner
-            .entry(source_state.clone())
+            .entry(new_transition.source_state.clone())
             .or_insert_with(Vec::new);
 
-        let new_transition = StateTransition {
-            source_state: source_state.clone(),
-            target_state: target_state.clone(),
-            message: message.clone(),
-        };
-
         // Checks if there is already a transition in the transitions vector that has
-        // the same next_state, current_state, and message as new_transition. If there is
-        // no duplicate transition, then new_transition is added to the transitions vector
-        if !transitions.iter().any(|t| {
-            t.target_state == target_state && t.source_state == source_state && t.message == *message
+        // the same next_state, current_state, and message as new_transition. If there is,
+        // it was seen once more, otherwise new_transition is added to the transitions vector
+        match transitions.iter_mut().find(|t| {
+            t.target_state == new_transition.target_state && t.source_state == new_transition.source_state && t.message == new_transition.message
         }) {
-            transitions.push(new_transition);
+            Some(transition) => transition.occurrences += new_transition.occurrences,
+            None => transitions.push(new_transition),
         }
     }
 
@@ -102,6 +109,41 @@ impl<T: Protocol + PartialEq> StateModel<T> {
 
 }
 
+impl<T: Protocol> PartialEq for StateModel<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<T: Protocol> Clone for StateModel<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+// A ServerState is not a valid JSON object key, so the StateModel is serialized as the flat
+// list of its StateTransitions and rebuilt from that list with StateModel::insert
+impl<T: Protocol> Serialize for StateModel<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.inner.values().flatten())
+    }
+}
+
+impl<'de, T: Protocol + PartialEq> Deserialize<'de> for StateModel<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let transitions: Vec<StateTransition<T::ServerState, T>> = Vec::deserialize(deserializer)?;
+
+        let mut state_model = StateModel::new();
+        for transition in transitions {
+            state_model.insert(transition);
+        }
+
+        Ok(state_model)
+    }
+}
+
 fn escape_label(label: &str) -> String {
     label.replace("\"", "\\\"").replace("\n", "")
 }
@@ -1,3 +1,6 @@
+use serde::{Serialize, Deserialize};
+use serde::de::DeserializeOwned;
+
 use crate::Protocol;
 use crate::Message;
 
@@ -10,8 +13,37 @@ use crate::Message;
 // The 'P: Protocol' constraint enforces that the type 'P' must implement the 
 // Protocol trait. This ensures that the Message<P> field must be a message that 
 // belongs to a protocol that implements the Protocol trait.  
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "S: Serialize", deserialize = "S: DeserializeOwned"))]
 pub struct StateTransition<S, P: Protocol> {
 	pub source_state: S,
 	pub message: Message<P>,
 	pub target_state: S,
-}
\ No newline at end of file
+	// How many times the transition was seen. Transitions saved before this was counted
+	// are read back as seen once
+	#[serde(default = "single_occurrence")]
+	pub occurrences: usize,
+}
+
+fn single_occurrence() -> usize {
+	1
+}
+impl<S: PartialEq, P: Protocol> PartialEq for StateTransition<S, P> {
+	fn eq(&self, other: &Self) -> bool {
+		self.source_state == other.source_state
+			&& self.message == other.message
+			&& self.target_state == other.target_state
+			&& self.occurrences == other.occurrences
+	}
+}
+
+impl<S: Clone, P: Protocol> Clone for StateTransition<S, P> {
+	fn clone(&self) -> Self {
+		Self {
+			source_state: self.source_state.clone(),
+			message: self.message.clone(),
+			target_state: self.target_state.clone(),
+			occurrences: self.occurrences,
+		}
+	}
+}
@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{Child, ChildStderr, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::{Serialize, Deserialize};
+
+use crate::TransportProtocol;
+use crate::SanitizerReport;
+
+
+// Only the end of the target's stderr is kept, which is where sanitizer reports and panics are
+const STDERR_LIMIT: usize = 64 * 1024;
+
+// How long to wait for a dying target to be reaped after the oracle saw a crash
+pub(crate) const EXIT_GRACE_PERIOD: Duration = Duration::from_millis(200);
+
+// How the server under test is launched when the fuzzer supervises it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessSettings {
+    pub program: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub working_directory: Option<PathBuf>,
+    // Seconds to wait for the target to accept connections after it was started
+    #[serde(default = "ProcessSettings::default_startup_timeout")]
+    pub startup_timeout: f32,
+    // Number of stack frames of a sanitizer report which identify a unique bug
+    #[serde(default = "ProcessSettings::default_stack_depth")]
+    pub stack_depth: usize,
+}
+
+impl ProcessSettings {
+    pub fn new(program: String) -> Self {
+        Self {
+            program,
+            args: Vec::new(),
+            env: BTreeMap::new(),
+            working_directory: None,
+            startup_timeout: ProcessSettings::default_startup_timeout(),
+            stack_depth: ProcessSettings::default_stack_depth(),
+        }
+    }
+
+    fn default_startup_timeout() -> f32 {
+        5.0
+    }
+
+    fn default_stack_depth() -> usize {
+        5
+    }
+}
+
+// How the supervised target exited, saved with the crash record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessExit {
+    pub code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stderr: String,
+    // The ASan/UBSan report the target printed before it died, if it was built with a sanitizer
+    #[serde(default)]
+    pub sanitizer: Option<SanitizerReport>,
+}
+
+impl ProcessExit {
+    pub(crate) fn new(status: ExitStatus, stderr: String, stack_depth: usize) -> Self {
+        Self {
+            code: status.code(),
+            signal: status.signal(),
+            sanitizer: SanitizerReport::parse(&stderr, stack_depth),
+            stderr,
+        }
+    }
+
+    // Killed by a signal or caught by a sanitizer, rather than exiting on its own
+    pub fn is_crash(&self) -> bool {
+        self.signal.is_some() || self.sanitizer.is_some()
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.code, self.signal) {
+            (_, Some(signal)) => format!("target killed by signal {}", signal),
+            (Some(code), _) => format!("target exited with code {}", code),
+            (None, None) => String::from("target exited"),
+        }
+    }
+}
+
+// A Supervisor owns the process of the server under test. It starts the process before the
+// first MessageSequence, notices when it has died and starts it again before the next one.
+pub struct Supervisor {
+    settings: ProcessSettings,
+    server_address: String,
+    transport_protocol: TransportProtocol,
+    child: Option<Child>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    stderr_reader: Option<JoinHandle<()>>,
+    // How often the target died and had to be started again
+    pub exits: usize,
+}
+
+impl Supervisor {
+    pub fn new(settings: ProcessSettings, server_address: String, transport_protocol: TransportProtocol) -> Self {
+        Self {
+            settings,
+            server_address,
+            transport_protocol,
+            child: None,
+            stderr: Arc::new(Mutex::new(Vec::new())),
+            stderr_reader: None,
+            exits: 0,
+        }
+    }
+
+    // Start the target if it is not running. Returns once the target accepts connections,
+    // or with an error if it could not be started or did not come up in time
+    pub fn ensure_running(&mut self) -> Result<(), io::Error> {
+        if self.child.is_some() {
+            return Ok(());
+        }
+
+        let mut command = Command::new(&self.settings.program);
+        command
+            .args(&self.settings.args)
+            .envs(&self.settings.env)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+
+        if let Some(working_directory) = &self.settings.working_directory {
+            command.current_dir(working_directory);
+        }
+
+        let mut child = command.spawn()?;
+
+        let (stderr, stderr_reader) = drain_stderr(child.stderr.take().unwrap());
+        self.stderr = stderr;
+        self.stderr_reader = Some(stderr_reader);
+
+        self.child = Some(child);
+        self.wait_until_ready()
+    }
+
+    // Poll the target until it accepts a connection. Datagram targets cannot be probed without
+    // sending them a datagram, and a custom transport's address may mean anything, so those
+    // are given a short moment to bind instead
+    fn wait_until_ready(&mut self) -> Result<(), io::Error> {
+        let deadline = Instant::now() + Duration::from_secs_f32(self.settings.startup_timeout);
+
+        if matches!(self.transport_protocol, TransportProtocol::UDP | TransportProtocol::UnixDatagram | TransportProtocol::Custom(_)) {
+            thread::sleep(Duration::from_millis(100).min(deadline.saturating_duration_since(Instant::now())));
+            return Ok(());
+        }
+
+        loop {
+            if let Some(exit) = self.poll_exit() {
+                return Err(io::Error::new(io::ErrorKind::Other, format!("{}: {}", exit.describe(), exit.stderr.trim())));
+            }
+
+            let accepted = match self.transport_protocol {
+                TransportProtocol::UnixStream => UnixStream::connect(&self.server_address).is_ok(),
+                _ => TcpStream::connect(&self.server_address).is_ok(),
+            };
+            if accepted {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, format!("{} did not accept connections on {}", self.settings.program, self.server_address)));
+            }
+
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    // Returns how the target exited if it is no longer running. The process is reaped and
+    // will be started again by the next call to ensure_running
+    pub fn poll_exit(&mut self) -> Option<ProcessExit> {
+        let status = match self.child.as_mut()?.try_wait() {
+            Ok(Some(status)) => status,
+            _ => return None,
+        };
+
+        self.child = None;
+        self.exits += 1;
+
+        // The pipe closes when the process dies, so the reader finishes with everything it wrote
+        if let Some(stderr_reader) = self.stderr_reader.take() {
+            let _ = stderr_reader.join();
+        }
+        let stderr = String::from_utf8_lossy(&self.stderr.lock().unwrap()).into_owned();
+
+        Some(ProcessExit::new(status, stderr, self.settings.stack_depth))
+    }
+
+    // Like poll_exit, but gives a target which is on its way down a moment to finish dying
+    pub fn wait_exit(&mut self) -> Option<ProcessExit> {
+        let deadline = Instant::now() + EXIT_GRACE_PERIOD;
+        loop {
+            if let Some(exit) = self.poll_exit() {
+                return Some(exit);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        if let Some(stderr_reader) = self.stderr_reader.take() {
+            let _ = stderr_reader.join();
+        }
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// Drain a child's stderr continuously so it never blocks on a full pipe, keeping the last
+// STDERR_LIMIT bytes. The reader finishes once the child has exited and closed the pipe
+pub(crate) fn drain_stderr(mut pipe: ChildStderr) -> (Arc<Mutex<Vec<u8>>>, JoinHandle<()>) {
+    let stderr = Arc::new(Mutex::new(Vec::new()));
+    let buffer_stderr = Arc::clone(&stderr);
+
+    let stderr_reader = thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        while let Ok(n) = pipe.read(&mut buffer) {
+            if n == 0 {
+                break;
+            }
+            let mut stderr = buffer_stderr.lock().unwrap();
+            stderr.extend_from_slice(&buffer[..n]);
+            if stderr.len() > STDERR_LIMIT {
+                let excess = stderr.len() - STDERR_LIMIT;
+                stderr.drain(..excess);
+            }
+        }
+    });
+
+    (stderr, stderr_reader)
+}
@@ -0,0 +1,65 @@
+use crate::Protocol;
+use crate::Message;
+use crate::Response;
+use crate::Outcome;
+use crate::{Control, ControlStep};
+
+
+// The interaction history of one MessageSequence run against the target: the greeting the
+// target sent on its own, if the protocol has one, every Message sent, paired with the
+// Response it got, and the oracle's Outcome for the whole sequence
+pub struct Trace<P: Protocol> {
+	pub greeting: Option<Response>,
+	// On a connection kept open from a previous sequence, the state that sequence left the target in
+	pub continued_from: Option<P::ServerState>,
+	// The protocol's reset message sent on a kept connection before the first message, with its response
+	pub reset: Option<(Message<P>, Response)>,
+	pub interactions: Vec<(Message<P>, Response)>,
+	// The control steps run between the messages, each with what the target sent meanwhile: the
+	// greeting of a reconnect and the data of a wait. A message which found no connection it
+	// could send on is preceded by a reconnect which is not part of the MessageSequence
+	pub controls: Vec<(ControlStep, Response)>,
+	pub outcome: Outcome,
+}
+
+impl<P: Protocol> Trace<P> {
+	pub fn new() -> Self {
+		Self {
+			greeting: None,
+			continued_from: None,
+			reset: None,
+			interactions: Vec::new(),
+			controls: Vec::new(),
+			outcome: Outcome::new(),
+		}
+	}
+
+	// The state the target was left in at the end of the trace, from the last thing it sent
+	pub fn final_state(&self, protocol: &P) -> Option<P::ServerState> {
+		let last_response = self.interactions.last().map(|(_, response)| response)
+			.or(self.reset.as_ref().map(|(_, response)| response))
+			.or(self.greeting.as_ref());
+
+		let mut state = match last_response {
+			Some(response) => Some(protocol.parse_response(response)),
+			None => self.continued_from.clone(),
+		};
+
+		for (step, received) in self.controls.iter().filter(|(step, _)| step.before >= self.interactions.len()) {
+			state = state_after_control(protocol, &step.control, received, state);
+		}
+		state
+	}
+}
+
+// The state the target is in after control, which received what the target sent meanwhile.
+// A new connection is in the state it greeted with, or in none known if it did not greet
+pub(crate) fn state_after_control<P: Protocol>(protocol: &P, control: &Control, received: &Response, state: Option<P::ServerState>) -> Option<P::ServerState> {
+	match control {
+		Control::Close => None,
+		Control::HalfClose => state,
+		Control::Reconnect if received.data.is_empty() => None,
+		Control::WaitForData(_) if received.data.is_empty() => state,
+		Control::Reconnect | Control::WaitForData(_) => Some(protocol.parse_response(received)),
+	}
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+use crate::Protocol;
+use crate::Trace;
+use crate::ControlStep;
+use crate::{Outcome, Verdict};
+
+
+// Width of the sent and received columns of a printed Transcript
+const COLUMN_WIDTH: usize = 48;
+
+// One exchange of a replayed MessageSequence: the bytes sent, the bytes received, the
+// ServerState parsed from them and how long the target took to answer
+pub struct TranscriptEntry<P: Protocol> {
+	pub sent: Vec<u8>,
+	pub received: Vec<u8>,
+	pub state: P::ServerState,
+	pub response_time: f32,
+}
+
+// The readable form of a Trace, returned by Client::replay. If the states recorded when the
+// sequence was saved are given, every observed ServerState is checked against them
+pub struct Transcript<P: Protocol> {
+	// What the target sent before the first message, with nothing sent and no response time
+	pub greeting: Option<TranscriptEntry<P>>,
+	pub entries: Vec<TranscriptEntry<P>>,
+	// The control steps run between the entries, with what the target sent during each
+	pub controls: Vec<(ControlStep, Vec<u8>)>,
+	pub expected_states: Vec<P::ServerState>,
+	pub outcome: Outcome,
+}
+
+impl<P: Protocol> Transcript<P> {
+	pub fn from_trace(protocol: &P, trace: Trace<P>, expected_states: &[P::ServerState]) -> Self {
+		let greeting = trace.greeting.map(|response| {
+			TranscriptEntry {
+				state: protocol.parse_response(&response),
+				sent: Vec::new(),
+				received: response.data,
+				response_time: 0.0,
+			}
+		});
+
+		let entries = trace.interactions.into_iter().map(|(message, response)| {
+			TranscriptEntry {
+				state: protocol.parse_response(&response),
+				sent: message.data,
+				received: response.data,
+				response_time: message.response_time,
+			}
+		}).collect();
+
+		let controls = trace.controls.into_iter().map(|(step, response)| (step, response.data)).collect();
+
+		Self {
+			greeting,
+			entries,
+			controls,
+			expected_states: expected_states.to_vec(),
+			outcome: trace.outcome,
+		}
+	}
+
+	// Indices of the messages whose ServerState differs from the expected one, including
+	// messages expected to be answered which were never sent because the sequence ended early.
+	// Empty if no states were expected
+	pub fn mismatches(&self) -> Vec<usize> {
+		if self.expected_states.is_empty() {
+			return Vec::new();
+		}
+
+		(0..self.entries.len().max(self.expected_states.len()))
+			.filter(|&index| self.entries.get(index).map(|entry| &entry.state) != self.expected_states.get(index))
+			.collect()
+	}
+}
+
+impl<P: Protocol> fmt::Display for Transcript<P> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "{:>3}  {:>8}  {:<width$} | RECEIVED", "#", "TIME", "SENT", width = COLUMN_WIDTH)?;
+
+		if let Some(greeting) = &self.greeting {
+			for (row, received) in wrap(&greeting.received).iter().enumerate() {
+				let index = if row == 0 { "-" } else { "" };
+				writeln!(f, "{:>3}  {:>8}  {:<width$} | {}", index, "", "", received, width = COLUMN_WIDTH)?;
+			}
+			writeln!(f, "{:>15}state: {}", "", one_line(&greeting.state))?;
+		}
+
+		for (index, entry) in self.entries.iter().enumerate() {
+			self.write_controls(f, |before| before == index)?;
+
+			let sent = wrap(&entry.sent);
+			let received = wrap(&entry.received);
+
+			for row in 0..sent.len().max(received.len()) {
+				let left = sent.get(row).map(String::as_str).unwrap_or("");
+				let right = received.get(row).map(String::as_str).unwrap_or("");
+
+				if row == 0 {
+					writeln!(f, "{:>3}  {:>7.3}s  {:<width$} | {}", index, entry.response_time, left, right, width = COLUMN_WIDTH)?;
+				} else {
+					writeln!(f, "{:>3}  {:>8}  {:<width$} | {}", "", "", left, right, width = COLUMN_WIDTH)?;
+				}
+			}
+
+			match self.expected_states.get(index) {
+				Some(expected) if *expected != entry.state => writeln!(f, "{:>15}state: {}  MISMATCH, expected {}", "", one_line(&entry.state), one_line(expected))?,
+				_ => writeln!(f, "{:>15}state: {}", "", one_line(&entry.state))?,
+			}
+		}
+
+		self.write_controls(f, |before| before >= self.entries.len())?;
+
+		for (index, expected) in self.expected_states.iter().enumerate().skip(self.entries.len()) {
+			writeln!(f, "{:>3}  not sent  MISMATCH, expected {}", index, one_line(expected))?;
+		}
+
+		if self.outcome.verdict != Verdict::Reply {
+			writeln!(f, "{:?} after message {}: {}", self.outcome.verdict, self.outcome.message_index, self.outcome.detail)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl<P: Protocol> Transcript<P> {
+	// A row for each control step placed where placed_here says, with the control in brackets
+	// in the sent column
+	fn write_controls(&self, f: &mut fmt::Formatter, placed_here: impl Fn(usize) -> bool) -> fmt::Result {
+		for (step, received) in self.controls.iter().filter(|(step, _)| placed_here(step.before)) {
+			let control = format!("[{}]", step.control);
+			for (row, right) in wrap(received).iter().enumerate() {
+				let left = if row == 0 { control.as_str() } else { "" };
+				writeln!(f, "{:>3}  {:>8}  {:<width$} | {}", "", "", left, right, width = COLUMN_WIDTH)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+// ServerStates of text protocols often end in a line break or span several lines
+fn one_line<T: fmt::Debug>(state: &T) -> String {
+	format!("{:?}", state).trim_end().replace('\r', "\\r").replace('\n', "\\n")
+}
+
+// Escape bytes so that binary protocols print readably, e.g. HELO\x00\x00\x00\x07Hello!\n,
+// and split them into rows of at most COLUMN_WIDTH characters without cutting an escape apart
+fn wrap(bytes: &[u8]) -> Vec<String> {
+	let mut rows = vec![String::new()];
+
+	for byte in bytes {
+		let escaped: String = std::ascii::escape_default(*byte).map(char::from).collect();
+		if rows.last().unwrap().len() + escaped.len() > COLUMN_WIDTH {
+			rows.push(String::new());
+		}
+		rows.last_mut().unwrap().push_str(&escaped);
+	}
+
+	rows
+}
@@ -0,0 +1,161 @@
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket, UnixStream};
+use tokio::time::{self, Instant};
+
+use crate::Response;
+use crate::Error;
+use crate::Framing;
+use crate::{TransportProtocol, TransportOptions, UdpSettings};
+use super::incomplete_response;
+
+
+// The connections the async engine drives without blocking a thread on them
+enum Connection {
+    Tcp(TcpStream),
+    UnixStream(UnixStream),
+    Udp(UdpSocket),
+}
+
+// A connection of the async engine, which receives like the blocking transports of the same
+// TransportProtocol do. Engine::supports lists the protocols it connects over
+pub(crate) struct AsyncTransport {
+    connection: Connection,
+    // Bytes which arrived after the end of the last response, i.e. the start of the next one
+    pending: Vec<u8>,
+    udp: UdpSettings,
+}
+
+impl AsyncTransport {
+    pub async fn connect(transport_protocol: &TransportProtocol, server_address: &str, options: &TransportOptions) -> Result<Self, Error> {
+        let connect_error = |e| Error::Connect(server_address.to_string(), e);
+        let connect_timeout = options.timeouts.connect_timeout();
+
+        let connection = match transport_protocol {
+            TransportProtocol::TCP => {
+                let tcp_stream = within(connect_timeout, TcpStream::connect(server_address)).await.map_err(connect_error)?;
+                // Like the blocking TcpTransport, so that the pieces of a fragmented Delivery stay apart
+                tcp_stream.set_nodelay(true).map_err(connect_error)?;
+                Connection::Tcp(tcp_stream)
+            }
+            TransportProtocol::UnixStream => Connection::UnixStream(within(connect_timeout, UnixStream::connect(server_address)).await.map_err(connect_error)?),
+            TransportProtocol::UDP => Connection::Udp(bind_udp(server_address).await.map_err(connect_error)?),
+            _ => return Err(Error::Parse(format!("the async engine cannot connect over {}", transport_protocol))),
+        };
+
+        Ok(Self {
+            connection,
+            pending: Vec::new(),
+            udp: options.udp.clone(),
+        })
+    }
+
+    pub async fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        let result = match &mut self.connection {
+            Connection::Tcp(tcp_stream) => tcp_stream.write_all(message).await,
+            Connection::UnixStream(unix_stream) => unix_stream.write_all(message).await,
+            Connection::Udp(socket) => socket.send(message).await.map(|_| ()),
+        };
+        result.map_err(Error::Send)
+    }
+
+    pub async fn receive(&mut self, framing: &Framing, timeout: Duration) -> Result<Response, Error> {
+        let buffer = match &mut self.connection {
+            Connection::Tcp(tcp_stream) => read_stream_response(tcp_stream, &mut self.pending, framing, timeout).await,
+            Connection::UnixStream(unix_stream) => read_stream_response(unix_stream, &mut self.pending, framing, timeout).await,
+            Connection::Udp(socket) => read_datagram_response(socket, &self.udp, timeout).await,
+        };
+        buffer.map(Response::new).map_err(Error::Receive)
+    }
+
+    // Stop sending but keep receiving, like Transport::shutdown_write
+    pub async fn shutdown_write(&mut self) -> Result<(), Error> {
+        let result = match &mut self.connection {
+            Connection::Tcp(tcp_stream) => tcp_stream.shutdown().await,
+            Connection::UnixStream(unix_stream) => unix_stream.shutdown().await,
+            Connection::Udp(_) => Ok(()),
+        };
+        result.map_err(Error::Send)
+    }
+
+    pub fn is_datagram(&self) -> bool {
+        matches!(self.connection, Connection::Udp(_))
+    }
+}
+
+// Await future, failing with io::ErrorKind::TimedOut if it takes longer than timeout
+async fn within<T>(timeout: Duration, future: impl Future<Output = Result<T, io::Error>>) -> Result<T, io::Error> {
+    time::timeout(timeout, future).await
+        .unwrap_or_else(|_| Err(io::Error::new(ErrorKind::TimedOut, "timed out")))
+}
+
+// Bind an ephemeral local port of the target's address family and connect it to the target,
+// like the blocking UdpTransport
+async fn bind_udp(server_address: &str) -> Result<UdpSocket, io::Error> {
+    let remote_address = tokio::net::lookup_host(server_address).await?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "address resolved to nothing"))?;
+
+    let local_address = if remote_address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let udp_socket = UdpSocket::bind(local_address).await?;
+    udp_socket.connect(remote_address).await?;
+    Ok(udp_socket)
+}
+
+// Read until framing finds a complete response, the same way as the blocking stream transports
+async fn read_stream_response<S: AsyncRead + Unpin>(stream: &mut S, pending: &mut Vec<u8>, framing: &Framing, timeout: Duration) -> Result<Vec<u8>, io::Error> {
+    let deadline = Instant::now() + timeout;
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        if let Some(frame_length) = framing.frame_length(pending) {
+            return Ok(pending.drain(..frame_length).collect());
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let timeout = match framing.quiet_period() {
+            Some(quiet_period) if !pending.is_empty() => quiet_period.min(remaining),
+            _ => remaining,
+        };
+
+        if timeout.is_zero() {
+            return incomplete_response(pending);
+        }
+
+        match time::timeout(timeout, stream.read(&mut chunk)).await {
+            Err(_) => return incomplete_response(pending),
+            Ok(Ok(0)) => return Ok(std::mem::take(pending)),
+            Ok(Ok(n)) => pending.extend_from_slice(&chunk[..n]),
+            Ok(Err(e)) if e.kind() == ErrorKind::Interrupted => continue,
+            Ok(Err(e)) => return Err(e),
+        }
+    }
+}
+
+// Collect the datagrams answering one request, the same way as the blocking datagram transports
+async fn read_datagram_response(socket: &UdpSocket, udp_settings: &UdpSettings, timeout: Duration) -> Result<Vec<u8>, io::Error> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut datagram = vec![0u8; udp_settings.receive_buffer.max(1)];
+
+    match time::timeout(timeout, socket.recv(&mut datagram)).await {
+        Ok(Ok(n)) => buffer.extend_from_slice(&datagram[..n]),
+        Ok(Err(e)) => return Err(e),
+        Err(_) if !udp_settings.expect_reply => return Ok(buffer),
+        Err(_) => return Err(io::Error::new(ErrorKind::TimedOut, "no response before the timeout")),
+    }
+
+    let quiet_period = Duration::from_secs_f32(udp_settings.quiet_period);
+    if quiet_period.is_zero() {
+        return Ok(buffer);
+    }
+
+    // A port unreachable after the first datagram is left for the next send or receive to report
+    while let Ok(Ok(n)) = time::timeout(quiet_period, socket.recv(&mut datagram)).await {
+        buffer.extend_from_slice(&datagram[..n]);
+    }
+
+    Ok(buffer)
+}
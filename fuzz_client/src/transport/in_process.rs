@@ -0,0 +1,98 @@
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::Response;
+use crate::Error;
+use crate::Framing;
+use crate::TransportOptions;
+use super::{Transport, incomplete_response};
+
+
+// A target which lives in the fuzzer's own process, e.g. a parser or state machine written in
+// Rust. It is handed every request and returns the bytes the target would have sent back.
+pub trait Handler: Send {
+    fn handle(&mut self, request: &[u8]) -> Vec<u8>;
+}
+
+impl<F: FnMut(&[u8]) -> Vec<u8> + Send> Handler for F {
+    fn handle(&mut self, request: &[u8]) -> Vec<u8> {
+        self(request)
+    }
+}
+
+// Creates a fresh Handler for every connection, so one MessageSequence cannot leave state
+// behind for the next, the way a new connection to a server starts a new session
+#[derive(Clone)]
+pub struct HandlerFactory(Arc<dyn Fn() -> Box<dyn Handler> + Send + Sync>);
+
+impl HandlerFactory {
+    pub fn new<H: Handler + 'static>(new_handler: impl Fn() -> H + Send + Sync + 'static) -> Self {
+        Self(Arc::new(move || Box::new(new_handler()) as Box<dyn Handler>))
+    }
+
+    pub fn create(&self) -> Box<dyn Handler> {
+        (self.0)()
+    }
+}
+
+// Run the handler on one request. A panic is the in-process equivalent of a crashing target,
+// so it is caught and returned as an error carrying the panic message
+fn handle_request(handler: &mut dyn Handler, request: &[u8]) -> Result<Vec<u8>, io::Error> {
+    panic::catch_unwind(AssertUnwindSafe(|| handler.handle(request))).map_err(|payload| {
+        let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| String::from("unknown panic"));
+        io::Error::new(io::ErrorKind::ConnectionAborted, format!("handler panicked: {}", message))
+    })
+}
+
+// Hands every request to a Handler of its own, so no networking is involved at all
+pub struct InProcessTransport {
+    handler: Box<dyn Handler>,
+    factory: HandlerFactory,
+    // What the handler returned and was not received yet
+    pending: Vec<u8>,
+}
+
+// The server address only names the target in reports, the handler comes from options.handler
+impl Transport for InProcessTransport {
+    fn connect(_server_address: &str, options: &TransportOptions) -> Result<Self, Error> {
+        let factory = options.handler.clone()
+            .ok_or_else(|| Error::Parse(String::from("the in-process transport needs a handler")))?;
+
+        Ok(Self {
+            handler: factory.create(),
+            factory,
+            pending: Vec::new(),
+        })
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        let response = handle_request(self.handler.as_mut(), message).map_err(Error::Send)?;
+        self.pending.extend(response);
+        Ok(())
+    }
+
+    // Everything the handler will ever send is already there, so an incomplete frame is all
+    // there is and there is nothing to wait for. A handler which returned nothing hangs like a
+    // silent server
+    fn receive(&mut self, framing: &Framing, _timeout: Duration) -> Result<Response, Error> {
+        let buffer = match framing.frame_length(&self.pending) {
+            Some(frame_length) => self.pending.drain(..frame_length).collect(),
+            None => incomplete_response(&mut self.pending).map_err(Error::Receive)?,
+        };
+        Ok(Response::new(buffer))
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.handler = self.factory.create();
+        self.pending.clear();
+        Ok(())
+    }
+}
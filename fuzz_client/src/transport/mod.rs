@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Serialize, Deserialize};
+
+use crate::Response;
+use crate::Error;
+use crate::Framing;
+use crate::ProcessSettings;
+use crate::ProcessExit;
+
+mod tcp;
+mod udp;
+mod unix;
+mod pipe;
+mod in_process;
+mod tls;
+mod timeouts;
+#[cfg(feature = "async")]
+mod asynchronous;
+
+pub use tcp::TcpTransport;
+pub use udp::UdpTransport;
+pub use unix::{UnixStreamTransport, UnixDatagramTransport};
+pub use pipe::PipeTransport;
+pub use in_process::{InProcessTransport, Handler, HandlerFactory};
+pub use tls::{TlsSettings, TlsConnector};
+pub use timeouts::Timeouts;
+#[cfg(feature = "async")]
+pub(crate) use asynchronous::AsyncTransport;
+
+
+// A connection to the target which one MessageSequence is sent through. Every sequence gets a
+// Transport of its own, which is dropped once the sequence is done. Besides the built-in ones
+// a library user can plug in any Transport through TransportProtocol::Custom
+pub trait Transport: Send {
+    // Open a connection to server_address, whatever an address means to the transport
+    fn connect(server_address: &str, options: &TransportOptions) -> Result<Self, Error> where Self: Sized;
+
+    // A failed write is returned to the caller instead of reconnecting, since a target
+    // which stopped accepting data is exactly what the oracle needs to see
+    fn send(&mut self, message: &[u8]) -> Result<(), Error>;
+
+    // Read one response, split from what the target sent by framing, within timeout. A target
+    // which sends nothing at all before then fails with an io::ErrorKind::TimedOut, i.e. a hang
+    fn receive(&mut self, framing: &Framing, timeout: Duration) -> Result<Response, Error>;
+
+    fn shutdown(&mut self) -> Result<(), Error>;
+
+    // Stop sending but keep receiving, so the target reads the end of the stream while its
+    // answer can still arrive. Nothing to do for transports without a sending side of their own
+    fn shutdown_write(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Drop the connection and open a new one to the same target, as connect did
+    fn reset(&mut self) -> Result<(), Error>;
+
+    // Whether the target answers in datagrams, where an empty response only means that it did
+    // not answer the request rather than that it closed the connection
+    fn is_datagram(&self) -> bool {
+        false
+    }
+
+    // Upgrade the connection to TLS in the middle of a session, after the target agreed to it,
+    // e.g. with a 220 reply to SMTP's STARTTLS
+    fn start_tls(&mut self) -> Result<(), Error> {
+        Err(Error::Tls(String::from("only a TCP connection can be upgraded to TLS")))
+    }
+
+    // How the process behind the connection exited, waiting up to grace_period for it to do so.
+    // None for transports whose target is not owned by the connection
+    fn process_exit(&mut self, _grace_period: Duration) -> Option<ProcessExit> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportProtocol {
+    TCP,
+    UDP,
+    // TCP with a TLS session started right after connecting
+    TLS,
+    // Unix domain sockets, where the server address is the path of the socket
+    #[serde(rename = "unix-stream")]
+    UnixStream,
+    #[serde(rename = "unix-datagram")]
+    UnixDatagram,
+    // A process launched for every connection from [target.pipe], which reads requests from
+    // stdin and writes responses to stdout. The server address only names the target in reports
+    Pipe,
+    // A Handler called directly in the fuzzer's process, which only a library user can provide
+    #[serde(rename = "in-process")]
+    InProcess,
+    // A Transport registered under this name in TransportOptions::custom
+    Custom(String),
+}
+
+impl TransportProtocol {
+    // Connect to server_address with the built-in Transport for this protocol, or the one
+    // registered for a custom protocol
+    pub fn connect(&self, server_address: &str, options: &TransportOptions) -> Result<Box<dyn Transport>, Error> {
+        let transport: Box<dyn Transport> = match self {
+            TransportProtocol::TCP => Box::new(TcpTransport::connect(server_address, options)?),
+            TransportProtocol::UDP => Box::new(UdpTransport::connect(server_address, options)?),
+            TransportProtocol::TLS => Box::new(TcpTransport::connect_tls(server_address, options)?),
+            TransportProtocol::UnixStream => Box::new(UnixStreamTransport::connect(server_address, options)?),
+            TransportProtocol::UnixDatagram => Box::new(UnixDatagramTransport::connect(server_address, options)?),
+            TransportProtocol::Pipe => Box::new(PipeTransport::connect(server_address, options)?),
+            TransportProtocol::InProcess => Box::new(InProcessTransport::connect(server_address, options)?),
+            TransportProtocol::Custom(name) => {
+                let factory = options.custom.get(name)
+                    .ok_or_else(|| Error::Parse(format!("no transport is registered as {:?}", name)))?;
+                factory.connect(server_address, options)?
+            }
+        };
+        Ok(transport)
+    }
+}
+
+// The name --transport and campaign files take
+impl fmt::Display for TransportProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TransportProtocol::TCP => "tcp",
+            TransportProtocol::UDP => "udp",
+            TransportProtocol::TLS => "tls",
+            TransportProtocol::UnixStream => "unix-stream",
+            TransportProtocol::UnixDatagram => "unix-datagram",
+            TransportProtocol::Pipe => "pipe",
+            TransportProtocol::InProcess => "in-process",
+            TransportProtocol::Custom(name) => name,
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// How requests and responses are exchanged with a UDP or Unix datagram target
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UdpSettings {
+    // Size of the buffer each response datagram is read into, longer datagrams are truncated
+    pub receive_buffer: usize,
+    // After a datagram arrived, how many seconds to wait for further datagrams answering the same request
+    pub quiet_period: f32,
+    // Whether the target answers every request. If it does, silence is a hang, otherwise an empty reply
+    pub expect_reply: bool,
+}
+
+impl Default for UdpSettings {
+    fn default() -> Self {
+        Self {
+            receive_buffer: 65535,
+            quiet_period: 0.05,
+            expect_reply: false,
+        }
+    }
+}
+
+// What connecting needs besides the address, for the transports which use it
+#[derive(Clone, Default)]
+pub struct TransportOptions {
+    // How datagrams are received by UDP and Unix datagram transports
+    pub udp: UdpSettings,
+    // How TLS transports verify the target, against the bundled web PKI roots if None
+    pub tls: Option<TlsConnector>,
+    // The process a pipe transport launches
+    pub pipe: Option<ProcessSettings>,
+    // What an in-process transport hands the requests to
+    pub handler: Option<HandlerFactory>,
+    // The transports TransportProtocol::Custom connects, by their name
+    pub custom: HashMap<String, TransportFactory>,
+    // How long connecting and receiving may take
+    pub timeouts: Timeouts,
+}
+
+type Connect = dyn Fn(&str, &TransportOptions) -> Result<Box<dyn Transport>, Error> + Send + Sync;
+
+// Connects a Transport the crate does not know about, usually its Transport::connect
+#[derive(Clone)]
+pub struct TransportFactory(Arc<Connect>);
+
+impl TransportFactory {
+    pub fn new<T: Transport + 'static>(connect: impl Fn(&str, &TransportOptions) -> Result<T, Error> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(move |server_address: &str, options: &TransportOptions| {
+            Ok(Box::new(connect(server_address, options)?) as Box<dyn Transport>)
+        }))
+    }
+
+    pub fn connect(&self, server_address: &str, options: &TransportOptions) -> Result<Box<dyn Transport>, Error> {
+        (self.0)(server_address, options)
+    }
+}
+
+// The socket below a stream Transport, which reads honour a timeout on
+trait StreamSocket: Read {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), std::io::Error>;
+}
+
+// The socket below a datagram Transport
+trait DatagramSocket {
+    fn recv(&self, buffer: &mut [u8]) -> Result<usize, std::io::Error>;
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), std::io::Error>;
+}
+
+// Collect the datagrams answering one request, which may be none, one or several. A target
+// which is not expected to answer everything may stay silent, which gives an empty response
+fn read_datagram_response<S: DatagramSocket>(socket: &S, udp_settings: &UdpSettings, timeout: Duration) -> Result<Vec<u8>, std::io::Error> {
+    let mut buffer: Vec<u8> = Vec::new();
+    let mut datagram = vec![0u8; udp_settings.receive_buffer.max(1)];
+
+    socket.set_read_timeout(Some(timeout))?;
+    match socket.recv(&mut datagram) {
+        Ok(n) => buffer.extend_from_slice(&datagram[..n]),
+        Err(e) if (e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut) && !udp_settings.expect_reply => {
+            return Ok(buffer);
+        }
+        Err(e) => return Err(e),
+    }
+
+    let quiet_period = Duration::from_secs_f32(udp_settings.quiet_period);
+    if quiet_period.is_zero() {
+        return Ok(buffer);
+    }
+
+    // A port unreachable after the first datagram is left for the next send or receive to report
+    socket.set_read_timeout(Some(quiet_period))?;
+    while let Ok(n) = socket.recv(&mut datagram) {
+        buffer.extend_from_slice(&datagram[..n]);
+    }
+
+    Ok(buffer)
+}
+
+// Read until framing finds a complete response in pending and split it off. If the target
+// closes the connection or stops sending partway through a response, what arrived so far is
+// the response. Only a target which sends nothing at all before the timeout is an error
+fn read_stream_response<S: StreamSocket + ?Sized>(stream: &mut S, pending: &mut Vec<u8>, framing: &Framing, timeout: Duration) -> Result<Vec<u8>, std::io::Error> {
+    let deadline = Instant::now() + timeout;
+    let mut chunk = [0u8; 4096];
+
+    loop {
+        if let Some(frame_length) = framing.frame_length(pending) {
+            return Ok(pending.drain(..frame_length).collect());
+        }
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let timeout = match framing.quiet_period() {
+            Some(quiet_period) if !pending.is_empty() => quiet_period.min(remaining),
+            _ => remaining,
+        };
+
+        if timeout.is_zero() {
+            return incomplete_response(pending);
+        }
+        stream.set_read_timeout(Some(timeout))?;
+
+        match stream.read(&mut chunk) {
+            Ok(0) => return Ok(std::mem::take(pending)),
+            Ok(n) => pending.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                return incomplete_response(pending);
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn incomplete_response(pending: &mut Vec<u8>) -> Result<Vec<u8>, std::io::Error> {
+    if pending.is_empty() {
+        Err(std::io::Error::new(ErrorKind::TimedOut, "no response before the timeout"))
+    } else {
+        Ok(std::mem::take(pending))
+    }
+}
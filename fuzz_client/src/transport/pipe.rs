@@ -0,0 +1,213 @@
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::Response;
+use crate::Error;
+use crate::Framing;
+use crate::TransportOptions;
+use crate::ProcessSettings;
+use crate::ProcessExit;
+use crate::supervisor::drain_stderr;
+use super::{Transport, StreamSocket, read_stream_response};
+
+
+// A target which reads its requests from stdin and writes its responses to stdout. Every
+// connection is a fresh process, so one MessageSequence cannot leave state behind for the next.
+pub struct PipeTransport {
+    pipe: Pipe,
+    // Bytes which arrived after the end of the last response, i.e. the start of the next one
+    pending: Vec<u8>,
+    settings: ProcessSettings,
+}
+
+impl PipeTransport {
+    fn open(settings: &ProcessSettings) -> Result<Self, Error> {
+        let pipe = Pipe::spawn(settings).map_err(|e| Error::Connect(settings.program.clone(), e))?;
+
+        Ok(Self {
+            pipe,
+            pending: Vec::new(),
+            settings: settings.clone(),
+        })
+    }
+}
+
+// The server address only names the target in reports, the process comes from options.pipe
+impl Transport for PipeTransport {
+    fn connect(_server_address: &str, options: &TransportOptions) -> Result<Self, Error> {
+        let settings = options.pipe.as_ref()
+            .ok_or_else(|| Error::Parse(String::from("the pipe transport needs the process to launch")))?;
+        Self::open(settings)
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.pipe.write(message).map_err(Error::Send)
+    }
+
+    fn receive(&mut self, framing: &Framing, timeout: Duration) -> Result<Response, Error> {
+        read_stream_response(&mut self.pipe.stdout, &mut self.pending, framing, timeout).map(Response::new).map_err(Error::Receive)
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        self.pipe.close();
+        Ok(())
+    }
+
+    // Closing stdin is all shutdown does, since the process's stdout stays readable until it exits
+    fn shutdown_write(&mut self) -> Result<(), Error> {
+        self.pipe.close();
+        Ok(())
+    }
+
+    // The old process is killed when it is dropped
+    fn reset(&mut self) -> Result<(), Error> {
+        *self = Self::open(&self.settings)?;
+        Ok(())
+    }
+
+    fn process_exit(&mut self, grace_period: Duration) -> Option<ProcessExit> {
+        self.pipe.exit(grace_period)
+    }
+}
+
+// The process behind a PipeTransport
+struct Pipe {
+    child: Child,
+    // Taken when the connection is shut down, which closes the target's stdin
+    stdin: Option<ChildStdin>,
+    stdout: PipeReader,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    stderr_reader: Option<JoinHandle<()>>,
+    stack_depth: usize,
+}
+
+impl Pipe {
+    fn spawn(settings: &ProcessSettings) -> Result<Self, io::Error> {
+        let mut command = Command::new(&settings.program);
+        command
+            .args(&settings.args)
+            .envs(&settings.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(working_directory) = &settings.working_directory {
+            command.current_dir(working_directory);
+        }
+
+        let mut child = command.spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = PipeReader::new(child.stdout.take().unwrap());
+        let (stderr, stderr_reader) = drain_stderr(child.stderr.take().unwrap());
+
+        Ok(Self {
+            child,
+            stdin,
+            stdout,
+            stderr,
+            stderr_reader: Some(stderr_reader),
+            stack_depth: settings.stack_depth,
+        })
+    }
+
+    // A target which exited or closed stdin fails the write with BrokenPipe
+    fn write(&mut self, data: &[u8]) -> Result<(), io::Error> {
+        let stdin = self.stdin.as_mut().ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "stdin is closed"))?;
+        stdin.write_all(data)?;
+        stdin.flush()
+    }
+
+    // Close the target's stdin, which tells a well-behaved target to finish
+    fn close(&mut self) {
+        self.stdin = None;
+    }
+
+    // How the target exited, waiting up to grace_period for it to do so
+    fn exit(&mut self, grace_period: Duration) -> Option<ProcessExit> {
+        let deadline = Instant::now() + grace_period;
+        let status = loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                _ => return None,
+            }
+        };
+
+        if let Some(stderr_reader) = self.stderr_reader.take() {
+            let _ = stderr_reader.join();
+        }
+        let stderr = String::from_utf8_lossy(&self.stderr.lock().unwrap()).into_owned();
+
+        Some(ProcessExit::new(status, stderr, self.stack_depth))
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// The target's stdout, read by a thread so that reads can time out like a socket's. A read
+// which times out fails with WouldBlock, and one after the target closed stdout returns 0
+struct PipeReader {
+    chunks: Receiver<Vec<u8>>,
+    // The part of the last chunk which did not fit the caller's buffer
+    leftover: Vec<u8>,
+    timeout: Option<Duration>,
+}
+
+impl PipeReader {
+    fn new<R: Read + Send + 'static>(mut stdout: R) -> Self {
+        let (sender, chunks) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            while let Ok(n @ 1..) = stdout.read(&mut buffer) {
+                if sender.send(buffer[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            chunks,
+            leftover: Vec::new(),
+            timeout: None,
+        }
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.leftover.is_empty() {
+            let chunk = match self.timeout {
+                Some(timeout) => self.chunks.recv_timeout(timeout),
+                None => self.chunks.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match chunk {
+                Ok(chunk) => self.leftover = chunk,
+                Err(RecvTimeoutError::Timeout) => return Err(io::Error::new(io::ErrorKind::WouldBlock, "no output before the timeout")),
+                Err(RecvTimeoutError::Disconnected) => return Ok(0),
+            }
+        }
+
+        let n = buffer.len().min(self.leftover.len());
+        buffer[..n].copy_from_slice(&self.leftover[..n]);
+        self.leftover.drain(..n);
+        Ok(n)
+    }
+}
+
+impl StreamSocket for PipeReader {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+        self.timeout = timeout;
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{self, ErrorKind, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::Response;
+use crate::Error;
+use crate::Framing;
+use crate::TransportOptions;
+use super::{Transport, StreamSocket, read_stream_response};
+use super::tls::{TlsConnector, TlsSettings, TlsStream};
+
+
+// A TCP connection, which is upgraded to TLS by starting a session on it
+enum Connection {
+    Plain(TcpStream),
+    Tls(Box<TlsStream>),
+}
+
+pub struct TcpTransport {
+    connection: Connection,
+    // Bytes which arrived after the end of the last response, i.e. the start of the next one
+    pending: Vec<u8>,
+    server_address: String,
+    // How TLS sessions verify the target, against the bundled web PKI roots if None
+    tls: Option<TlsConnector>,
+    // Whether the TLS session starts right after connecting, as for TransportProtocol::TLS
+    implicit_tls: bool,
+    // How long connecting and the TLS handshake may take
+    connect_timeout: Duration,
+}
+
+impl TcpTransport {
+    // Connect and start a TLS session right away
+    pub fn connect_tls(server_address: &str, options: &TransportOptions) -> Result<Self, Error> {
+        Self::open(server_address, options.tls.clone(), true, options.timeouts.connect_timeout())
+    }
+
+    fn open(server_address: &str, tls: Option<TlsConnector>, implicit_tls: bool, connect_timeout: Duration) -> Result<Self, Error> {
+        let connect_error = |e| Error::Connect(server_address.to_string(), e);
+        let tcp_stream = connect_tcp(server_address, connect_timeout).map_err(connect_error)?;
+
+        let connection = if implicit_tls {
+            let tls_stream = tls_connector(tls.as_ref())?.connect(tcp_stream, server_address, connect_timeout).map_err(connect_error)?;
+            Connection::Tls(Box::new(tls_stream))
+        } else {
+            Connection::Plain(tcp_stream)
+        };
+
+        Ok(Self {
+            connection,
+            pending: Vec::new(),
+            server_address: server_address.to_string(),
+            tls,
+            implicit_tls,
+            connect_timeout,
+        })
+    }
+}
+
+// Connect to the first address server_address resolves to which accepts within timeout.
+// Every write goes out in a segment of its own instead of waiting to be coalesced, so a
+// fragmented Delivery reaches the target in the pieces it was cut into
+fn connect_tcp(server_address: &str, timeout: Duration) -> Result<TcpStream, io::Error> {
+    let mut last_error = io::Error::new(ErrorKind::InvalidInput, "address resolved to nothing");
+
+    for socket_address in server_address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_address, timeout) {
+            Ok(tcp_stream) => {
+                tcp_stream.set_nodelay(true)?;
+                return Ok(tcp_stream);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+
+    Err(last_error)
+}
+
+fn tls_connector(tls: Option<&TlsConnector>) -> Result<TlsConnector, Error> {
+    match tls {
+        Some(tls) => Ok(tls.clone()),
+        None => TlsConnector::new(&TlsSettings::default()),
+    }
+}
+
+impl Transport for TcpTransport {
+    fn connect(server_address: &str, options: &TransportOptions) -> Result<Self, Error> {
+        Self::open(server_address, options.tls.clone(), false, options.timeouts.connect_timeout())
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        let result = match &mut self.connection {
+            Connection::Plain(tcp_stream) => tcp_stream.write_all(message),
+            Connection::Tls(tls_stream) => tls_stream.write_all(message).and_then(|_| tls_stream.flush()),
+        };
+        result.map_err(Error::Send)
+    }
+
+    fn receive(&mut self, framing: &Framing, timeout: Duration) -> Result<Response, Error> {
+        let buffer = match &mut self.connection {
+            Connection::Plain(tcp_stream) => read_stream_response(tcp_stream, &mut self.pending, framing, timeout),
+            Connection::Tls(tls_stream) => read_stream_response(tls_stream.as_mut(), &mut self.pending, framing, timeout),
+        };
+        buffer.map(Response::new).map_err(Error::Receive)
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        match &mut self.connection {
+            Connection::Plain(tcp_stream) => tcp_stream.shutdown(Shutdown::Both).map_err(Error::Send),
+            Connection::Tls(tls_stream) => {
+                tls_stream.conn.send_close_notify();
+                let _ = tls_stream.flush();
+                tls_stream.sock.shutdown(Shutdown::Both).map_err(Error::Send)
+            }
+        }
+    }
+
+    // A TLS session is closed with close_notify before the socket stops sending
+    fn shutdown_write(&mut self) -> Result<(), Error> {
+        match &mut self.connection {
+            Connection::Plain(tcp_stream) => tcp_stream.shutdown(Shutdown::Write).map_err(Error::Send),
+            Connection::Tls(tls_stream) => {
+                tls_stream.conn.send_close_notify();
+                let _ = tls_stream.flush();
+                tls_stream.sock.shutdown(Shutdown::Write).map_err(Error::Send)
+            }
+        }
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        *self = Self::open(&self.server_address, self.tls.clone(), self.implicit_tls, self.connect_timeout)?;
+        Ok(())
+    }
+
+    // Anything the target sent after agreeing to TLS and before the handshake is discarded.
+    // A connection which already is TLS is left alone
+    fn start_tls(&mut self) -> Result<(), Error> {
+        let tcp_stream = match &self.connection {
+            Connection::Plain(tcp_stream) => tcp_stream.try_clone().map_err(Error::Send)?,
+            Connection::Tls(_) => return Ok(()),
+        };
+
+        let tls_stream = tls_connector(self.tls.as_ref())?.connect(tcp_stream, &self.server_address, self.connect_timeout).map_err(Error::Send)?;
+        self.connection = Connection::Tls(Box::new(tls_stream));
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl StreamSocket for TcpStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl StreamSocket for TlsStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{Serialize, Deserialize};
+
+use crate::Protocol;
+use crate::Message;
+
+
+// How long the fuzzer waits for the target, set in [target.timeouts]. All values are seconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeouts {
+    // To open a connection, including the TLS handshake of a TLS transport
+    pub connect: f32,
+    // For the complete response to a message. A target which sends nothing before it passes hangs
+    pub read: f32,
+    // For a whole MessageSequence, including the delays between its messages. Unlimited if None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<f32>,
+    // Read timeouts for single message types, by the name the protocol gives the type, e.g.
+    // EMAIL_CONTENT. These take precedence over the ones the protocol sets itself
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub messages: HashMap<String, f32>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: 5.0,
+            read: 5.0,
+            sequence: None,
+            messages: HashMap::new(),
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs_f32(self.connect)
+    }
+
+    pub fn sequence_timeout(&self) -> Option<Duration> {
+        self.sequence.map(Duration::from_secs_f32)
+    }
+
+    // The read timeout for the response to message
+    pub fn read_timeout<P: Protocol>(&self, protocol: &P, message: &Message<P>) -> Duration {
+        match self.messages.get(&format!("{:?}", message.message_type)) {
+            Some(&read) => Duration::from_secs_f32(read),
+            None => protocol.read_timeout(message, Duration::from_secs_f32(self.read)),
+        }
+    }
+}
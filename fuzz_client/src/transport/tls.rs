@@ -0,0 +1,132 @@
+use std::fs;
+use std::io;
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::pki_types::pem::PemObject;
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme, StreamOwned};
+use serde::{Serialize, Deserialize};
+
+use crate::Error;
+
+
+// How the certificate of a TLS target is verified, set in [target.tls]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsSettings {
+    // Accept whatever certificate the target presents, e.g. the self-signed one of a test server
+    pub accept_any_certificate: bool,
+    // PEM file of the certificates to trust instead of the bundled web PKI roots
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<PathBuf>,
+    // The name sent in SNI and checked against the certificate. Defaults to the host of the target address
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+}
+
+pub type TlsStream = StreamOwned<ClientConnection, TcpStream>;
+
+// A rustls client configuration built once from TlsSettings and shared by every connection
+#[derive(Clone)]
+pub struct TlsConnector {
+    config: Arc<ClientConfig>,
+    server_name: Option<String>,
+}
+
+impl TlsConnector {
+    pub fn new(settings: &TlsSettings) -> Result<Self, Error> {
+        let provider = Arc::new(crypto::ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(|e| Error::Tls(e.to_string()))?;
+
+        let config = if settings.accept_any_certificate {
+            builder.dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider.signature_verification_algorithms)))
+                .with_no_client_auth()
+        } else {
+            builder.with_root_certificates(Self::root_certificates(settings)?).with_no_client_auth()
+        };
+
+        Ok(Self {
+            config: Arc::new(config),
+            server_name: settings.server_name.clone(),
+        })
+    }
+
+    fn root_certificates(settings: &TlsSettings) -> Result<RootCertStore, Error> {
+        let mut roots = RootCertStore::empty();
+
+        match &settings.ca_file {
+            Some(ca_file) => {
+                let pem = fs::read(ca_file).map_err(|e| Error::Io(ca_file.clone(), e))?;
+                for certificate in CertificateDer::pem_slice_iter(&pem) {
+                    let certificate = certificate.map_err(|e| Error::Tls(format!("{}: {}", ca_file.display(), e)))?;
+                    roots.add(certificate).map_err(|e| Error::Tls(format!("{}: {}", ca_file.display(), e)))?;
+                }
+                if roots.is_empty() {
+                    return Err(Error::Tls(format!("{}: no certificates found", ca_file.display())));
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+
+        Ok(roots)
+    }
+
+    // Run the TLS handshake over an established TCP connection to server_address. A handshake
+    // which fails, e.g. because the certificate is rejected, is returned as an InvalidData error
+    pub fn connect(&self, tcp_stream: TcpStream, server_address: &str, timeout: Duration) -> Result<TlsStream, io::Error> {
+        let server_name = self.server_name(server_address)?;
+        let connection = ClientConnection::new(self.config.clone(), server_name)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let mut tls_stream = StreamOwned::new(connection, tcp_stream);
+        tls_stream.sock.set_read_timeout(Some(timeout))?;
+        while tls_stream.conn.is_handshaking() {
+            tls_stream.conn.complete_io(&mut tls_stream.sock)?;
+        }
+
+        Ok(tls_stream)
+    }
+
+    // The configured server name, or else the host part of "host:port" or "[v6]:port"
+    fn server_name(&self, server_address: &str) -> Result<ServerName<'static>, io::Error> {
+        let host = match &self.server_name {
+            Some(server_name) => server_name.as_str(),
+            None => server_address.rsplit_once(':').map_or(server_address, |(host, _)| host),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+
+        ServerName::try_from(host.to_string())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("server name {}: {}", host, e)))
+    }
+}
+
+// Trusts every certificate, but still checks that the handshake is signed by its key
+#[derive(Debug)]
+struct AcceptAnyCertificate(WebPkiSupportedAlgorithms);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(&self, _end_entity: &CertificateDer, _intermediates: &[CertificateDer], _server_name: &ServerName,
+                          _ocsp_response: &[u8], _now: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], certificate: &CertificateDer, signature: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, certificate, signature, &self.0)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], certificate: &CertificateDer, signature: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, certificate, signature, &self.0)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.supported_schemes()
+    }
+}
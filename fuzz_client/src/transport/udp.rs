@@ -0,0 +1,80 @@
+use std::io::ErrorKind;
+use std::net::{UdpSocket, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::Response;
+use crate::Error;
+use crate::Framing;
+use crate::{TransportOptions, UdpSettings};
+use super::{Transport, DatagramSocket, read_datagram_response};
+
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    settings: UdpSettings,
+    server_address: String,
+}
+
+impl UdpTransport {
+    fn open(server_address: &str, settings: UdpSettings) -> Result<Self, Error> {
+        let socket = bind_udp(server_address).map_err(|e| Error::Connect(server_address.to_string(), e))?;
+
+        Ok(Self {
+            socket,
+            settings,
+            server_address: server_address.to_string(),
+        })
+    }
+}
+
+// Bind an ephemeral local port of the target's address family and connect it to the target.
+// Connecting makes the kernel report an ICMP port unreachable as ConnectionRefused
+fn bind_udp(server_address: &str) -> Result<UdpSocket, std::io::Error> {
+    let remote_address = server_address.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidInput, "address resolved to nothing"))?;
+
+    let local_address = if remote_address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let udp_socket = UdpSocket::bind(local_address)?;
+    udp_socket.connect(remote_address)?;
+    Ok(udp_socket)
+}
+
+impl Transport for UdpTransport {
+    fn connect(server_address: &str, options: &TransportOptions) -> Result<Self, Error> {
+        Self::open(server_address, options.udp.clone())
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.socket.send(message).map(|_| ()).map_err(Error::Send)
+    }
+
+    // Framing does not apply, every datagram arriving before the target goes quiet is part of the response
+    fn receive(&mut self, _framing: &Framing, timeout: Duration) -> Result<Response, Error> {
+        read_datagram_response(&self.socket, &self.settings, timeout).map(Response::new).map_err(Error::Receive)
+    }
+
+    // No specific shutdown needed for UDP
+    fn shutdown(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        *self = Self::open(&self.server_address, self.settings.clone())?;
+        Ok(())
+    }
+
+    fn is_datagram(&self) -> bool {
+        true
+    }
+}
+
+impl DatagramSocket for UdpSocket {
+    fn recv(&self, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+        UdpSocket::recv(self, buffer)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        UdpSocket::set_read_timeout(self, timeout)
+    }
+}
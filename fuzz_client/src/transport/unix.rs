@@ -0,0 +1,147 @@
+use std::fs;
+use std::io::Write;
+use std::net::Shutdown;
+use std::os::unix::net::{UnixDatagram, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::Response;
+use crate::Error;
+use crate::Framing;
+use crate::{TransportOptions, UdpSettings};
+use super::{Transport, StreamSocket, DatagramSocket, read_stream_response, read_datagram_response};
+
+
+// Numbers the local sockets Unix datagram transports bind, which are unique per process
+static UNIX_DATAGRAM_SOCKETS: AtomicUsize = AtomicUsize::new(0);
+
+// A Unix stream socket, where the server address is the path of the socket
+pub struct UnixStreamTransport {
+    stream: UnixStream,
+    // Bytes which arrived after the end of the last response, i.e. the start of the next one
+    pending: Vec<u8>,
+    server_address: String,
+}
+
+impl Transport for UnixStreamTransport {
+    fn connect(server_address: &str, _options: &TransportOptions) -> Result<Self, Error> {
+        let stream = UnixStream::connect(server_address).map_err(|e| Error::Connect(server_address.to_string(), e))?;
+
+        Ok(Self {
+            stream,
+            pending: Vec::new(),
+            server_address: server_address.to_string(),
+        })
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.stream.write_all(message).map_err(Error::Send)
+    }
+
+    fn receive(&mut self, framing: &Framing, timeout: Duration) -> Result<Response, Error> {
+        read_stream_response(&mut self.stream, &mut self.pending, framing, timeout).map(Response::new).map_err(Error::Receive)
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        self.stream.shutdown(Shutdown::Both).map_err(Error::Send)
+    }
+
+    fn shutdown_write(&mut self) -> Result<(), Error> {
+        self.stream.shutdown(Shutdown::Write).map_err(Error::Send)
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        *self = Self::connect(&self.server_address, &TransportOptions::default())?;
+        Ok(())
+    }
+}
+
+impl StreamSocket for UnixStream {
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+// A Unix datagram socket, received from like UDP
+pub struct UnixDatagramTransport {
+    socket: LocalUnixDatagram,
+    settings: UdpSettings,
+    server_address: String,
+}
+
+impl UnixDatagramTransport {
+    fn open(server_address: &str, settings: UdpSettings) -> Result<Self, Error> {
+        let socket = LocalUnixDatagram::connect(server_address).map_err(|e| Error::Connect(server_address.to_string(), e))?;
+
+        Ok(Self {
+            socket,
+            settings,
+            server_address: server_address.to_string(),
+        })
+    }
+}
+
+impl Transport for UnixDatagramTransport {
+    fn connect(server_address: &str, options: &TransportOptions) -> Result<Self, Error> {
+        Self::open(server_address, options.udp.clone())
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.socket.socket.send(message).map(|_| ()).map_err(Error::Send)
+    }
+
+    // Framing does not apply, every datagram arriving before the target goes quiet is part of the response
+    fn receive(&mut self, _framing: &Framing, timeout: Duration) -> Result<Response, Error> {
+        read_datagram_response(&self.socket, &self.settings, timeout).map(Response::new).map_err(Error::Receive)
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        *self = Self::open(&self.server_address, self.settings.clone())?;
+        Ok(())
+    }
+
+    fn is_datagram(&self) -> bool {
+        true
+    }
+}
+
+// A Unix datagram socket bound to a path of its own, which the target sends its replies to.
+// The path is removed again when the socket is dropped
+struct LocalUnixDatagram {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl LocalUnixDatagram {
+    fn connect(server_address: &str) -> Result<Self, std::io::Error> {
+        let number = UNIX_DATAGRAM_SOCKETS.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("fuzz_client-{}-{}.sock", std::process::id(), number));
+        let _ = fs::remove_file(&path);
+
+        let socket = UnixDatagram::bind(&path)?;
+        let local = Self { socket, path };
+        local.socket.connect(server_address)?;
+        Ok(local)
+    }
+}
+
+impl Drop for LocalUnixDatagram {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl DatagramSocket for LocalUnixDatagram {
+    fn recv(&self, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+        self.socket.recv(buffer)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), std::io::Error> {
+        self.socket.set_read_timeout(timeout)
+    }
+}
@@ -0,0 +1,96 @@
+// The async engine against a local stand-in target which echoes every request, run with
+// cargo test --features async
+#![cfg(feature = "async")]
+
+mod common;
+
+use std::net::{TcpListener, UdpSocket};
+use std::time::{Duration, Instant};
+
+use fuzz_client::{Client, Control, Engine, GreetingProtocol, Message, MessageSequence, Timeouts, TransportProtocol, Verdict};
+
+use common::{spawn_stand_in, Reply};
+
+
+fn spawn_echo() -> String {
+    spawn_stand_in(None, |request: &[u8]| Reply::Answer(request.to_vec())).address
+}
+
+// A request in the greeting wire format, numbered so that its echo tells the sequences apart
+fn request(number: usize) -> Message<GreetingProtocol> {
+    let payload = format!("Hello number {:03}!\n", number);
+    let mut bytes = b"HELO".to_vec();
+    bytes.extend((payload.len() as u64).to_be_bytes());
+    bytes.extend(payload.as_bytes());
+    Message::from_bytes(GreetingProtocol, &bytes)
+}
+
+fn async_client(address: String, transport_protocol: TransportProtocol, workers: usize) -> Client<GreetingProtocol> {
+    let mut client = Client::new(address, transport_protocol, GreetingProtocol);
+    client.engine = Engine::Async;
+    client.workers = workers;
+    client
+}
+
+#[test]
+fn runs_sequences_concurrently_and_waits_out_their_timings() {
+    let mut client = async_client(spawn_echo(), TransportProtocol::TCP, 100);
+    client.corpus = (0..100).map(|number| MessageSequence::from_messages(vec![request(number); 2], vec![0.5])).collect();
+
+    let start = Instant::now();
+    let traces = client.run_corpus();
+    let elapsed = start.elapsed();
+
+    assert!(elapsed >= Duration::from_millis(500));
+    assert!(elapsed < Duration::from_secs(5), "took {:?}", elapsed);
+    assert_eq!(traces.len(), 100);
+
+    // Every trace belongs to the sequence at the same position in the corpus
+    for (number, trace) in traces.iter().enumerate() {
+        assert_eq!(trace.outcome.verdict, Verdict::Reply);
+        assert_eq!(trace.interactions.len(), 2);
+        assert_eq!(trace.interactions[1].1.data, request(number).data);
+    }
+}
+
+#[test]
+fn a_silent_target_hangs_without_holding_up_the_others() {
+    let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut client = async_client(silent.local_addr().unwrap().to_string(), TransportProtocol::UDP, 10);
+    client.transport_options.udp.expect_reply = true;
+    client.transport_options.timeouts = Timeouts { read: 0.5, ..Timeouts::default() };
+    client.corpus = (0..10).map(|number| MessageSequence::from_messages(vec![request(number)], Vec::new())).collect();
+
+    let start = Instant::now();
+    let traces = client.run_corpus();
+
+    assert!(start.elapsed() < Duration::from_secs(2), "took {:?}", start.elapsed());
+    assert!(traces.iter().all(|trace| trace.outcome.verdict == Verdict::Hang));
+}
+
+#[test]
+fn a_target_which_is_not_listening_is_refused() {
+    let unused = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+    let mut client = async_client(unused, TransportProtocol::TCP, 4);
+    client.corpus = (0..4).map(|number| MessageSequence::from_messages(vec![request(number)], Vec::new())).collect();
+
+    let traces = client.run_corpus();
+
+    assert!(traces.iter().all(|trace| trace.outcome.verdict == Verdict::Refused));
+}
+
+#[test]
+fn control_steps_run_between_the_messages() {
+    let mut client = async_client(spawn_echo(), TransportProtocol::TCP, 2);
+    let mut sequence = MessageSequence::from_messages(vec![request(0), request(1)], vec![0.0]);
+    sequence.insert_control(1, Control::HalfClose);
+    client.corpus = vec![sequence];
+
+    let trace = client.run_corpus().remove(0);
+
+    // The half-closed connection cannot carry the second request, so a new one does
+    assert_eq!(trace.outcome.verdict, Verdict::Reply);
+    assert_eq!(trace.interactions[1].1.data, request(1).data);
+    let controls: Vec<&Control> = trace.controls.iter().map(|(step, _)| &step.control).collect();
+    assert_eq!(controls, [&Control::HalfClose, &Control::Reconnect]);
+}
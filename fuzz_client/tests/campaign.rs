@@ -0,0 +1,170 @@
+// Loading, saving and validating campaign files
+use std::path::PathBuf;
+
+use fuzz_client::{Campaign, ConfigError, Engine, Framing, ProcessSettings, TransportProtocol};
+
+
+fn path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("fuzz_client_campaign_{}_{}", std::process::id(), name))
+}
+
+// A change which makes a valid campaign invalid
+type Change = Box<dyn Fn(&mut Campaign)>;
+
+fn campaign() -> Campaign {
+    Campaign::new(String::from("127.0.0.1:8025"), TransportProtocol::TCP, String::from("smtp"))
+}
+
+// Write contents to a file named name and load it as a campaign
+fn load(name: &str, contents: &str) -> Result<Campaign, ConfigError> {
+    let path = path(name);
+    std::fs::write(&path, contents).unwrap();
+    let campaign = Campaign::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    campaign
+}
+
+#[test]
+fn the_smtp_campaign_loads() {
+    let campaign = Campaign::load(concat!(env!("CARGO_MANIFEST_DIR"), "/campaigns/smtp.toml")).unwrap();
+
+    assert_eq!(campaign.target.address, "127.0.0.1:8025");
+    assert_eq!(campaign.target.transport, TransportProtocol::TCP);
+    assert_eq!(campaign.target.protocol, "smtp");
+    assert_eq!(campaign.corpus.pcap_files, vec![String::from("../resources/new_smtp.pcap")]);
+    assert_eq!(campaign.fuzz, Campaign::default_fuzz_config());
+    assert_eq!(campaign.optimizer.pso.swarm_size, 10);
+    assert_eq!(campaign.optimizer.bayesian.iterations, 20);
+}
+
+#[test]
+fn a_saved_campaign_loads_as_it_was() {
+    let mut saved = campaign();
+    saved.target.additional_addresses = vec![String::from("127.0.0.1:8026")];
+    saved.target.framing = Some(Framing::Delimiter { delimiter: String::from("\r\n") });
+    saved.target.timeouts.messages.insert(String::from("EMAIL_CONTENT"), 10.0);
+    saved.fuzz.generations = 7;
+    saved.fuzz.message_pool_size = 12;
+    saved.execution.workers = 4;
+    saved.execution.seed = Some(42);
+
+    for extension in ["toml", "json"] {
+        let path = path(&format!("round_trip.{}", extension));
+        saved.save(&path).unwrap();
+        let loaded = Campaign::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), saved);
+    }
+}
+
+#[test]
+fn a_partial_fuzz_table_keeps_the_other_defaults() {
+    let campaign = load("partial.toml", "[target]\naddress = \"127.0.0.1:8025\"\nprotocol = \"smtp\"\n\n[fuzz]\ngenerations = 3\nmessage_pool_size = 10\n").unwrap();
+
+    let expected = Campaign::default_fuzz_config();
+    assert_eq!(campaign.fuzz.generations, 3);
+    assert_eq!(campaign.fuzz.message_pool_size, 10);
+    assert_eq!(campaign.fuzz.selection_pressure, expected.selection_pressure);
+    assert_eq!(campaign.fuzz.state_rarity_weight, expected.state_rarity_weight);
+
+    // Without a [fuzz] table at all every hyperparameter is the default
+    let campaign = load("no_fuzz.json", r#"{ "target": { "address": "127.0.0.1:8025", "protocol": "smtp" } }"#).unwrap();
+    assert_eq!(campaign.fuzz, expected);
+}
+
+#[test]
+fn files_which_are_not_campaigns_are_reported_with_their_path() {
+    let error = Campaign::load(path("campaign.yaml")).unwrap_err();
+    assert!(error.to_string().ends_with("campaign.yaml: campaign files must end in .toml or .json"), "{}", error);
+
+    let error = load("broken.toml", "[target\n").unwrap_err();
+    assert!(matches!(error, ConfigError::Parse(..)), "{}", error);
+
+    let error = load("invalid.toml", "[target]\naddress = \"nowhere\"\nprotocol = \"smtp\"\n").unwrap_err();
+    assert_eq!(error.to_string(), "invalid campaign: target.address must be host:port, got \"nowhere\"");
+}
+
+#[test]
+fn every_invalid_field_is_named() {
+    let process = || ProcessSettings::new(String::from("./target"));
+    let cases: Vec<(Change, &str)> = vec![
+        (Box::new(|c| c.target.address = String::from("localhost")), "target.address must be host:port, got \"localhost\""),
+        (Box::new(|c| c.target.additional_addresses = vec![String::from(":8025")]), "target.additional_addresses must be host:port, got \":8025\""),
+        (Box::new(|c| {
+            c.target.transport = TransportProtocol::UnixStream;
+            c.target.address = String::new();
+        }), "target.address must be the path of a socket, got \"\""),
+        (Box::new(|c| c.target.protocol = String::from(" ")), "target.protocol must not be empty"),
+        (Box::new(move |c| c.target.process = Some(ProcessSettings::new(String::new()))), "target.process.program must not be empty"),
+        (Box::new(move |c| c.target.process = Some(ProcessSettings { startup_timeout: 0.0, ..process() })), "target.process.startup_timeout must be positive, got 0"),
+        (Box::new(move |c| c.target.process = Some(ProcessSettings { stack_depth: 0, ..process() })), "target.process.stack_depth must be at least 1"),
+        (Box::new(move |c| {
+            c.target.process = Some(process());
+            c.execution.workers = 2;
+        }), "execution.workers must be 1 when the target is launched by target.process"),
+        (Box::new(|c| {
+            c.target.transport = TransportProtocol::Pipe;
+            c.target.address = String::from("target");
+        }), "target.pipe must name the program to launch when target.transport is pipe"),
+        (Box::new(|c| {
+            c.target.transport = TransportProtocol::Pipe;
+            c.target.address = String::from("target");
+            c.target.pipe = Some(ProcessSettings::new(String::new()));
+        }), "target.pipe.program must not be empty"),
+        (Box::new(move |c| {
+            c.target.transport = TransportProtocol::Pipe;
+            c.target.address = String::from("target");
+            c.target.pipe = Some(ProcessSettings { stack_depth: 0, ..process() });
+        }), "target.pipe.stack_depth must be at least 1"),
+        (Box::new(move |c| {
+            c.target.transport = TransportProtocol::Pipe;
+            c.target.address = String::from("target");
+            c.target.pipe = Some(process());
+            c.target.process = Some(process());
+        }), "target.process cannot be used with the pipe transport, which launches target.pipe itself"),
+        (Box::new(move |c| c.target.pipe = Some(process())), "target.pipe is only used when target.transport is pipe"),
+        (Box::new(|c| c.target.framing = Some(Framing::FixedSize { size: 0 })), "target.framing: size must be positive"),
+        (Box::new(|c| c.target.udp.receive_buffer = 0), "target.udp.receive_buffer must be at least 1"),
+        (Box::new(|c| c.target.udp.quiet_period = -1.0), "target.udp.quiet_period must not be negative, got -1"),
+        (Box::new(|c| c.target.timeouts.connect = 0.0), "target.timeouts.connect must be positive, got 0"),
+        (Box::new(|c| c.target.timeouts.read = -1.0), "target.timeouts.read must be positive, got -1"),
+        (Box::new(|c| c.target.timeouts.sequence = Some(0.0)), "target.timeouts.sequence must be positive, got 0"),
+        (Box::new(|c| {
+            c.target.timeouts.messages.insert(String::from("EMAIL_CONTENT"), 0.0);
+        }), "target.timeouts.messages.EMAIL_CONTENT must be positive, got 0"),
+        (Box::new(|c| c.corpus.pcap_files = vec![String::from("missing.pcap")]), "corpus.pcap_files: missing.pcap does not exist"),
+        (Box::new(|c| c.fuzz.generations = 0), "fuzz.generations must be at least 1"),
+        (Box::new(|c| c.fuzz.message_pool_size = 0), "fuzz.message_pool_size must be at least 1"),
+        (Box::new(|c| c.fuzz.selection_pressure = 1.5), "fuzz.selection_pressure must be between 0 and 1, got 1.5"),
+        (Box::new(|c| c.fuzz.state_rarity_weight = -0.5), "fuzz.state_rarity_weight must be between 0 and 1, got -0.5"),
+        (Box::new(|c| c.optimizer.pso.swarm_size = 0), "optimizer.pso.swarm_size must be at least 1"),
+        (Box::new(|c| c.optimizer.pso.message_pool_size = 0), "optimizer.pso.message_pool_size must be at least 1"),
+        (Box::new(|c| c.optimizer.bayesian.fuzzer_generations = 0), "optimizer.bayesian.fuzzer_generations must be at least 1"),
+        (Box::new(|c| c.execution.workers = 0), "execution.workers must be at least 1"),
+        (Box::new(|c| c.execution.sequences_per_connection = 0), "execution.sequences_per_connection must be at least 1"),
+        (Box::new(|c| c.optimizer.pso.vmax = 0.0), "optimizer.pso.vmax must be in (0, 1], got 0"),
+        (Box::new(|c| c.optimizer.pso.social_weight = -1.0), "optimizer.pso.social_weight must not be negative, got -1"),
+        (Box::new(|c| c.execution.max_delivery_delay = -0.1), "execution.max_delivery_delay must not be negative, got -0.1"),
+    ];
+
+    assert!(campaign().validate().is_ok());
+    for (change, expected) in cases {
+        let mut campaign = campaign();
+        change(&mut campaign);
+        assert_eq!(campaign.validate().unwrap_err().to_string(), format!("invalid campaign: {}", expected));
+    }
+}
+
+#[test]
+fn the_async_engine_is_checked_against_the_build_and_the_transport() {
+    let mut campaign = campaign();
+    campaign.execution.engine = Engine::Async;
+    if cfg!(feature = "async") {
+        assert!(campaign.validate().is_ok());
+        campaign.target.transport = TransportProtocol::Pipe;
+        campaign.target.pipe = Some(ProcessSettings::new(String::from("./target")));
+        assert_eq!(campaign.validate().unwrap_err().to_string(), "invalid campaign: execution.engine async cannot connect over target.transport pipe");
+    } else {
+        assert_eq!(campaign.validate().unwrap_err().to_string(), "invalid campaign: execution.engine async needs fuzz_client built with the async feature");
+    }
+}
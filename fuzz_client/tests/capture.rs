@@ -0,0 +1,246 @@
+// Reassembly of captured TCP connections, fed with packets built here instead of read from a
+// capture file, over the link types a capture can come in
+use std::net::{IpAddr, SocketAddr};
+
+use fuzz_client::{state_transitions, CapturedConnection, Client, Direction, Protocol, Reassembler, Response, SMTP, TransportProtocol, Turn};
+use pcap::Linktype;
+
+
+const SERVER: &str = "10.0.0.1:25";
+const SYN: u8 = 0x02;
+const ACK: u8 = 0x10;
+
+fn address(text: &str) -> SocketAddr {
+    text.parse().unwrap()
+}
+
+// A TCP segment with a 20 byte header
+fn tcp(source: SocketAddr, destination: SocketAddr, sequence: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let mut segment = Vec::new();
+    segment.extend(source.port().to_be_bytes());
+    segment.extend(destination.port().to_be_bytes());
+    segment.extend(sequence.to_be_bytes());
+    segment.extend(0u32.to_be_bytes());
+    segment.extend([5 << 4, flags, 0xff, 0xff, 0, 0, 0, 0]);
+    segment.extend(payload);
+    segment
+}
+
+// An IPv4 or IPv6 packet carrying segment
+fn ip(source: SocketAddr, destination: SocketAddr, segment: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::new();
+    match (source.ip(), destination.ip()) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            packet.extend([0x45, 0]);
+            packet.extend((20 + segment.len() as u16).to_be_bytes());
+            packet.extend([0, 0, 0x40, 0, 64, 6, 0, 0]);
+            packet.extend(source.octets());
+            packet.extend(destination.octets());
+        }
+        (IpAddr::V6(source), IpAddr::V6(destination)) => {
+            packet.extend([0x60, 0, 0, 0]);
+            packet.extend((segment.len() as u16).to_be_bytes());
+            packet.extend([6, 64]);
+            packet.extend(source.octets());
+            packet.extend(destination.octets());
+        }
+        _ => unreachable!(),
+    }
+    packet.extend(segment);
+    packet
+}
+
+fn ethernet(ethertype: u16, vlan: bool, network: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0u8; 12];
+    if vlan {
+        frame.extend([0x81, 0x00, 0x00, 0x2a]);
+    }
+    frame.extend(ethertype.to_be_bytes());
+    frame.extend(network);
+    frame
+}
+
+fn ethertype(source: SocketAddr) -> u16 {
+    if source.is_ipv4() { 0x0800 } else { 0x86dd }
+}
+
+// A packet of the link type, from source to destination
+fn packet(linktype: Linktype, source: &str, destination: &str, sequence: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+    let (source, destination) = (address(source), address(destination));
+    let network = ip(source, destination, &tcp(source, destination, sequence, flags, payload));
+
+    match linktype {
+        Linktype::ETHERNET => ethernet(ethertype(source), true, &network),
+        Linktype::LINUX_SLL => {
+            let mut frame = vec![0u8; 14];
+            frame.extend(ethertype(source).to_be_bytes());
+            frame.extend(network);
+            frame
+        }
+        Linktype::NULL => {
+            let family: u32 = if source.is_ipv4() { 2 } else { 30 };
+            let mut frame = family.to_le_bytes().to_vec();
+            frame.extend(network);
+            frame
+        }
+        _ => network,
+    }
+}
+
+fn turn(direction: Direction, data: &[u8]) -> Turn {
+    Turn { direction, data: data.to_vec() }
+}
+
+fn reassemble(server: &str, linktype: Linktype, packets: &[Vec<u8>]) -> Vec<CapturedConnection> {
+    let mut reassembler = Reassembler::new(address(server));
+    for packet in packets {
+        reassembler.add_packet(linktype, packet);
+    }
+    reassembler.finish()
+}
+
+#[test]
+fn segments_out_of_order_and_retransmitted_are_read_once_in_order() {
+    let client = "10.0.0.2:40000";
+    let packets = [
+        packet(Linktype::ETHERNET, client, SERVER, 99, SYN, b""),
+        packet(Linktype::ETHERNET, SERVER, client, 499, SYN | ACK, b""),
+        packet(Linktype::ETHERNET, SERVER, client, 500, ACK, b"220 ready\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 105, ACK, b"x\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 100, ACK, b"EHLO "),
+        packet(Linktype::ETHERNET, client, SERVER, 100, ACK, b"EHLO "),
+        packet(Linktype::ETHERNET, SERVER, client, 511, ACK, b"250 OK\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 108, ACK, b"QUIT\r\n"),
+    ];
+
+    let connections = reassemble(SERVER, Linktype::ETHERNET, &packets);
+
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].client, address(client));
+    assert_eq!(connections[0].turns, [
+        turn(Direction::ToClient, b"220 ready\r\n"),
+        turn(Direction::ToServer, b"EHLO x\r\n"),
+        turn(Direction::ToClient, b"250 OK\r\n"),
+        turn(Direction::ToServer, b"QUIT\r\n"),
+    ]);
+}
+
+#[test]
+fn interleaved_connections_keep_the_order_of_their_own_requests() {
+    let (first, second) = ("10.0.0.2:40000", "10.0.0.3:40001");
+    let packets = [
+        packet(Linktype::ETHERNET, first, SERVER, 1, ACK, b"HELO a\r\n"),
+        packet(Linktype::ETHERNET, second, SERVER, 7, ACK, b"HELO b\r\n"),
+        packet(Linktype::ETHERNET, SERVER, second, 1, ACK, b"250 b\r\n"),
+        packet(Linktype::ETHERNET, SERVER, first, 1, ACK, b"250 a\r\n"),
+        packet(Linktype::ETHERNET, first, SERVER, 9, ACK, b"NOOP\r\n"),
+        packet(Linktype::ETHERNET, second, SERVER, 15, ACK, b"QUIT\r\n"),
+        packet(Linktype::ETHERNET, "10.0.0.4:40002", "10.0.0.9:25", 1, ACK, b"elsewhere\r\n"),
+    ];
+
+    let connections = reassemble(SERVER, Linktype::ETHERNET, &packets);
+    let requests: Vec<Vec<&[u8]>> = connections.iter().map(|connection| connection.requests().collect()).collect();
+
+    assert_eq!(requests, [vec![&b"HELO a\r\n"[..], b"NOOP\r\n"], vec![&b"HELO b\r\n"[..], b"QUIT\r\n"]]);
+}
+
+#[test]
+fn requests_without_a_reply_in_between_are_one_turn() {
+    let client = "10.0.0.2:40000";
+    let packets = [
+        packet(Linktype::ETHERNET, client, SERVER, 1, ACK, b"MAIL FROM:<a@b>\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 18, ACK, b"RCPT TO:<c@d>\r\n"),
+    ];
+
+    let connections = reassemble(SERVER, Linktype::ETHERNET, &packets);
+
+    assert_eq!(connections[0].turns, [turn(Direction::ToServer, b"MAIL FROM:<a@b>\r\nRCPT TO:<c@d>\r\n")]);
+}
+
+#[test]
+fn a_reused_client_port_starts_a_new_connection() {
+    let client = "10.0.0.2:40000";
+    let packets = [
+        packet(Linktype::ETHERNET, client, SERVER, 99, SYN, b""),
+        packet(Linktype::ETHERNET, client, SERVER, 100, ACK, b"QUIT\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 7000, SYN, b""),
+        packet(Linktype::ETHERNET, client, SERVER, 7001, ACK, b"NOOP\r\n"),
+    ];
+
+    let connections = reassemble(SERVER, Linktype::ETHERNET, &packets);
+
+    assert_eq!(connections.len(), 2);
+    assert_eq!(connections[1].turns, [turn(Direction::ToServer, b"NOOP\r\n")]);
+}
+
+#[test]
+fn ipv6_linux_cooked_loopback_and_raw_captures_are_read() {
+    let (server, client) = ("[::1]:25", "[::1]:40000");
+    for linktype in [Linktype::LINUX_SLL, Linktype::NULL, Linktype::RAW] {
+        let packets = [
+            packet(linktype, client, server, 1, ACK, b"NOOP\r\n"),
+            packet(linktype, server, client, 1, ACK, b"250 OK\r\n"),
+        ];
+
+        let connections = reassemble(server, linktype, &packets);
+
+        assert_eq!(connections.len(), 1, "{:?}", linktype);
+        assert_eq!(connections[0].turns, [turn(Direction::ToServer, b"NOOP\r\n"), turn(Direction::ToClient, b"250 OK\r\n")]);
+    }
+}
+
+#[test]
+fn ethernet_padding_is_not_part_of_the_payload() {
+    let mut short = packet(Linktype::ETHERNET, "10.0.0.2:40000", SERVER, 1, ACK, b"Q");
+    short.extend([0u8; 8]);
+
+    let connections = reassemble("0.0.0.0:25", Linktype::ETHERNET, &[short]);
+
+    assert_eq!(connections[0].turns, [turn(Direction::ToServer, b"Q")]);
+}
+
+#[test]
+fn each_answered_request_leads_from_the_previous_reply_to_its_own() {
+    let client = "10.0.0.2:40000";
+    let packets = [
+        packet(Linktype::ETHERNET, client, SERVER, 1, ACK, b"NOOP\r\n"),
+        packet(Linktype::ETHERNET, SERVER, client, 1, ACK, b"220 ready\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 7, ACK, b"EHLO x\r\n"),
+        packet(Linktype::ETHERNET, SERVER, client, 12, ACK, b"250-x\r\n250 SIZE\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 15, ACK, b"MAIL FROM:<a@b>\r\n"),
+        packet(Linktype::ETHERNET, SERVER, client, 29, ACK, b"250 OK\r\n"),
+        packet(Linktype::ETHERNET, client, SERVER, 32, ACK, b"QUIT\r\n"),
+    ];
+
+    let connections = reassemble(SERVER, Linktype::ETHERNET, &packets);
+    let transitions = state_transitions(&SMTP, &SMTP.framing(), &connections);
+
+    // The NOOP went out before the greeting and the QUIT was never answered. SMTP's framing
+    // reads the EHLO reply as one response
+    let state = |reply: &[u8]| SMTP.parse_response(&Response::new(reply.to_vec()));
+    let steps: Vec<_> = transitions.iter()
+        .map(|transition| (transition.source_state.clone(), transition.message.data.clone(), transition.target_state.clone()))
+        .collect();
+    assert_eq!(steps, [
+        (state(b"220 ready\r\n"), b"EHLO x\r\n".to_vec(), state(b"250-x\r\n250 SIZE\r\n")),
+        (state(b"250-x\r\n250 SIZE\r\n"), b"MAIL FROM:<a@b>\r\n".to_vec(), state(b"250 OK\r\n")),
+    ]);
+}
+
+#[test]
+fn the_state_model_counts_every_session_a_transition_was_seen_in() {
+    let mut packets = Vec::new();
+    for client in ["10.0.0.2:40000", "10.0.0.3:40000", "10.0.0.4:40000"] {
+        packets.push(packet(Linktype::ETHERNET, SERVER, client, 1, ACK, b"220 ready\r\n"));
+        packets.push(packet(Linktype::ETHERNET, client, SERVER, 1, ACK, b"NOOP\r\n"));
+        packets.push(packet(Linktype::ETHERNET, SERVER, client, 12, ACK, b"250 OK\r\n"));
+    }
+    let connections = reassemble(SERVER, Linktype::ETHERNET, &packets);
+
+    let mut client = Client::new(String::from(SERVER), TransportProtocol::TCP, SMTP);
+    client.seed_state_model(&connections);
+
+    let transitions: Vec<_> = client.state_model().inner.values().flatten().collect();
+    assert_eq!(transitions.len(), 1);
+    assert_eq!(transitions[0].occurrences, 3);
+}
@@ -0,0 +1,114 @@
+// Saving a fuzzing run to a checkpoint and resuming it, against an in-process SMTP stand-in
+// which answers the first three commands of a connection with "250 OK" and then stays silent.
+// In process, the sequences run without their delays
+use std::path::PathBuf;
+
+use fuzz_client::{Campaign, Checkpoint, Client, FuzzConfig, HandlerFactory, SMTP, TransportProtocol};
+
+
+const SEED: u64 = 42;
+const TARGET: &str = "stand-in";
+
+fn stand_in() -> HandlerFactory {
+    HandlerFactory::new(|| {
+        let mut commands = 0;
+        move |_: &[u8]| {
+            commands += 1;
+            if commands > 3 {
+                return Vec::new();
+            }
+            b"250 OK\r\n".to_vec()
+        }
+    })
+}
+
+// Response times are left out of the fitness, so that a run only depends on its seed
+fn config(generations: usize) -> FuzzConfig {
+    FuzzConfig { generations, response_time_weight: 0.0, ..Campaign::default_fuzz_config() }
+}
+
+fn output_directory(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("fuzz_client_checkpoint_{}_{}", name, std::process::id()))
+}
+
+fn client(output_directory: PathBuf) -> Client<SMTP> {
+    let mut client = Client::with_seed(String::from(TARGET), TransportProtocol::InProcess, SMTP, SEED);
+    configure(&mut client, output_directory);
+    client
+}
+
+fn configure(client: &mut Client<SMTP>, output_directory: PathBuf) {
+    client.output_directory = output_directory;
+    client.checkpoint_interval = 1;
+    client.greeting = false;
+    client.transport_options.handler = Some(stand_in());
+}
+
+#[test]
+fn a_checkpoint_holds_the_state_the_run_reached() {
+    let directory = output_directory("saved");
+    let mut client = client(directory.clone());
+    client.fuzz(config(2), false).unwrap();
+
+    let checkpoint: Checkpoint<SMTP> = Checkpoint::load(client.checkpoint_path()).unwrap();
+
+    assert_eq!(checkpoint.generation, 2);
+    assert_eq!(checkpoint.seed, SEED);
+    assert_eq!(checkpoint.fitness_history.len(), 2);
+    assert!(checkpoint.corpus == client.corpus);
+    assert!(checkpoint.state_model == *client.state_model());
+    assert_eq!(&checkpoint.rng, client.rng());
+    assert!(!checkpoint.crash_buckets.is_empty());
+    assert_eq!(&checkpoint.crash_buckets, client.crash_buckets());
+
+    // A resumed client is the same run again, down to its message pool
+    let config = checkpoint.config.clone();
+    let saved: Checkpoint<SMTP> = Checkpoint::load(client.checkpoint_path()).unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    let resumed = Client::from_checkpoint(String::from(TARGET), TransportProtocol::InProcess, SMTP, saved).checkpoint(&config);
+    assert_eq!(resumed.generation, checkpoint.generation);
+    assert!(resumed.corpus == checkpoint.corpus && resumed.message_pool == checkpoint.message_pool && resumed.state_model == checkpoint.state_model);
+    assert_eq!(resumed.fitness_history, checkpoint.fitness_history);
+    assert_eq!(resumed.crash_buckets, checkpoint.crash_buckets);
+    assert_eq!((resumed.seed, &resumed.rng), (checkpoint.seed, &checkpoint.rng));
+}
+
+#[test]
+fn a_resumed_run_continues_like_an_uninterrupted_one() {
+
+    let uninterrupted_directory = output_directory("uninterrupted");
+    let mut uninterrupted = client(uninterrupted_directory.clone());
+    uninterrupted.fuzz(config(3), false).unwrap();
+
+    let interrupted_directory = output_directory("interrupted");
+    let mut interrupted = client(interrupted_directory.clone());
+    interrupted.fuzz(config(2), false).unwrap();
+    let checkpoint: Checkpoint<SMTP> = Checkpoint::load(interrupted.checkpoint_path()).unwrap();
+    let crashes_before_restart = checkpoint.crash_buckets.clone();
+
+    let mut resumed = Client::from_checkpoint(String::from(TARGET), TransportProtocol::InProcess, SMTP, checkpoint);
+    configure(&mut resumed, interrupted_directory.clone());
+    resumed.fuzz(config(3), false).unwrap();
+
+    let expected: Checkpoint<SMTP> = Checkpoint::load(uninterrupted.checkpoint_path()).unwrap();
+    let actual: Checkpoint<SMTP> = Checkpoint::load(resumed.checkpoint_path()).unwrap();
+    std::fs::remove_dir_all(&uninterrupted_directory).unwrap();
+    std::fs::remove_dir_all(&interrupted_directory).unwrap();
+
+    assert_eq!(actual.generation, 3);
+    assert_eq!(actual.fitness_history, expected.fitness_history);
+    assert!(actual.corpus == expected.corpus);
+    assert!(actual.message_pool == expected.message_pool);
+    assert!(actual.state_model == expected.state_model);
+    assert_eq!(actual.rng, expected.rng);
+
+    // The crashes found before the restart are still counted
+    assert_eq!(resumed.crash_buckets().keys().collect::<Vec<_>>(), uninterrupted.crash_buckets().keys().collect::<Vec<_>>());
+    for (bucket, crashes) in &crashes_before_restart {
+        assert!(resumed.crash_buckets()[bucket].hits >= crashes.hits);
+    }
+    assert_eq!(
+        resumed.crash_buckets().values().map(|bucket| bucket.hits).collect::<Vec<_>>(),
+        uninterrupted.crash_buckets().values().map(|bucket| bucket.hits).collect::<Vec<_>>(),
+    );
+}
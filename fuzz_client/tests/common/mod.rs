@@ -0,0 +1,141 @@
+// Local stand-in targets shared by the integration tests. Each test file only uses some of them
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use fuzz_client::{Client, Message, MessageSequence, SMTP, Trace, TransportProtocol};
+
+
+pub const SMTP_BANNER: &[u8] = b"220 localhost ESMTP\r\n";
+
+// What a stand-in does after a read
+pub enum Reply {
+    Answer(Vec<u8>),
+    AnswerAndHangUp(Vec<u8>),
+    // The answer in several writes with a pause after each, so that the client reads them apart
+    AnswerInPieces(Vec<Vec<u8>>, Duration),
+    HangUp,
+}
+
+// A TCP stand-in listening on an ephemeral port, with what it has seen so far
+pub struct StandIn {
+    pub address: String,
+    connections: Arc<AtomicUsize>,
+    reads: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl StandIn {
+    // The number of connections accepted so far
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    // Every chunk read so far, over all connections
+    pub fn reads(&self) -> Vec<Vec<u8>> {
+        self.reads.lock().unwrap().clone()
+    }
+}
+
+// Spawn a stand-in which sends banner on connecting, if there is one, and then hands every
+// read to reply. Every connection gets its own copy of reply, so state it keeps is per
+// connection. Once the client stops sending reply sees an empty read, and what it answers
+// then is the last thing the client can read
+pub fn spawn_stand_in<F>(banner: Option<&'static [u8]>, reply: F) -> StandIn
+where
+    F: FnMut(&[u8]) -> Reply + Clone + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let stand_in = StandIn {
+        address: listener.local_addr().unwrap().to_string(),
+        connections: Arc::new(AtomicUsize::new(0)),
+        reads: Arc::new(Mutex::new(Vec::new())),
+    };
+    let accepted = stand_in.connections.clone();
+    let recorded = stand_in.reads.clone();
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            accepted.fetch_add(1, Ordering::SeqCst);
+            let recorded = recorded.clone();
+            let mut reply = reply.clone();
+            thread::spawn(move || {
+                if banner.is_some_and(|banner| stream.write_all(banner).is_err()) {
+                    return;
+                }
+                let mut buffer = [0u8; 1024];
+                while let Ok(n) = stream.read(&mut buffer) {
+                    if n > 0 {
+                        recorded.lock().unwrap().push(buffer[..n].to_vec());
+                    }
+                    let hang_up = match reply(&buffer[..n]) {
+                        Reply::Answer(answer) => stream.write_all(&answer).is_err(),
+                        Reply::AnswerAndHangUp(answer) => {
+                            let _ = stream.write_all(&answer);
+                            true
+                        }
+                        Reply::AnswerInPieces(pieces, pause) => pieces.iter().any(|piece| {
+                            let failed = stream.write_all(piece).is_err();
+                            thread::sleep(pause);
+                            failed
+                        }),
+                        Reply::HangUp => true,
+                    };
+                    if hang_up || n == 0 {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    stand_in
+}
+
+// An SMTP stand-in which greets and then answers every read with reply
+pub fn spawn_smtp_stand_in<F>(reply: F) -> StandIn
+where
+    F: FnMut(&[u8]) -> Reply + Clone + Send + 'static,
+{
+    spawn_stand_in(Some(SMTP_BANNER), reply)
+}
+
+// Spawn a UDP stand-in which sends back the datagrams reply returns for each one it receives,
+// and return its address
+pub fn spawn_udp_stand_in<F>(mut reply: F) -> String
+where
+    F: FnMut(&[u8]) -> Vec<Vec<u8>> + Send + 'static,
+{
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = socket.local_addr().unwrap().to_string();
+
+    thread::spawn(move || {
+        let mut buffer = [0u8; 65535];
+        while let Ok((n, peer)) = socket.recv_from(&mut buffer) {
+            for datagram in reply(&buffer[..n]) {
+                socket.send_to(&datagram, peer).unwrap();
+            }
+        }
+    });
+
+    address
+}
+
+// SMTP commands sent back to back
+pub fn sequence(commands: &[&[u8]]) -> MessageSequence<SMTP> {
+    let messages = commands.iter().map(|&bytes| Message::from_bytes(SMTP, bytes)).collect();
+    MessageSequence::from_messages(messages, vec![0.0; commands.len().saturating_sub(1)])
+}
+
+// Run corpus against stand_in with an SMTP client which configure set up, and return the trace
+// of every sequence
+pub fn run(stand_in: &StandIn, corpus: Vec<MessageSequence<SMTP>>, configure: impl FnOnce(&mut Client<SMTP>)) -> Vec<Trace<SMTP>> {
+    let mut client = Client::new(stand_in.address.clone(), TransportProtocol::TCP, SMTP);
+    configure(&mut client);
+    client.corpus = corpus;
+    client.run_corpus()
+}
@@ -0,0 +1,91 @@
+// Control steps between the messages of a sequence, against a local SMTP stand-in which greets,
+// answers every command with "250 OK" and says "221 bye" once the client stops sending
+mod common;
+
+use fuzz_client::{Control, MessageSequence, SMTP, Trace, Verdict};
+use fuzz_client::seeded_rng;
+
+use common::{spawn_smtp_stand_in, Reply};
+
+
+fn noops(count: usize) -> MessageSequence<SMTP> {
+    common::sequence(&vec![&b"NOOP \r\n"[..]; count])
+}
+
+// The trace of the sequence and the number of connections the stand-in accepted for it
+fn run(sequence: MessageSequence<SMTP>) -> (Trace<SMTP>, usize) {
+    let stand_in = spawn_smtp_stand_in(|request: &[u8]| match request.is_empty() {
+        true => Reply::Answer(b"221 bye\r\n".to_vec()),
+        false => Reply::Answer(b"250 OK\r\n".to_vec()),
+    });
+    let trace = common::run(&stand_in, vec![sequence], |_| {}).remove(0);
+    (trace, stand_in.connections())
+}
+
+#[test]
+fn a_reconnect_sends_the_rest_over_a_new_connection() {
+    let mut sequence = noops(2);
+    sequence.insert_control(1, Control::Reconnect);
+    let (trace, connections) = run(sequence);
+
+    assert_eq!(connections, 2);
+    assert_eq!(trace.outcome.verdict, Verdict::Reply);
+    assert_eq!(trace.interactions.len(), 2);
+
+    // The reconnect reads the greeting of the new connection
+    let (step, greeting) = &trace.controls[0];
+    assert_eq!((step.before, &step.control), (1, &Control::Reconnect));
+    assert_eq!(greeting.data, b"220 localhost ESMTP\r\n");
+}
+
+#[test]
+fn a_wait_after_a_half_close_reads_the_final_reply() {
+    let mut sequence = noops(1);
+    sequence.insert_control(1, Control::HalfClose);
+    sequence.insert_control(1, Control::WaitForData(1.0));
+    let (trace, connections) = run(sequence);
+
+    assert_eq!(connections, 1);
+    assert_eq!(trace.outcome.verdict, Verdict::Reply);
+    assert_eq!(trace.controls.len(), 2);
+    assert_eq!(trace.controls[1].1.data, b"221 bye\r\n");
+}
+
+#[test]
+fn a_message_after_a_close_reconnects_first() {
+    let mut sequence = noops(2);
+    sequence.insert_control(1, Control::Close);
+    let (trace, connections) = run(sequence);
+
+    assert_eq!(connections, 2);
+    assert_eq!(trace.interactions[1].1.data, b"250 OK\r\n");
+    let controls: Vec<&Control> = trace.controls.iter().map(|(step, _)| &step.control).collect();
+    assert_eq!(controls, [&Control::Close, &Control::Reconnect]);
+}
+
+#[test]
+fn mutation_places_control_steps_between_the_messages() {
+    let mut rng = seeded_rng(7);
+    let mut sequence = noops(4);
+
+    for _ in 0..200 {
+        sequence.mutate_message_sequence(SMTP, 0.0, &[], &mut rng);
+        sequence.mutate_controls(&mut rng);
+    }
+
+    assert!(!sequence.controls.is_empty());
+    assert!(sequence.controls.iter().all(|step| step.before <= sequence.messages.len()));
+    assert!(sequence.controls.windows(2).all(|pair| pair[0].before <= pair[1].before));
+}
+
+#[test]
+fn sequence_mutation_alone_leaves_control_steps_out() {
+    let mut rng = seeded_rng(7);
+    let mut sequence = noops(4);
+
+    for _ in 0..200 {
+        sequence.mutate_message_sequence(SMTP, 0.0, &[], &mut rng);
+    }
+
+    assert!(sequence.controls.is_empty());
+}
@@ -0,0 +1,116 @@
+// A Transport written outside the crate and plugged in through TransportProtocol::Custom. The
+// loopback target answers every request with the request in upper case, and "COUNT" with the
+// number of requests it was sent since the connection was opened
+use std::time::Duration;
+
+use fuzz_client::{Campaign, Client, Error, Framing, GreetingProtocol, Message, MessageSequence, Response, Transport, TransportFactory, TransportOptions, TransportProtocol, Verdict};
+
+
+struct Loopback {
+    requests: usize,
+    pending: Vec<Vec<u8>>,
+}
+
+impl Transport for Loopback {
+    fn connect(server_address: &str, _options: &TransportOptions) -> Result<Self, Error> {
+        if server_address != "loopback" {
+            let refused = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "no such loopback");
+            return Err(Error::Connect(server_address.to_string(), refused));
+        }
+        Ok(Self { requests: 0, pending: Vec::new() })
+    }
+
+    fn send(&mut self, message: &[u8]) -> Result<(), Error> {
+        self.requests += 1;
+        let response = if message.starts_with(b"COUNT") {
+            self.requests.to_string().into_bytes()
+        } else {
+            message.to_ascii_uppercase()
+        };
+        self.pending.push(response);
+        Ok(())
+    }
+
+    fn receive(&mut self, _framing: &Framing, _timeout: Duration) -> Result<Response, Error> {
+        Ok(Response::new(self.pending.remove(0)))
+    }
+
+    fn shutdown(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        *self = Self::connect("loopback", &TransportOptions::default())?;
+        Ok(())
+    }
+}
+
+fn loopback() -> TransportProtocol {
+    TransportProtocol::Custom(String::from("loopback"))
+}
+
+fn options() -> TransportOptions {
+    let mut options = TransportOptions::default();
+    options.custom.insert(String::from("loopback"), TransportFactory::new(Loopback::connect));
+    options
+}
+
+fn exchange(transport: &mut dyn Transport, request: &[u8]) -> Vec<u8> {
+    transport.send(request).unwrap();
+    transport.receive(&Framing::line(), Duration::from_secs(5)).unwrap().data
+}
+
+#[test]
+fn client_replays_through_a_custom_transport() {
+    let mut client = Client::new(String::from("loopback"), loopback(), GreetingProtocol);
+    client.transport_options = options();
+
+    let messages = [&b"hello"[..], b"COUNT"].iter()
+        .map(|&bytes| Message::from_bytes(GreetingProtocol, bytes))
+        .collect();
+    let transcript = client.replay(&MessageSequence::from_messages(messages, vec![0.0]), &[]);
+
+    assert_eq!(transcript.outcome.verdict, Verdict::Reply);
+    // Greeting messages are padded to the length of a header
+    assert!(transcript.entries[0].received.starts_with(b"HELLO"));
+    assert_eq!(transcript.entries[1].received, b"2");
+}
+
+#[test]
+fn reset_starts_over_and_connect_errors_are_judged() {
+    let mut transport = loopback().connect("loopback", &options()).unwrap();
+    assert_eq!(exchange(transport.as_mut(), b"COUNT"), b"1");
+    assert_eq!(exchange(transport.as_mut(), b"COUNT"), b"2");
+
+    transport.reset().unwrap();
+    assert_eq!(exchange(transport.as_mut(), b"COUNT"), b"1");
+
+    let error = loopback().connect("elsewhere", &options()).err().unwrap();
+    assert_eq!(Verdict::from_error(&error), Verdict::Refused);
+
+    // A name nothing was registered under cannot be connected
+    assert!(TransportProtocol::Custom(String::from("serial")).connect("loopback", &options()).is_err());
+}
+
+#[test]
+fn campaign_files_name_custom_transports() {
+    let path = std::env::temp_dir().join(format!("fuzz_client_custom_{}.toml", std::process::id()));
+    Campaign::new(String::from("loopback"), loopback(), String::from("greeting")).save(&path).unwrap();
+    let campaign = Campaign::load(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    let campaign = campaign.unwrap();
+    assert_eq!(campaign.target.transport, loopback());
+    assert_eq!(campaign.target.transport.to_string(), "loopback");
+}
+
+#[test]
+fn campaign_files_spell_transports_like_the_command_line() {
+    let path = std::env::temp_dir().join(format!("fuzz_client_spelling_{}.toml", std::process::id()));
+    for transport in [TransportProtocol::TCP, TransportProtocol::UDP, TransportProtocol::TLS, TransportProtocol::UnixStream, TransportProtocol::UnixDatagram, TransportProtocol::Pipe, TransportProtocol::InProcess] {
+        Campaign::new(String::from("target"), transport.clone(), String::from("smtp")).save(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert!(text.contains(&format!("transport = \"{}\"", transport)), "{}", text);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
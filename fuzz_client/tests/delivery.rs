@@ -0,0 +1,110 @@
+// How the writes of a message reach the target, against a local SMTP stand-in which records
+// every read it makes and answers each complete line with "250 OK"
+mod common;
+
+use fuzz_client::{minimize, Client, Delivery, Message, MessageSequence, MinimizationGoal, Outcome, Reproducer, SMTP, Timeouts, Trace, TransportProtocol, Verdict};
+
+use common::{spawn_smtp_stand_in, Reply};
+
+
+fn noop(delivery: Delivery) -> Message<SMTP> {
+    let mut message = Message::from_bytes(SMTP, b"NOOP \r\n");
+    message.delivery = delivery;
+    message
+}
+
+// The trace of the messages and the chunks the stand-in read them in
+fn run(messages: Vec<Message<SMTP>>) -> (Trace<SMTP>, Vec<Vec<u8>>) {
+    let stand_in = spawn_smtp_stand_in(|read: &[u8]| {
+        let lines = read.iter().filter(|&&byte| byte == b'\n').count();
+        Reply::Answer(b"250 OK\r\n".repeat(lines))
+    });
+    let timings = vec![0.0; messages.len().saturating_sub(1)];
+    let trace = common::run(&stand_in, vec![MessageSequence::from_messages(messages, timings)], |_| {}).remove(0);
+    (trace, stand_in.reads())
+}
+
+#[test]
+fn a_fragmented_message_arrives_in_its_pieces() {
+    let (trace, reads) = run(vec![noop(Delivery::Fragmented { offsets: vec![2, 4], delay: 0.1 })]);
+
+    assert_eq!(reads, [b"NO".to_vec(), b"OP".to_vec(), b" \r\n".to_vec()]);
+    assert_eq!(trace.outcome.verdict, Verdict::Reply);
+    assert_eq!(trace.interactions[0].1.data, b"250 OK\r\n");
+}
+
+#[test]
+fn a_slow_message_arrives_one_byte_at_a_time() {
+    let (trace, reads) = run(vec![noop(Delivery::Slow { delay: 0.02 })]);
+
+    assert_eq!(reads.len(), b"NOOP \r\n".len());
+    assert!(reads.iter().all(|read| read.len() == 1));
+    assert_eq!(trace.interactions[0].1.data, b"250 OK\r\n");
+}
+
+#[test]
+fn pipelined_messages_share_a_write_and_are_answered_in_turn() {
+    let (trace, reads) = run(vec![noop(Delivery::Pipelined), noop(Delivery::Pipelined), noop(Delivery::Whole)]);
+
+    assert_eq!(reads, [b"NOOP \r\nNOOP \r\nNOOP \r\n".to_vec()]);
+    assert_eq!(trace.interactions.len(), 3);
+    assert!(trace.interactions.iter().all(|(_, response)| response.data == b"250 OK\r\n"));
+}
+
+#[test]
+fn offsets_outside_the_message_are_ignored() {
+    let delivery = Delivery::Fragmented { offsets: vec![0, 3, 3, 40], delay: 0.0 };
+    assert_eq!(delivery.segments(b"HELLO"), [&b"HEL"[..], &b"LO"[..]]);
+    assert_eq!(Delivery::Slow { delay: 0.0 }.segments(b""), [&b""[..]]);
+}
+
+#[test]
+fn minimization_keeps_the_delivery_a_crash_needs() {
+    // A stand-in which hangs up on a line that took it more than one read
+    let mut reads = 0;
+    let stand_in = spawn_smtp_stand_in(move |read: &[u8]| {
+        reads += 1;
+        if !read.contains(&b'\n') {
+            return Reply::Answer(Vec::new());
+        }
+        if reads > 1 {
+            return Reply::HangUp;
+        }
+        reads = 0;
+        Reply::Answer(b"250 OK\r\n".to_vec())
+    });
+
+    // Candidates which cut a line off are never answered, so they should fail quickly
+    let mut client = Client::new(stand_in.address.clone(), TransportProtocol::TCP, SMTP);
+    client.transport_options.timeouts = Timeouts { read: 0.3, ..Timeouts::default() };
+    let fragmented = noop(Delivery::Fragmented { offsets: vec![2], delay: 0.2 });
+    let sequence = MessageSequence::from_messages(vec![noop(Delivery::Whole), fragmented, noop(Delivery::Whole)], vec![0.0, 0.0]);
+    let outcome = client.replay(&sequence, &[]).outcome;
+    assert_eq!(outcome.verdict, Verdict::Closed);
+
+    let minimized = minimize(&mut client, &sequence, &MinimizationGoal::Outcome(outcome)).unwrap();
+
+    assert_eq!(minimized.messages.len(), 1);
+    assert!(matches!(&minimized.messages[0].delivery, Delivery::Fragmented { offsets, .. } if *offsets == [2]));
+}
+
+#[test]
+fn sequences_which_differ_only_in_delivery_get_their_own_reproducers() {
+    let output_directory = std::env::temp_dir().join(format!("fuzz_client_delivery_{}", std::process::id()));
+    let deliveries = [Delivery::Whole, Delivery::Fragmented { offsets: vec![2], delay: 0.1 }, Delivery::Slow { delay: 0.1 }];
+
+    let paths: Vec<_> = deliveries.into_iter().map(|delivery| {
+        let mut reproducer: Reproducer<SMTP> = Reproducer {
+            target: String::from("127.0.0.1:8025"),
+            transport: TransportProtocol::TCP,
+            outcome: Outcome { verdict: Verdict::Reset, ..Outcome::new() },
+            replay_command: String::new(),
+            sequence: MessageSequence::from_messages(vec![noop(Delivery::Whole), noop(delivery)], vec![0.0]),
+            states: Vec::new(),
+        };
+        reproducer.save(&output_directory, "smtp", None).unwrap()
+    }).collect();
+    std::fs::remove_dir_all(&output_directory).unwrap();
+
+    assert!(paths[0] != paths[1] && paths[1] != paths[2] && paths[0] != paths[2], "{:?}", paths);
+}
@@ -0,0 +1,61 @@
+// Runs with the same seed against a deterministic target, an in-process SMTP stand-in which
+// answers every command with "250 OK" after a delay depending on the command, so that parallel
+// workers finish their sequences in a different order every time
+use std::thread;
+use std::time::Duration;
+
+use fuzz_client::{Campaign, Client, FuzzConfig, HandlerFactory, SMTP, TransportProtocol};
+
+
+fn stand_in() -> HandlerFactory {
+    HandlerFactory::new(|| {
+        |request: &[u8]| {
+            thread::sleep(Duration::from_millis(request.len() as u64 % 7));
+            b"250 OK\r\n".to_vec()
+        }
+    })
+}
+
+// Response times are left out of the fitness, as they differ between any two runs
+fn config() -> FuzzConfig {
+    FuzzConfig { generations: 4, response_time_weight: 0.0, ..Campaign::default_fuzz_config() }
+}
+
+fn fuzz(seed: u64, workers: usize) -> Client<SMTP> {
+    let mut client = Client::with_seed(String::from("stand-in"), TransportProtocol::InProcess, SMTP, seed);
+    client.output_directory = std::env::temp_dir().join(format!("fuzz_client_determinism_{}_{}_{}", seed, workers, std::process::id()));
+    client.greeting = false;
+    client.workers = workers;
+    client.transport_options.handler = Some(stand_in());
+
+    client.fuzz(config(), false).unwrap();
+    std::fs::remove_dir_all(&client.output_directory).unwrap();
+    client
+}
+
+fn assert_same_run(first: &Client<SMTP>, second: &Client<SMTP>) {
+    let (first, second) = (first.checkpoint(&config()), second.checkpoint(&config()));
+    assert!(first.corpus == second.corpus);
+    assert!(first.message_pool == second.message_pool);
+    assert!(first.state_model == second.state_model);
+    assert_eq!(first.fitness_history, second.fitness_history);
+    assert_eq!(first.rng, second.rng);
+}
+
+#[test]
+fn runs_with_the_same_seed_produce_the_same_corpus_and_state_model() {
+    assert_same_run(&fuzz(7, 1), &fuzz(7, 1));
+}
+
+#[test]
+fn parallel_workers_do_not_change_the_run() {
+    let parallel = fuzz(7, 8);
+    assert_same_run(&parallel, &fuzz(7, 8));
+    assert_same_run(&parallel, &fuzz(7, 1));
+}
+
+#[test]
+fn another_seed_gives_another_run() {
+    let (first, second) = (fuzz(7, 1).checkpoint(&config()), fuzz(8, 1).checkpoint(&config()));
+    assert!(first.corpus != second.corpus);
+}
@@ -0,0 +1,143 @@
+// Splitting responses out of a stream, both on buffers and over TCP against a local stand-in
+// which answers each request with the bytes it asks for. Framings which validate rejects may
+// still be handed to a transport by a Protocol or a Client, and must not panic there
+mod common;
+
+use std::time::Duration;
+
+use fuzz_client::{Framing, Protocol, SMTP, Transport, TransportOptions, TransportProtocol};
+
+use common::{spawn_stand_in, Reply};
+
+
+const EHLO_REPLY: &[u8] = b"250-localhost\r\n250-PIPELINING\r\n250 SIZE 1000\r\n";
+
+// A message in srv's wire format: a 4 byte type, the 8 byte big-endian length of the body and the body
+fn srv_message(message_type: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut bytes = message_type.to_vec();
+    bytes.extend((body.len() as u64).to_be_bytes());
+    bytes.extend(body);
+    bytes
+}
+
+fn srv_framing() -> Framing {
+    Framing::LengthPrefixed { length_offset: 4, length_size: 8, header_size: 12, little_endian: false, length_includes_header: false }
+}
+
+// A connection to a stand-in which answers EHLO with a multi-line reply cut in the middle of its
+// lines, FRAMES with two srv messages in one write, SPLIT with one srv message in three writes,
+// PAUSE with two lines 300 ms apart, and anything else with itself
+fn connect() -> Box<dyn Transport> {
+    let stand_in = spawn_stand_in(None, |request: &[u8]| {
+        let pause = Duration::from_millis(50);
+        match request {
+            b"EHLO" => Reply::AnswerInPieces(vec![EHLO_REPLY[..20].to_vec(), EHLO_REPLY[20..].to_vec()], pause),
+            b"FRAMES" => Reply::Answer([srv_message(b"HELO", b"first"), srv_message(b"BYE_", b"second")].concat()),
+            b"SPLIT" => {
+                let message = srv_message(b"HELO", b"in three pieces");
+                Reply::AnswerInPieces(vec![message[..3].to_vec(), message[3..14].to_vec(), message[14..].to_vec()], pause)
+            }
+            b"PAUSE" => Reply::AnswerInPieces(vec![b"one\n".to_vec(), b"two\n".to_vec()], Duration::from_millis(300)),
+            _ => Reply::Answer(request.to_vec()),
+        }
+    });
+    TransportProtocol::TCP.connect(&stand_in.address, &TransportOptions::default()).unwrap()
+}
+
+fn exchange(transport: &mut dyn Transport, request: &[u8], framing: &Framing) -> Vec<u8> {
+    transport.send(request).unwrap();
+    receive(transport, framing)
+}
+
+fn receive(transport: &mut dyn Transport, framing: &Framing) -> Vec<u8> {
+    transport.receive(framing, Duration::from_secs(2)).unwrap().data
+}
+
+#[test]
+fn an_smtp_multiline_reply_is_one_response() {
+    let framing = SMTP.framing();
+    assert_eq!(framing, Framing::SmtpMultiline);
+
+    let buffer = [EHLO_REPLY, b"250 OK\r\n"].concat();
+    assert_eq!(framing.frame_length(&buffer), Some(EHLO_REPLY.len()));
+    assert_eq!(framing.frame_length(&EHLO_REPLY[..30]), None);
+
+    let mut transport = connect();
+    assert_eq!(exchange(transport.as_mut(), b"EHLO", &framing), EHLO_REPLY);
+}
+
+#[test]
+fn length_prefixed_frames_are_read_one_at_a_time() {
+    let framing = srv_framing();
+    let first = srv_message(b"HELO", b"first");
+    assert_eq!(framing.frame_length(&first[..11]), None);
+    assert_eq!(framing.frame_length(&first[..16]), None);
+    assert_eq!(framing.frame_length(&[first.clone(), b"rest".to_vec()].concat()), Some(first.len()));
+
+    let mut transport = connect();
+    assert_eq!(exchange(transport.as_mut(), b"FRAMES", &framing), first);
+    assert_eq!(receive(transport.as_mut(), &framing), srv_message(b"BYE_", b"second"));
+}
+
+#[test]
+fn lengths_can_be_little_endian_and_count_the_header() {
+    let little_endian = Framing::LengthPrefixed { length_offset: 0, length_size: 2, header_size: 2, little_endian: true, length_includes_header: false };
+    assert_eq!(little_endian.frame_length(b"\x03\x00abcdef"), Some(5));
+
+    let including_header = Framing::LengthPrefixed { length_offset: 1, length_size: 2, header_size: 3, little_endian: false, length_includes_header: true };
+    assert_eq!(including_header.frame_length(b"T\x00\x05abcdef"), Some(5));
+    // A length shorter than the header still takes the whole header
+    assert_eq!(including_header.frame_length(b"T\x00\x01abcdef"), Some(3));
+}
+
+#[test]
+fn a_frame_split_across_several_reads_is_put_back_together() {
+    let mut transport = connect();
+    assert_eq!(exchange(transport.as_mut(), b"SPLIT", &srv_framing()), srv_message(b"HELO", b"in three pieces"));
+}
+
+#[test]
+fn fixed_size_responses_are_cut_from_the_stream() {
+    let framing = Framing::FixedSize { size: 4 };
+    assert_eq!(framing.frame_length(b"abc"), None);
+    assert_eq!(framing.frame_length(b"abcdef"), Some(4));
+
+    let mut transport = connect();
+    assert_eq!(exchange(transport.as_mut(), b"abcdefgh", &framing), b"abcd");
+    assert_eq!(receive(transport.as_mut(), &framing), b"efgh");
+}
+
+#[test]
+fn an_idle_response_ends_when_the_target_goes_quiet() {
+    assert_eq!(Framing::Idle { quiet_period: 0.1 }.frame_length(b"anything"), None);
+
+    // Pauses shorter than the quiet period stay within the response
+    let mut transport = connect();
+    assert_eq!(exchange(transport.as_mut(), b"PAUSE", &Framing::Idle { quiet_period: 1.0 }), b"one\ntwo\n");
+
+    let mut transport = connect();
+    let framing = Framing::Idle { quiet_period: 0.1 };
+    assert_eq!(exchange(transport.as_mut(), b"PAUSE", &framing), b"one\n");
+    assert_eq!(receive(transport.as_mut(), &framing), b"two\n");
+}
+
+#[test]
+fn an_empty_delimiter_finds_no_response() {
+    let framing = Framing::Delimiter { delimiter: String::new() };
+    assert_eq!(framing.frame_length(b"250 OK\r\n"), None);
+}
+
+#[test]
+fn a_length_outside_the_header_is_read_once_it_arrived() {
+    let framing = Framing::LengthPrefixed { length_offset: 2, length_size: 2, header_size: 2, little_endian: false, length_includes_header: false };
+    assert_eq!(framing.frame_length(b"AB"), None);
+    assert_eq!(framing.frame_length(b"AB\x00\x01"), Some(3));
+}
+
+#[test]
+fn an_empty_response_is_never_complete() {
+    assert_eq!(Framing::FixedSize { size: 0 }.frame_length(b"data"), None);
+
+    let framing = Framing::LengthPrefixed { length_offset: 0, length_size: 1, header_size: 0, little_endian: false, length_includes_header: false };
+    assert_eq!(framing.frame_length(b"\x00"), None);
+}
@@ -0,0 +1,64 @@
+// Reading the greeting of a target which speaks first, against a local SMTP stand-in which sends
+// its 220 banner on connecting, unless it is told to stay silent, and answers every command with 250
+mod common;
+
+use std::time::{Duration, Instant};
+
+use fuzz_client::{Client, Message, MessageSequence, SMTP, Timeouts, TransportProtocol, Transcript, Verdict};
+
+use common::{spawn_stand_in, Reply, SMTP_BANNER};
+
+
+fn spawn_greeting_stand_in(banner: bool) -> String {
+    spawn_stand_in(banner.then_some(SMTP_BANNER), |_: &[u8]| Reply::Answer(b"250 OK\r\n".to_vec())).address
+}
+
+fn replay(client: &mut Client<SMTP>) -> Transcript<SMTP> {
+    let messages = [&b"EHLO localhost\r\n"[..], b"NOOP \r\n"].iter()
+        .map(|&bytes| Message::from_bytes(SMTP, bytes))
+        .collect();
+    client.replay(&MessageSequence::from_messages(messages, vec![0.0]), &[])
+}
+
+#[test]
+fn the_banner_is_read_before_the_first_message() {
+    let mut client = Client::new(spawn_greeting_stand_in(true), TransportProtocol::TCP, SMTP);
+
+    let transcript = replay(&mut client);
+
+    assert_eq!(transcript.outcome.verdict, Verdict::Reply);
+    let greeting = transcript.greeting.unwrap();
+    assert_eq!(greeting.received, b"220 localhost ESMTP\r\n");
+    assert_eq!(greeting.state.status_code, 220);
+
+    let received: Vec<&[u8]> = transcript.entries.iter().map(|entry| entry.received.as_slice()).collect();
+    assert_eq!(received, [&b"250 OK\r\n"[..], b"250 OK\r\n"]);
+}
+
+#[test]
+fn a_target_which_does_not_greet_hangs_before_the_first_message() {
+    let mut client = Client::new(spawn_greeting_stand_in(false), TransportProtocol::TCP, SMTP);
+    client.transport_options.timeouts = Timeouts { read: 0.2, ..Timeouts::default() };
+
+    let start = Instant::now();
+    let transcript = replay(&mut client);
+
+    assert_eq!(transcript.outcome.verdict, Verdict::Hang);
+    assert_eq!(transcript.outcome.message_index, 0);
+    assert!(transcript.outcome.detail.contains("no greeting"));
+    assert!(transcript.greeting.is_none());
+    assert!(transcript.entries.is_empty());
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn reading_the_greeting_can_be_turned_off() {
+    let mut client = Client::new(spawn_greeting_stand_in(false), TransportProtocol::TCP, SMTP);
+    client.greeting = false;
+
+    let transcript = replay(&mut client);
+
+    assert_eq!(transcript.outcome.verdict, Verdict::Reply);
+    assert!(transcript.greeting.is_none());
+    assert_eq!(transcript.entries.len(), 2);
+}
@@ -0,0 +1,117 @@
+// The in-process transport, driving the srv crate's Session directly instead of a Server on a
+// socket. Every connection gets a fresh Session, so each sequence starts in the Initial state
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use fuzz_client::{Client, Framing, FuzzConfig, GreetingProtocol, HandlerFactory, Message, MessageSequence, TransportOptions, TransportProtocol, Verdict};
+
+
+// A request in the srv wire format: a 4 byte header, the payload length and the payload
+fn request(header: &[u8; 4], payload: &str) -> Vec<u8> {
+    let mut bytes = header.to_vec();
+    bytes.extend((payload.len() as u64).to_be_bytes());
+    bytes.extend(payload.as_bytes());
+    bytes
+}
+
+// srv's Session as a Handler, a new one for every connection
+fn session() -> HandlerFactory {
+    HandlerFactory::new(|| {
+        let mut session = srv::Session::new();
+        move |request: &[u8]| session.handle(request)
+    })
+}
+
+fn options(handler: HandlerFactory) -> TransportOptions {
+    TransportOptions { handler: Some(handler), ..TransportOptions::default() }
+}
+
+fn client(handler: HandlerFactory) -> Client<GreetingProtocol> {
+    let mut client = Client::new(String::from("srv"), TransportProtocol::InProcess, GreetingProtocol);
+    client.transport_options = options(handler);
+    client
+}
+
+fn sequence(requests: &[Vec<u8>]) -> MessageSequence<GreetingProtocol> {
+    let messages = requests.iter().map(|bytes| Message::from_bytes(GreetingProtocol, bytes)).collect();
+    MessageSequence::from_messages(messages, vec![0.0; requests.len().saturating_sub(1)])
+}
+
+#[test]
+fn replays_a_session_without_a_socket() {
+    let mut client = client(session());
+    let message_sequence = sequence(&[
+        request(b"HELO", "Hello!\n"),
+        request(b"TIME", "What time is it?\n"),
+        request(b"TIME", "Hello!\n"),
+        request(b"BYE_", "Goodbye!\n"),
+    ]);
+
+    let transcript = client.replay(&message_sequence, &[]);
+    assert_eq!(transcript.outcome.verdict, Verdict::Reply);
+
+    let received: Vec<&[u8]> = transcript.entries.iter().map(|entry| entry.received.as_slice()).collect();
+    assert_eq!(received, [
+        &b"200;OK;Hello, client!\n"[..],
+        b"200;OK;The time is now.\n",
+        b"400;ERROR;Header mismatch\n",
+        b"200;OK;Goodbye, client!\n",
+    ]);
+}
+
+#[test]
+fn every_connection_starts_a_new_session() {
+    let created = Arc::new(AtomicUsize::new(0));
+    let counter = created.clone();
+    let factory = HandlerFactory::new(move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut session = srv::Session::new();
+        move |request: &[u8]| session.handle(request)
+    });
+
+    let mut first = TransportProtocol::InProcess.connect("srv", &options(factory.clone())).unwrap();
+    let mut second = TransportProtocol::InProcess.connect("srv", &options(factory)).unwrap();
+    assert_eq!(created.load(Ordering::SeqCst), 2);
+
+    let framing = Framing::line();
+    first.send(&request(b"HELO", "Hello!\n")).unwrap();
+    assert_eq!(first.receive(&framing, Duration::from_secs(5)).unwrap().data, b"200;OK;Hello, client!\n");
+
+    // Goodbye is only answered after Hello, which the second session has not seen
+    first.send(&request(b"BYE_", "Goodbye!\n")).unwrap();
+    second.send(&request(b"BYE_", "Goodbye!\n")).unwrap();
+    assert_eq!(first.receive(&framing, Duration::from_secs(5)).unwrap().data, b"200;OK;Goodbye, client!\n");
+    assert_eq!(second.receive(&framing, Duration::from_secs(5)).unwrap().data, b"400;ERROR;Invalid state transition\n");
+}
+
+#[test]
+fn a_silent_handler_hangs_and_a_panicking_one_crashes() {
+    let mut client = client(HandlerFactory::new(|| |_: &[u8]| Vec::new()));
+    let transcript = client.replay(&sequence(&[request(b"HELO", "Hello!\n")]), &[]);
+    assert_eq!(transcript.outcome.verdict, Verdict::Hang);
+
+    let mut client = self::client(HandlerFactory::new(|| |request: &[u8]| {
+        assert!(request.starts_with(b"HELO"), "unexpected request");
+        request.to_vec()
+    }));
+    let transcript = client.replay(&sequence(&[request(b"HELO", "Hello!\n"), request(b"BYE_", "Goodbye!\n")]), &[]);
+    assert!(transcript.outcome.verdict.is_crash());
+    assert_eq!(transcript.entries[0].received, request(b"HELO", "Hello!\n"));
+}
+
+#[test]
+fn fuzzes_a_session_in_process() {
+    let output_directory = std::env::temp_dir().join(format!("fuzz_client_in_process_{}", std::process::id()));
+    let mut client = client(session());
+    client.output_directory = output_directory.clone();
+
+    let mut config = FuzzConfig::new();
+    config.generations = 3;
+    let result = client.fuzz(config, false);
+    let fitness = std::fs::read_to_string(output_directory.join("fitness.csv"));
+    std::fs::remove_dir_all(&output_directory).unwrap();
+
+    result.unwrap();
+    assert_eq!(fitness.unwrap().lines().count(), 4);
+}
@@ -0,0 +1,61 @@
+// Several sequences over one connection, against a local SMTP stand-in which greets, answers
+// RSET with "250 reset", QUIT with "221 bye" before closing, and everything else with "250 OK"
+mod common;
+
+use fuzz_client::{MessageSequence, SMTP, Trace, Verdict};
+
+use common::{sequence, spawn_smtp_stand_in, Reply};
+
+
+// The traces of the corpus and the number of connections the stand-in accepted for them
+fn run(corpus: Vec<MessageSequence<SMTP>>, sequences_per_connection: usize, reset_between_sequences: bool) -> (Vec<Trace<SMTP>>, usize) {
+    let stand_in = spawn_smtp_stand_in(|request: &[u8]| match request {
+        b"RSET\r\n" => Reply::Answer(b"250 reset\r\n".to_vec()),
+        b"QUIT\r\n" => Reply::AnswerAndHangUp(b"221 bye\r\n".to_vec()),
+        _ => Reply::Answer(b"250 OK\r\n".to_vec()),
+    });
+    let traces = common::run(&stand_in, corpus, |client| {
+        client.sequences_per_connection = sequences_per_connection;
+        client.reset_between_sequences = reset_between_sequences;
+    });
+    (traces, stand_in.connections())
+}
+
+#[test]
+fn sequences_share_a_connection_with_a_reset_in_between() {
+    let noop = sequence(&[b"EHLO localhost\r\n", b"NOOP \r\n"]);
+    let (traces, connections) = run(vec![noop.clone(), noop.clone(), noop.clone(), noop], 2, true);
+
+    assert_eq!(connections, 2);
+    assert!(traces.iter().all(|trace| trace.outcome.verdict == Verdict::Reply));
+
+    // Every second sequence continues the session of the one before, after RSET
+    assert!(traces[0].greeting.is_some() && traces[0].reset.is_none());
+    assert!(traces[1].greeting.is_none() && traces[1].continued_from.as_ref().unwrap().status_code == 250);
+    let (reset_message, reset_response) = traces[1].reset.as_ref().unwrap();
+    assert_eq!(reset_message.data, b"RSET\r\n");
+    assert_eq!(reset_response.data, b"250 reset\r\n");
+    assert!(traces[2].greeting.is_some() && traces[2].continued_from.is_none());
+}
+
+#[test]
+fn the_reset_message_can_be_left_out() {
+    let noop = sequence(&[b"NOOP \r\n"]);
+    let (traces, connections) = run(vec![noop.clone(), noop.clone(), noop], 3, false);
+
+    assert_eq!(connections, 1);
+    assert!(traces.iter().skip(1).all(|trace| trace.reset.is_none() && trace.continued_from.is_some()));
+    assert_eq!(traces[2].interactions[0].1.data, b"250 OK\r\n");
+}
+
+#[test]
+fn a_session_ended_by_the_sequence_is_replaced_by_a_new_connection() {
+    let quit = sequence(&[b"NOOP \r\n", b"QUIT\r\n"]);
+    let noop = sequence(&[b"NOOP \r\n"]);
+    let (traces, connections) = run(vec![quit, noop], 5, true);
+
+    assert_eq!(connections, 2);
+    assert_eq!(traces[1].outcome.verdict, Verdict::Reply);
+    assert!(traces[1].greeting.is_some() && traces[1].continued_from.is_none());
+    assert_eq!(traces[1].interactions[0].1.data, b"250 OK\r\n");
+}
@@ -0,0 +1,46 @@
+// The replay commands saved with reproducers, which should run as printed or say why they cannot
+use std::path::{Path, PathBuf};
+
+use fuzz_client::{MessageSequence, Outcome, Reproducer, SMTP, TransportProtocol};
+
+
+fn replay_command(transport: TransportProtocol, campaign_file: Option<&Path>) -> String {
+    let path = std::env::temp_dir().join(format!("fuzz_client_reproducer_{}.json", std::process::id()));
+    let mut reproducer: Reproducer<SMTP> = Reproducer {
+        target: String::from("127.0.0.1:8025"),
+        transport,
+        outcome: Outcome::new(),
+        replay_command: String::new(),
+        sequence: MessageSequence::new(),
+        states: Vec::new(),
+    };
+    reproducer.save_as(&path, "smtp", campaign_file).unwrap();
+    let saved: Reproducer<SMTP> = Reproducer::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    saved.replay_command.replace(&path.display().to_string(), "crash.json")
+}
+
+#[test]
+fn socket_targets_replay_from_the_command_line() {
+    assert_eq!(
+        replay_command(TransportProtocol::UnixStream, None),
+        "fuzz_client replay --protocol smtp --target 127.0.0.1:8025 --transport unix-stream --sequence crash.json",
+    );
+}
+
+#[test]
+fn pipe_and_tls_targets_replay_with_their_campaign_file() {
+    let campaign_file = PathBuf::from("campaigns/smtp.toml");
+    assert_eq!(
+        replay_command(TransportProtocol::TLS, Some(&campaign_file)),
+        "fuzz_client replay --config campaigns/smtp.toml --protocol smtp --target 127.0.0.1:8025 --transport tls --sequence crash.json",
+    );
+    assert!(replay_command(TransportProtocol::Pipe, None).starts_with("fuzz_client replay --config <campaign file of the run> --sequence"));
+}
+
+#[test]
+fn in_process_and_custom_targets_replay_via_the_library() {
+    assert!(replay_command(TransportProtocol::InProcess, None).starts_with("replay via library"));
+    let campaign_file = PathBuf::from("campaigns/serial.toml");
+    assert!(replay_command(TransportProtocol::Custom(String::from("serial")), Some(&campaign_file)).starts_with("replay via library"));
+}
@@ -0,0 +1,130 @@
+// Parsing AddressSanitizer and UndefinedBehaviorSanitizer reports out of a target's stderr and
+// grouping them into buckets by bug class and top stack frames
+use fuzz_client::SanitizerReport;
+
+
+const STACK_DEPTH: usize = 3;
+
+// A heap overflow in parse_command, with the process id, addresses and allocation trace an
+// actual report would have
+fn heap_overflow(pid: u32, address: &str) -> String {
+    format!("\
+220 smtpd ready
+=================================================================
+=={pid}==ERROR: AddressSanitizer: heap-buffer-overflow on address 0x{address} at pc 0x55d0c8e4a1b2 bp 0x7ffd5b8f2a10 sp 0x7ffd5b8f2a08
+READ of size 1 at 0x{address} thread T0
+    #0 0x55d0c8e4a1b1 in parse_command /src/smtpd.c:120:9
+    #1 0x55d0c8e4b3c4 in handle_client /src/smtpd.c:310:5
+    #2 0x55d0c8e4c5d6 in main /src/smtpd.c:402:3
+    #3 0x7f3a1c029d8f  (/lib/x86_64-linux-gnu/libc.so.6+0x29d8f)
+
+0x{address} is located 0 bytes after 1-byte region [0x602000000010,0x602000000011)
+allocated by thread T0 here:
+    #0 0x55d0c8e0f2a7 in malloc (/src/smtpd+0xcf2a7)
+    #1 0x55d0c8e4a0f0 in read_line /src/smtpd.c:88:17
+
+SUMMARY: AddressSanitizer: heap-buffer-overflow /src/smtpd.c:120:9 in parse_command
+=={pid}==ABORTING
+", pid = pid, address = address)
+}
+
+const USE_AFTER_FREE: &str = "\
+==4711==ERROR: AddressSanitizer: heap-use-after-free on address 0x603000000040 at pc 0x55d0c8e4a300 bp 0x7ffd5b8f2a10 sp 0x7ffd5b8f2a08
+WRITE of size 8 at 0x603000000040 thread T0
+    #0 0x55d0c8e4a2ff in reset_session /src/smtpd.c:210:14
+    #1 0x55d0c8e4b3c4 in handle_client /src/smtpd.c:310:5
+    #2 0x55d0c8e4c5d6 in main /src/smtpd.c:402:3
+";
+
+const SIGNED_OVERFLOW: &str = "\
+250 OK
+/src/smtpd.c:151:22: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+";
+
+const SIGNED_OVERFLOW_WITH_STACK: &str = "\
+/src/smtpd.c:151:22: runtime error: signed integer overflow: 2147483647 + 1 cannot be represented in type 'int'
+    #0 0x55d0c8e4a7a0 in parse_size /src/smtpd.c:151:22
+    #1 0x55d0c8e4b3c4 in handle_client /src/smtpd.c:310:5
+SUMMARY: UndefinedBehaviorSanitizer: undefined-behavior /src/smtpd.c:151:22 in
+";
+
+fn parse(stderr: &str) -> SanitizerReport {
+    SanitizerReport::parse(stderr, STACK_DEPTH).unwrap()
+}
+
+#[test]
+fn an_asan_report_gives_its_bug_class_and_top_frames() {
+    let report = parse(&heap_overflow(1234, "602000000011"));
+
+    assert_eq!(report.sanitizer, "AddressSanitizer");
+    assert_eq!(report.bug_class, "heap-buffer-overflow");
+    // Only the trace of the access counts, not the one of the allocation
+    assert_eq!(report.frames, vec![
+        "parse_command /src/smtpd.c:120:9",
+        "handle_client /src/smtpd.c:310:5",
+        "main /src/smtpd.c:402:3",
+    ]);
+    assert!(report.bucket().starts_with("heap-buffer-overflow-"));
+    assert_eq!(report.bucket(), format!("heap-buffer-overflow-{:016x}", report.stack_hash));
+}
+
+#[test]
+fn a_frame_without_a_function_keeps_its_module() {
+    let report = SanitizerReport::parse(&heap_overflow(1234, "602000000011"), 4).unwrap();
+    assert_eq!(report.frames[3], "(/lib/x86_64-linux-gnu/libc.so.6+0x29d8f)");
+}
+
+#[test]
+fn a_ubsan_report_without_a_stack_trace_is_named_by_its_location() {
+    let report = parse(SIGNED_OVERFLOW);
+
+    assert_eq!(report.sanitizer, "UndefinedBehaviorSanitizer");
+    assert_eq!(report.bug_class, "signed integer overflow");
+    assert_eq!(report.frames, vec!["/src/smtpd.c:151:22"]);
+    assert_eq!(report.bucket(), format!("signed-integer-overflow-{:016x}", report.stack_hash));
+}
+
+#[test]
+fn a_ubsan_report_with_a_stack_trace_is_named_by_its_frames() {
+    let report = parse(SIGNED_OVERFLOW_WITH_STACK);
+
+    assert_eq!(report.bug_class, "signed integer overflow");
+    assert_eq!(report.frames, vec!["parse_size /src/smtpd.c:151:22", "handle_client /src/smtpd.c:310:5"]);
+}
+
+#[test]
+fn stderr_without_a_report_has_none() {
+    assert_eq!(SanitizerReport::parse("220 smtpd ready\nSegmentation fault\n", STACK_DEPTH), None);
+    assert_eq!(SanitizerReport::parse("", STACK_DEPTH), None);
+}
+
+#[test]
+fn reports_which_differ_only_in_addresses_share_a_bucket() {
+    let first = parse(&heap_overflow(1234, "602000000011"));
+    let second = parse(&heap_overflow(98765, "6020000000f1").replace("0x55d0c8e4", "0x5631aa02"));
+
+    assert_eq!(first.stack_hash, second.stack_hash);
+    assert_eq!(first.bucket(), second.bucket());
+}
+
+#[test]
+fn another_top_frame_is_another_bucket() {
+    let overflow = parse(&heap_overflow(1234, "602000000011"));
+    let moved = parse(&heap_overflow(1234, "602000000011").replace("parse_command /src/smtpd.c:120:9", "parse_argument /src/smtpd.c:133:9"));
+    assert_ne!(overflow.bucket(), moved.bucket());
+
+    // The same frames with another bug class are another bug as well
+    let use_after_free = parse(USE_AFTER_FREE);
+    let same_frames = parse(&USE_AFTER_FREE.replace("heap-use-after-free", "heap-buffer-overflow"));
+    assert_ne!(use_after_free.stack_hash, same_frames.stack_hash);
+
+    // Frames below the stack depth do not matter
+    let deeper = parse(&heap_overflow(1234, "602000000011").replace("libc.so.6+0x29d8f", "libc.so.6+0x29e40"));
+    assert_eq!(overflow.bucket(), deeper.bucket());
+}
+
+// The bucket names directories of earlier runs, so the hash of a report must never change
+#[test]
+fn bucket_names_stay_the_same_across_builds() {
+    assert_eq!(parse(SIGNED_OVERFLOW).bucket(), "signed-integer-overflow-a90a036b20c80d41");
+}
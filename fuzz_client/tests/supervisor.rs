@@ -0,0 +1,85 @@
+// Launching and restarting a target, here a small Python SMTP stand-in which answers every
+// command with "250 OK" and kills itself with SIGSEGV when it is sent CRASH
+mod common;
+
+use std::net::TcpListener;
+
+use fuzz_client::{Client, ProcessSettings, SMTP, Supervisor, TransportProtocol, Verdict};
+
+use common::sequence;
+
+
+const SIGSEGV: i32 = 11;
+
+const TARGET: &str = r#"
+import os, signal, socket, sys
+
+server = socket.socket()
+server.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+server.bind(("127.0.0.1", int(sys.argv[1])))
+server.listen()
+while True:
+    connection, _ = server.accept()
+    try:
+        connection.sendall(b"220 localhost ESMTP\r\n")
+        for line in connection.makefile("rb"):
+            if line.startswith(b"CRASH"):
+                sys.stderr.write("segmentation fault on CRASH\n")
+                sys.stderr.flush()
+                os.kill(os.getpid(), signal.SIGSEGV)
+            connection.sendall(b"250 OK\r\n")
+    except OSError:
+        pass
+    connection.close()
+"#;
+
+// A client for the stand-in, which its supervisor starts on a free port
+fn client() -> Client<SMTP> {
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let address = format!("127.0.0.1:{}", port);
+
+    let mut settings = ProcessSettings::new(String::from("python3"));
+    settings.args = vec![String::from("-c"), String::from(TARGET), port.to_string()];
+
+    let mut client = Client::new(address.clone(), TransportProtocol::TCP, SMTP);
+    client.supervisor = Some(Supervisor::new(settings, address, TransportProtocol::TCP));
+    client
+}
+
+#[test]
+fn a_target_killed_by_a_signal_is_recorded_and_restarted() {
+    let mut client = client();
+    client.corpus = vec![sequence(&[b"NOOP\r\n"]), sequence(&[b"NOOP\r\n", b"CRASH\r\n"]), sequence(&[b"NOOP\r\n"])];
+    let traces = client.run_corpus();
+
+    assert_eq!(traces[0].outcome.verdict, Verdict::Reply);
+    assert!(traces[0].outcome.process_exit.is_none());
+
+    let outcome = &traces[1].outcome;
+    assert_eq!(outcome.verdict, Verdict::Died);
+    assert_eq!(outcome.message_index, 1);
+    let process_exit = outcome.process_exit.as_ref().unwrap();
+    assert_eq!(process_exit.signal, Some(SIGSEGV));
+    assert_eq!(process_exit.code, None);
+    assert!(process_exit.is_crash());
+    assert!(process_exit.stderr.contains("segmentation fault on CRASH"), "{}", process_exit.stderr);
+    assert!(outcome.detail.starts_with(&format!("target killed by signal {}", SIGSEGV)), "{}", outcome.detail);
+
+    // The next sequence runs against a fresh target
+    assert_eq!(traces[2].outcome.verdict, Verdict::Reply);
+    assert_eq!(traces[2].interactions.last().unwrap().1.data, b"250 OK\r\n");
+    assert_eq!(client.supervisor.as_ref().unwrap().exits, 1);
+}
+
+#[test]
+fn a_target_which_cannot_start_refuses_the_sequence() {
+    let mut client = client();
+    let mut settings = ProcessSettings::new(String::from("python3"));
+    settings.args = vec![String::from("-c"), String::from("import sys; sys.stderr.write('no config'); sys.exit(2)")];
+    client.supervisor = Some(Supervisor::new(settings, String::from("127.0.0.1:1"), TransportProtocol::TCP));
+    client.corpus = vec![sequence(&[b"NOOP\r\n"])];
+
+    let traces = client.run_corpus();
+    assert_eq!(traces[0].outcome.verdict, Verdict::Refused);
+    assert_eq!(traces[0].outcome.detail, "start target: target exited with code 2: no config");
+}
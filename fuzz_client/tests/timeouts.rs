@@ -0,0 +1,71 @@
+// Read and sequence timeouts against a local stand-in target, which answers Hello right away
+// and takes 600 ms to answer Goodbye
+mod common;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fuzz_client::{Client, GreetingProtocol, Message, MessageSequence, Timeouts, TransportProtocol, Transcript, Verdict};
+
+use common::{spawn_stand_in, Reply};
+
+
+fn spawn_slow_stand_in() -> String {
+    let stand_in = spawn_stand_in(None, |request: &[u8]| {
+        if request.ends_with(b"Goodbye!\n") {
+            thread::sleep(Duration::from_millis(600));
+        }
+        Reply::Answer(b"200;OK;Answer\n".to_vec())
+    });
+    stand_in.address
+}
+
+// A request in the greeting wire format: a 4 byte header, the payload length and the payload
+fn request(header: &[u8; 4], payload: &str) -> Message<GreetingProtocol> {
+    let mut bytes = header.to_vec();
+    bytes.extend((payload.len() as u64).to_be_bytes());
+    bytes.extend(payload.as_bytes());
+    Message::from_bytes(GreetingProtocol, &bytes)
+}
+
+fn replay(timeouts: Timeouts, messages: Vec<Message<GreetingProtocol>>, timings: Vec<f32>) -> Transcript<GreetingProtocol> {
+    let mut client = Client::new(spawn_slow_stand_in(), TransportProtocol::TCP, GreetingProtocol);
+    client.transport_options.timeouts = timeouts;
+    client.replay(&MessageSequence::from_messages(messages, timings), &[])
+}
+
+#[test]
+fn a_short_read_timeout_turns_a_slow_answer_into_a_hang() {
+    let timeouts = Timeouts { read: 0.2, ..Timeouts::default() };
+
+    let start = Instant::now();
+    let transcript = replay(timeouts, vec![request(b"HELO", "Hello!\n"), request(b"BYE_", "Goodbye!\n")], vec![0.0]);
+
+    assert_eq!(transcript.outcome.verdict, Verdict::Hang);
+    assert_eq!(transcript.outcome.message_index, 1);
+    assert!(start.elapsed() < Duration::from_millis(600));
+}
+
+#[test]
+fn message_types_can_have_their_own_read_timeout() {
+    let mut timeouts = Timeouts { read: 0.2, ..Timeouts::default() };
+    timeouts.messages.insert(String::from("Goodbye"), 2.0);
+
+    let transcript = replay(timeouts, vec![request(b"HELO", "Hello!\n"), request(b"BYE_", "Goodbye!\n")], vec![0.0]);
+
+    assert_eq!(transcript.outcome.verdict, Verdict::Reply);
+    assert_eq!(transcript.entries[1].received, b"200;OK;Answer\n");
+}
+
+#[test]
+fn a_sequence_which_runs_too_long_is_cut_short() {
+    let timeouts = Timeouts { sequence: Some(0.3), ..Timeouts::default() };
+    let messages = vec![request(b"HELO", "Hello!\n"); 3];
+
+    let transcript = replay(timeouts, messages, vec![0.2, 0.2]);
+
+    assert_eq!(transcript.outcome.verdict, Verdict::Hang);
+    assert_eq!(transcript.outcome.message_index, 2);
+    assert!(transcript.outcome.detail.contains("sequence took longer"));
+    assert_eq!(transcript.entries.len(), 2);
+}
@@ -0,0 +1,150 @@
+// TLS and STARTTLS against local stand-in targets with a self-signed certificate for localhost.
+// The TLS target echoes every message, the SMTP target greets and answers EHLO and STARTTLS in plain text
+// and "250 secure" to everything sent inside TLS
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rcgen::CertifiedKey;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+
+use fuzz_client::{Client, Framing, Message, MessageSequence, SMTP, TlsConnector, TlsSettings, Transport, TransportOptions, TransportProtocol, Verdict};
+
+
+fn server_config(certified_key: &CertifiedKey) -> Arc<ServerConfig> {
+    let certificate: CertificateDer<'static> = certified_key.cert.der().clone();
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified_key.key_pair.serialize_der()));
+
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(vec![certificate], key)
+        .unwrap();
+    Arc::new(config)
+}
+
+// Accept connections until the test ends, each handled by serve on its own thread
+fn spawn_target(serve: fn(TcpStream, Arc<ServerConfig>)) -> (String, CertifiedKey) {
+    let certified_key = rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+    let config = server_config(&certified_key);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+
+    thread::spawn(move || {
+        for tcp_stream in listener.incoming().flatten() {
+            let config = config.clone();
+            thread::spawn(move || serve(tcp_stream, config));
+        }
+    });
+
+    (address, certified_key)
+}
+
+fn serve_echo(tcp_stream: TcpStream, config: Arc<ServerConfig>) {
+    let mut tls_stream = StreamOwned::new(ServerConnection::new(config).unwrap(), tcp_stream);
+    let mut buffer = [0u8; 4096];
+
+    while let Ok(n @ 1..) = tls_stream.read(&mut buffer) {
+        if tls_stream.write_all(&buffer[..n]).is_err() {
+            break;
+        }
+    }
+}
+
+fn serve_smtp(mut tcp_stream: TcpStream, config: Arc<ServerConfig>) {
+    let mut buffer = [0u8; 4096];
+    tcp_stream.write_all(b"220 localhost ESMTP\r\n").unwrap();
+
+    while let Ok(n @ 1..) = tcp_stream.read(&mut buffer) {
+        let command = &buffer[..n];
+        if command.starts_with(b"EHLO") {
+            tcp_stream.write_all(b"250-localhost\r\n250 STARTTLS\r\n").unwrap();
+        } else if command.starts_with(b"STARTTLS") {
+            tcp_stream.write_all(b"220 ready to start TLS\r\n").unwrap();
+            let mut tls_stream = StreamOwned::new(ServerConnection::new(config).unwrap(), tcp_stream);
+            while let Ok(1..) = tls_stream.read(&mut buffer) {
+                if tls_stream.write_all(b"250 secure\r\n").is_err() {
+                    break;
+                }
+            }
+            return;
+        } else {
+            tcp_stream.write_all(b"250 plain\r\n").unwrap();
+        }
+    }
+}
+
+fn connector(settings: TlsSettings) -> TlsConnector {
+    TlsConnector::new(&settings).unwrap()
+}
+
+fn accept_any() -> TlsConnector {
+    connector(TlsSettings { accept_any_certificate: true, ..TlsSettings::default() })
+}
+
+fn options(tls: TlsConnector) -> TransportOptions {
+    TransportOptions { tls: Some(tls), ..TransportOptions::default() }
+}
+
+fn exchange(transport: &mut dyn Transport, request: &[u8]) -> Vec<u8> {
+    transport.send(request).unwrap();
+    transport.receive(&Framing::line(), Duration::from_secs(5)).unwrap().data
+}
+
+#[test]
+fn accepts_any_certificate_when_asked() {
+    let (address, _) = spawn_target(serve_echo);
+    let mut transport = TransportProtocol::TLS.connect(&address, &options(accept_any())).unwrap();
+
+    assert_eq!(exchange(transport.as_mut(), b"hello\n"), b"hello\n");
+    assert_eq!(exchange(transport.as_mut(), b"again\n"), b"again\n");
+}
+
+#[test]
+fn verifies_against_a_ca_file() {
+    let (address, certified_key) = spawn_target(serve_echo);
+    let ca_file = std::env::temp_dir().join(format!("fuzz_client_tls_{}.pem", std::process::id()));
+    fs::write(&ca_file, certified_key.cert.pem()).unwrap();
+
+    let tls = connector(TlsSettings {
+        ca_file: Some(ca_file.clone()),
+        server_name: Some(String::from("localhost")),
+        ..TlsSettings::default()
+    });
+    let result = TransportProtocol::TLS.connect(&address, &options(tls));
+    fs::remove_file(&ca_file).unwrap();
+
+    assert_eq!(exchange(result.unwrap().as_mut(), b"trusted\n"), b"trusted\n");
+}
+
+#[test]
+fn rejects_an_untrusted_certificate() {
+    let (address, _) = spawn_target(serve_echo);
+    let tls = connector(TlsSettings { server_name: Some(String::from("localhost")), ..TlsSettings::default() });
+
+    assert!(TransportProtocol::TLS.connect(&address, &options(tls)).is_err());
+}
+
+#[test]
+fn smtp_sequence_continues_inside_starttls() {
+    let (address, _) = spawn_target(serve_smtp);
+    let mut client = Client::new(address, TransportProtocol::TCP, SMTP);
+    client.transport_options = options(accept_any());
+
+    let messages = [&b"EHLO localhost\r\n"[..], b"STARTTLS\r\n", b"NOOP \r\n", b"NOOP \r\n"].iter()
+        .map(|&bytes| Message::from_bytes(SMTP, bytes))
+        .collect();
+    let message_sequence = MessageSequence::from_messages(messages, vec![0.0; 3]);
+
+    let transcript = client.replay(&message_sequence, &[]);
+    assert_eq!(transcript.outcome.verdict, Verdict::Reply);
+
+    let received: Vec<&[u8]> = transcript.entries.iter().map(|entry| entry.received.as_slice()).collect();
+    assert_eq!(received, [&b"250-localhost\r\n250 STARTTLS\r\n"[..], b"220 ready to start TLS\r\n", b"250 secure\r\n", b"250 secure\r\n"]);
+}
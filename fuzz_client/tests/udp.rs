@@ -0,0 +1,109 @@
+// UDP transport against a local stand-in target, which answers every datagram depending on
+// what it starts with: "MULTI" gets three datagrams, "QUIET" none, "COUNT" the number of
+// datagrams seen so far and anything else is echoed back
+mod common;
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use fuzz_client::{Client, Framing, GreetingProtocol, Message, MessageSequence, Transport, TransportOptions, TransportProtocol, UdpSettings, Verdict};
+
+use common::spawn_udp_stand_in;
+
+
+fn spawn_stand_in() -> String {
+    let mut count = 0;
+    spawn_udp_stand_in(move |request: &[u8]| {
+        count += 1;
+        if request.starts_with(b"MULTI") {
+            vec![b"one;".to_vec(), b"two;".to_vec(), b"three".to_vec()]
+        } else if request.starts_with(b"COUNT") {
+            vec![count.to_string().into_bytes()]
+        } else if request.starts_with(b"QUIET") {
+            Vec::new()
+        } else {
+            vec![request.to_vec()]
+        }
+    })
+}
+
+// The address of a port nothing listens on, so the kernel answers with ICMP port unreachable
+fn closed_address() -> String {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.local_addr().unwrap().to_string()
+}
+
+fn options(udp: UdpSettings) -> TransportOptions {
+    TransportOptions { udp, ..TransportOptions::default() }
+}
+
+fn exchange(transport: &mut dyn Transport, request: &[u8]) -> Result<Vec<u8>, fuzz_client::Error> {
+    transport.send(request)?;
+    transport.receive(&Framing::line(), Duration::from_secs(5)).map(|response| response.data)
+}
+
+#[test]
+fn binds_an_ephemeral_port_per_connection() {
+    let address = spawn_stand_in();
+    let mut first = TransportProtocol::UDP.connect(&address, &TransportOptions::default()).unwrap();
+    let mut second = TransportProtocol::UDP.connect(&address, &TransportOptions::default()).unwrap();
+
+    assert_eq!(exchange(first.as_mut(), b"hello").unwrap(), b"hello");
+    assert_eq!(exchange(second.as_mut(), b"world").unwrap(), b"world");
+}
+
+#[test]
+fn collects_every_datagram_of_a_response() {
+    let address = spawn_stand_in();
+    let mut transport = TransportProtocol::UDP.connect(&address, &TransportOptions::default()).unwrap();
+
+    assert_eq!(exchange(transport.as_mut(), b"MULTI").unwrap(), b"one;two;three");
+    // Nothing of the previous response is left over for the next one
+    assert_eq!(exchange(transport.as_mut(), b"COUNT").unwrap(), b"2");
+}
+
+#[test]
+fn silence_is_an_empty_response_unless_a_reply_is_expected() {
+    let address = spawn_stand_in();
+    let mut transport = TransportProtocol::UDP.connect(&address, &TransportOptions::default()).unwrap();
+    assert_eq!(exchange(transport.as_mut(), b"QUIET").unwrap(), b"");
+
+    let udp_settings = UdpSettings { expect_reply: true, ..UdpSettings::default() };
+    let mut transport = TransportProtocol::UDP.connect(&address, &options(udp_settings)).unwrap();
+    let error = exchange(transport.as_mut(), b"QUIET").unwrap_err();
+    assert_eq!(Verdict::from_error(&error), Verdict::Hang);
+}
+
+#[test]
+fn truncates_datagrams_to_the_receive_buffer() {
+    let address = spawn_stand_in();
+    let udp_settings = UdpSettings { receive_buffer: 4, ..UdpSettings::default() };
+    let mut transport = TransportProtocol::UDP.connect(&address, &options(udp_settings)).unwrap();
+
+    assert_eq!(exchange(transport.as_mut(), b"truncated").unwrap(), b"trun");
+}
+
+#[test]
+fn port_unreachable_is_refused() {
+    let mut client = Client::new(closed_address(), TransportProtocol::UDP, GreetingProtocol);
+    let message_sequence = MessageSequence::from_messages(vec![Message::from_bytes(GreetingProtocol, b"hello")], Vec::new());
+
+    let transcript = client.replay(&message_sequence, &[]);
+    assert_eq!(transcript.outcome.verdict, Verdict::Refused);
+    assert!(transcript.outcome.verdict.is_crash());
+}
+
+#[test]
+fn replay_continues_past_an_unanswered_datagram() {
+    let mut client = Client::new(spawn_stand_in(), TransportProtocol::UDP, GreetingProtocol);
+    let messages = [&b"QUIET"[..], b"COUNT"].iter()
+        .map(|&bytes| Message::from_bytes(GreetingProtocol, bytes))
+        .collect();
+    let message_sequence = MessageSequence::from_messages(messages, vec![0.0]);
+
+    let transcript = client.replay(&message_sequence, &[]);
+    assert_eq!(transcript.outcome.verdict, Verdict::Reply);
+    assert_eq!(transcript.entries.len(), 2);
+    assert!(transcript.entries[0].received.is_empty());
+    assert_eq!(transcript.entries[1].received, b"2");
+}
@@ -0,0 +1,58 @@
+// Parallel workers against a local SMTP stand-in which answers "NOOP <n>" with "250 <n>", the
+// later n the sooner, so that the workers finish their sequences in reverse corpus order
+mod common;
+
+use std::thread;
+use std::time::Duration;
+
+use fuzz_client::{Campaign, Client, FuzzConfig, MessageSequence, SMTP, TransportProtocol};
+
+use common::{sequence, spawn_smtp_stand_in, Reply, StandIn};
+
+
+const SEQUENCES: usize = 8;
+
+fn spawn_stand_in() -> StandIn {
+    spawn_smtp_stand_in(|request: &[u8]| {
+        let number: u64 = String::from_utf8_lossy(request).trim().trim_start_matches("NOOP ").parse().unwrap_or(0);
+        thread::sleep(Duration::from_millis((SEQUENCES as u64 - number) * 30));
+        Reply::Answer(format!("250 {}\r\n", number).into_bytes())
+    })
+}
+
+fn corpus() -> Vec<MessageSequence<SMTP>> {
+    (0..SEQUENCES).map(|number| sequence(&[format!("NOOP {}\r\n", number).as_bytes()])).collect()
+}
+
+fn client(stand_in: &StandIn) -> Client<SMTP> {
+    let mut client = Client::new(stand_in.address.clone(), TransportProtocol::TCP, SMTP);
+    client.workers = SEQUENCES;
+    client.corpus = corpus();
+    client
+}
+
+#[test]
+fn traces_come_back_in_corpus_order() {
+    let stand_in = spawn_stand_in();
+    let traces = client(&stand_in).run_corpus();
+
+    let responses: Vec<String> = traces.iter().map(|trace| String::from_utf8_lossy(&trace.interactions[0].1.data).into_owned()).collect();
+    let expected: Vec<String> = (0..SEQUENCES).map(|number| format!("250 {}\r\n", number)).collect();
+    assert_eq!(responses, expected);
+}
+
+#[test]
+fn the_state_model_learns_the_traces_in_corpus_order() {
+    let stand_in = spawn_stand_in();
+    let mut client = client(&stand_in);
+    client.output_directory = std::env::temp_dir().join(format!("fuzz_client_workers_{}", std::process::id()));
+    client.fuzz(FuzzConfig { generations: 1, ..Campaign::default_fuzz_config() }, false).unwrap();
+    std::fs::remove_dir_all(&client.output_directory).unwrap();
+
+    // Every sequence starts from the greeting, so the transitions out of it are in the order
+    // their traces reached the state model
+    let (_, transitions) = client.state_model().inner.iter().find(|(state, _)| state.status_code == 220).unwrap();
+    let messages: Vec<&[u8]> = transitions.iter().map(|transition| transition.message.data.as_slice()).collect();
+    let expected: Vec<Vec<u8>> = corpus().iter().map(|sequence| sequence.messages[0].data.clone()).collect();
+    assert_eq!(messages, expected);
+}
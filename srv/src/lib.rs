@@ -2,8 +2,10 @@ mod state_machine;
 mod message;
 mod response;
 mod server;
+mod session;
 
 pub use state_machine::{ServerState, ServerError, StateTransitionRules, StateMachine};
 pub use message::{Message, MessageType};
 pub use response::Response;
-pub use server::Server;
\ No newline at end of file
+pub use server::Server;
+pub use session::Session;
\ No newline at end of file
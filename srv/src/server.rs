@@ -6,29 +6,23 @@ use std::net::{TcpListener, TcpStream};
 use std::io::{Read, Write};
 use std::thread;
 
-use crate::StateMachine;
-use crate::ServerState;
-use crate::StateTransitionRules;
-use crate::Message;
-use crate::Response;
+use crate::Session;
 
 
 pub struct Server {
     listener: TcpListener,
-    state_machine: StateMachine,
-    state_transition_rules: StateTransitionRules,
+    // One session serves every client in turn, so the state carries over between connections
+    session: Session,
 }
 
 impl Server {
     pub fn new(addr: &str) -> Self {
         let listener = TcpListener::bind(addr).unwrap();
-        let state_machine = StateMachine::new();
-        let state_transition_rules = StateTransitionRules::new();
+        let session = Session::new();
 
         Self {
             listener,
-            state_machine,
-            state_transition_rules,
+            session,
         }
     }
 
@@ -43,12 +37,12 @@ impl Server {
                     let message_result = self.receive_message(&mut client_stream);
 
                     match message_result {
-                        Ok(Some(message)) => {
-                            println!("CLIENT: {}", String::from_utf8_lossy(&message.data));
+                        Ok(Some(data)) => {
+                            println!("CLIENT: {}", String::from_utf8_lossy(&data));
 
-                            let response = self.state_machine.handle_message(&message, &self.state_transition_rules);
+                            let response = self.session.handle(&data);
 
-                            println!("SERVER: {}", &response.response_string);
+                            println!("SERVER: {}", String::from_utf8_lossy(&response));
 
                             if let Err(e) = self.send_response(&mut client_stream, &response) {
                                 eprintln!("Failed to write to client: {}", e);
@@ -72,9 +66,9 @@ impl Server {
             }
         }
     }
-       
 
-    fn receive_message(&mut self, mut stream: &TcpStream) -> Result<Option<Message>, std::io::Error> {
+
+    fn receive_message(&mut self, mut stream: &TcpStream) -> Result<Option<Vec<u8>>, std::io::Error> {
         let mut buffer = [0; 1024];
         let bytes_read = stream.read(&mut buffer)?;
 
@@ -83,29 +77,11 @@ impl Server {
             return Ok(None);
         }
 
-        Ok(Some(Message::new(&buffer[..bytes_read])))
+        Ok(Some(buffer[..bytes_read].to_vec()))
     }
 
-    fn send_response(&self, mut stream: &TcpStream, response: &Response) -> Result<(), std::io::Error> {
-        let mut response_string = response.response_string.clone();
-
-        if self.state_machine.current_state == ServerState::Secret1 {
-            response_string = String::from("200;OK;Secret Response1\n");
-        }
-        if self.state_machine.current_state == ServerState::Secret2 {
-            response_string = String::from("200;OK;Secret Response2\n");
-        }
-        if self.state_machine.current_state == ServerState::Secret3 {
-            response_string = String::from("200;OK;Secret Response3\n");
-        }
-        if self.state_machine.current_state == ServerState::Secret4 {
-            response_string = String::from("200;OK;Secret Response4\n");
-        }
-        if self.state_machine.current_state == ServerState::Secret5 {
-            response_string = String::from("200;OK;Secret Response5\n");
-        }
-
-        stream.write(response_string.as_bytes())?;
+    fn send_response(&self, mut stream: &TcpStream, response: &[u8]) -> Result<(), std::io::Error> {
+        stream.write_all(response)?;
         stream.flush()?;
         Ok(())
     }
@@ -0,0 +1,48 @@
+use crate::StateMachine;
+use crate::ServerState;
+use crate::StateTransitionRules;
+use crate::Message;
+
+
+// Everything the server does with one client's bytes, without the socket. The Server feeds it
+// what it reads from a connection, and a fuzzer can call it directly to skip the network.
+pub struct Session {
+    state_machine: StateMachine,
+    state_transition_rules: StateTransitionRules,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self {
+            state_machine: StateMachine::new(),
+            state_transition_rules: StateTransitionRules::new(),
+        }
+    }
+
+    pub fn current_state(&self) -> &ServerState {
+        &self.state_machine.current_state
+    }
+
+    // Parse one message, advance the state machine and return the response to send back
+    pub fn handle(&mut self, data: &[u8]) -> Vec<u8> {
+        let message = Message::new(data);
+        let response = self.state_machine.handle_message(&message, &self.state_transition_rules);
+
+        let response_string = match self.state_machine.current_state {
+            ServerState::Secret1 => String::from("200;OK;Secret Response1\n"),
+            ServerState::Secret2 => String::from("200;OK;Secret Response2\n"),
+            ServerState::Secret3 => String::from("200;OK;Secret Response3\n"),
+            ServerState::Secret4 => String::from("200;OK;Secret Response4\n"),
+            ServerState::Secret5 => String::from("200;OK;Secret Response5\n"),
+            _ => response.response_string,
+        };
+
+        response_string.into_bytes()
+    }
+}